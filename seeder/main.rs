@@ -2,14 +2,14 @@ use std::time::Instant;
 use std::io::{self, Write};
 
 use anyhow::Result;
-use chrono::{TimeZone, Utc};
+use chrono::{Datelike, NaiveDate, NaiveTime};
 use dotenvy::dotenv;
 use fake::faker::lorem::en::Sentence;
 use fake::faker::name::raw::Name;
 use fake::locales::EN;
 use fake::Fake;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
+    bson::{doc, oid::ObjectId, DateTime},
     options::ClientOptions,
     Client, Collection,
 };
@@ -22,12 +22,18 @@ fn logln(msg: &str) {
     let _ = io::stdout().flush();
 }
 
+// Fechas como Date de Mongo a medianoche UTC (igual que models::bson_date)
+fn bson_date(year: i32, month: u32, day: u32) -> Option<DateTime> {
+    let date = NaiveDate::from_ymd_opt(year, month, day)?;
+    Some(DateTime::from_millis(date.and_time(NaiveTime::MIN).and_utc().timestamp_millis()))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AuthorDoc {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     id: Option<ObjectId>,
     name: String,
-    date_of_birth: Option<String>,
+    date_of_birth: Option<DateTime>,
     country: Option<String>,
     description: Option<String>,
     image_path: Option<String>,
@@ -40,7 +46,7 @@ struct BookDoc {
     author_id: ObjectId,
     title: String,
     summary: Option<String>,
-    publication_date: Option<String>,
+    publication_date: Option<DateTime>,
     total_sales: Option<i64>,
     cover_image_path: Option<String>,
 }
@@ -146,10 +152,7 @@ async fn main() -> Result<()> {
         let year = rng.gen_range(1930..=1995);
         let month = rng.gen_range(1..=12);
         let day = rng.gen_range(1..=28);
-        let dob = Utc
-            .with_ymd_and_hms(year, month, day, 0, 0, 0)
-            .single()
-            .map(|dt| dt.format("%Y-%m-%d").to_string());
+        let dob = bson_date(year, month, day);
 
         let country = Some(countries.choose(&mut rng).unwrap().to_string());
 
@@ -190,10 +193,7 @@ async fn main() -> Result<()> {
             let pub_year = rng.gen_range(1990..=2024);
             let pub_month = rng.gen_range(1..=12);
             let pub_day = rng.gen_range(1..=28);
-            let pub_date = Utc
-                .with_ymd_and_hms(pub_year, pub_month, pub_day, 0, 0, 0)
-                .single()
-                .map(|dt| dt.format("%Y-%m-%d").to_string());
+            let pub_date = bson_date(pub_year, pub_month, pub_day);
 
             books.push(BookDoc {
                 id: Some(book_id),
@@ -246,9 +246,8 @@ async fn main() -> Result<()> {
         
         // Generate exactly 5 years of sales per book
         let pub_year = book.publication_date
-            .as_ref()
-            .and_then(|d| d.split('-').next())
-            .and_then(|y| y.parse::<i32>().ok())
+            .and_then(|d| chrono::DateTime::from_timestamp_millis(d.timestamp_millis()))
+            .map(|d| d.year())
             .unwrap_or(2020);
         
         for year_offset in 0..SALES_YEARS_PER_BOOK {
@@ -337,6 +336,7 @@ async fn main() -> Result<()> {
     logln(&format!("  • Books: {}", updated_books.len()));
     logln(&format!("  • Reviews: {} (avg {:.1} per book)", reviews.len(), reviews.len() as f64 / updated_books.len() as f64));
     logln(&format!("  • Sales: {} ({} years per book)", sales.len(), SALES_YEARS_PER_BOOK));
+    logln("\n🔥 If the app is running, refresh its cache: curl -X POST <app>/admin/cache/warmup");
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,182 @@
+// Codificación de los valores de la cache: [cabecera][cuerpo]. La cabecera
+// dice el formato y si el cuerpo va comprimido (LZ4). Los valores sin
+// cabecera son el JSON plano de antes y se siguen leyendo.
+use std::borrow::Cow;
+
+use anyhow::bail;
+use serde::{de::DeserializeOwned, Serialize};
+
+const JSON: u8 = 0x01;
+const MSGPACK: u8 = 0x02;
+// bit de cuerpo comprimido
+const COMPRESSED: u8 = 0x80;
+
+pub const DEFAULT_COMPRESS_ABOVE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+        }
+    }
+
+    fn header(&self) -> u8 {
+        match self {
+            Self::Json => JSON,
+            Self::MessagePack => MSGPACK,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Codec {
+    format: Format,
+    // comprimir cuerpos de más de N bytes (None: nunca)
+    compress_above: Option<usize>,
+}
+
+impl Codec {
+    pub fn new(format: Format, compress_above: Option<usize>) -> Self {
+        Self { format, compress_above }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        let body = match self.format {
+            Format::Json => serde_json::to_vec(value)?,
+            // con nombres de campo: tolera skip_serializing_if y campos nuevos
+            Format::MessagePack => rmp_serde::to_vec_named(value)?,
+        };
+
+        let mut header = self.format.header();
+        let body = match self.compress_above {
+            Some(min) if body.len() > min => {
+                let compressed = lz4_flex::compress_prepend_size(&body);
+                // lo que no se deja comprimir se guarda tal cual
+                if compressed.len() < body.len() {
+                    header |= COMPRESSED;
+                    compressed
+                } else {
+                    body
+                }
+            }
+            _ => body,
+        };
+
+        let mut out = Vec::with_capacity(body.len() + 1);
+        out.push(header);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    // Lee cualquier formato, no solo el configurado: al cambiar CACHE_CODEC
+    // lo que ya estaba en la cache sigue valiendo
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        let Some((&header, body)) = bytes.split_first() else { bail!("empty value") };
+        let format = header & !COMPRESSED;
+        // JSON sin cabecera: empieza por '{', '[', '"', un dígito...
+        if format != JSON && format != MSGPACK {
+            return Ok(serde_json::from_slice(bytes)?);
+        }
+
+        let body = if header & COMPRESSED != 0 {
+            Cow::Owned(lz4_flex::decompress_size_prepended(body)?)
+        } else {
+            Cow::Borrowed(body)
+        };
+        Ok(match format {
+            JSON => serde_json::from_slice(&body)?,
+            _ => rmp_serde::from_slice(&body)?,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.compress_above {
+            Some(min) => format!("{} (LZ4 above {min} bytes)", self.format.name()),
+            None => format!("{} (uncompressed)", self.format.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Page {
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        scores: Vec<i64>,
+    }
+
+    fn page(words: usize) -> Page {
+        Page { title: "Rayuela".into(), summary: Some("novela ".repeat(words)), scores: vec![5, 4, 3] }
+    }
+
+    #[test]
+    fn json_and_msgpack_round_trip() {
+        for format in [Format::Json, Format::MessagePack] {
+            let codec = Codec::new(format, None);
+            let bytes = codec.encode(&page(3)).unwrap();
+            assert_eq!(bytes[0], format.header());
+            assert_eq!(codec.decode::<Page>(&bytes).unwrap(), page(3));
+
+            // un campo omitido (skip_serializing_if) vuelve como None
+            let short = Page { summary: None, ..page(0) };
+            assert_eq!(codec.decode::<Page>(&codec.encode(&short).unwrap()).unwrap(), short);
+        }
+    }
+
+    #[test]
+    fn compresses_only_above_the_threshold() {
+        for format in [Format::Json, Format::MessagePack] {
+            let codec = Codec::new(format, Some(DEFAULT_COMPRESS_ABOVE));
+
+            let small = codec.encode(&page(3)).unwrap();
+            assert_eq!(small[0] & COMPRESSED, 0);
+
+            let large = codec.encode(&page(500)).unwrap();
+            assert_eq!(large[0], format.header() | COMPRESSED);
+            assert!(large.len() < DEFAULT_COMPRESS_ABOVE);
+            assert_eq!(codec.decode::<Page>(&large).unwrap(), page(500));
+
+            // sin umbral no se comprime nunca
+            let plain = Codec::new(format, None).encode(&page(500)).unwrap();
+            assert_eq!(plain[0] & COMPRESSED, 0);
+        }
+    }
+
+    #[test]
+    fn reads_values_written_with_another_codec() {
+        let json = Codec::new(Format::Json, Some(16)).encode(&page(100)).unwrap();
+        let msgpack = Codec::new(Format::MessagePack, None);
+        assert_eq!(msgpack.decode::<Page>(&json).unwrap(), page(100));
+    }
+
+    #[test]
+    fn reads_legacy_json_without_header() {
+        let codec = Codec::new(Format::MessagePack, Some(DEFAULT_COMPRESS_ABOVE));
+        let legacy = serde_json::to_vec(&page(3)).unwrap();
+        assert_eq!(codec.decode::<Page>(&legacy).unwrap(), page(3));
+        assert_eq!(codec.decode::<Vec<i64>>(b"[1,2,3]").unwrap(), vec![1, 2, 3]);
+        assert_eq!(codec.decode::<i64>(b"42").unwrap(), 42);
+        assert!(codec.decode::<Page>(b"").is_err());
+    }
+}
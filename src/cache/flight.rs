@@ -0,0 +1,87 @@
+// Single-flight por clave: si muchas peticiones fallan la cache a la vez,
+// solo una calcula el valor y las demás esperan a que lo deje en la cache.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+#[derive(Default)]
+pub struct SingleFlight {
+    keys: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+// Mientras vive, nadie más en el proceso calcula esa clave
+pub struct FlightGuard<'a> {
+    flights: &'a SingleFlight,
+    key: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl SingleFlight {
+    fn slot(&self, key: &str) -> Arc<AsyncMutex<()>> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.entry(key.to_string()).or_default().clone()
+    }
+
+    // Espera el turno para `key`
+    pub async fn lock(&self, key: &str) -> FlightGuard<'_> {
+        let guard = self.slot(key).lock_owned().await;
+        FlightGuard { flights: self, key: key.to_string(), guard: Some(guard) }
+    }
+
+    // Turno para `key` solo si nadie lo tiene (refrescos en segundo plano)
+    pub fn try_lock(&self, key: &str) -> Option<FlightGuard<'_>> {
+        let guard = self.slot(key).try_lock_owned().ok()?;
+        Some(FlightGuard { flights: self, key: key.to_string(), guard: Some(guard) })
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut keys = self.flights.keys.lock().unwrap_or_else(|e| e.into_inner());
+        self.guard.take();
+        // sin nadie esperando (solo queda la referencia del mapa): fuera
+        if keys.get(&self.key).is_some_and(|slot| Arc::strong_count(slot) == 1) {
+            keys.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn one_turn_per_key_at_a_time() {
+        let flights = SingleFlight::default();
+        let first = flights.lock("k").await;
+        assert!(flights.try_lock("k").is_none());
+        // otras claves no esperan
+        assert!(flights.try_lock("other").is_some());
+
+        drop(first);
+        assert!(flights.try_lock("k").is_some());
+    }
+
+    #[tokio::test]
+    async fn a_waiter_gets_the_turn_and_the_slot_is_freed() {
+        let flights = Arc::new(SingleFlight::default());
+        let first = flights.lock("k").await;
+
+        let waiter = {
+            let flights = flights.clone();
+            tokio::spawn(async move {
+                let _turn = flights.lock("k").await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+        // con alguien esperando, el turno no se borra del mapa
+        drop(first);
+        waiter.await.unwrap();
+
+        assert!(flights.keys.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+// Cache en memoria del proceso: TTL por entrada, tope de entradas y de bytes,
+// y expulsión LRU. Sirve sin Redis (una sola instancia) o como respaldo.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+
+use super::metrics::metrics;
+use super::Cache;
+
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+struct Entry {
+    value: Vec<u8>,
+    expires: Option<Instant>,
+    // último uso; clave en `Inner::lru`
+    tick: u64,
+    tags: Vec<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    // BTreeMap para poder recorrer un prefijo en del_prefix
+    entries: BTreeMap<String, Entry>,
+    // tick -> clave, de la menos a la más recientemente usada
+    lru: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+    // tag -> claves registradas con ese tag
+    tags: HashMap<String, HashSet<String>>,
+}
+
+impl Inner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.lru.remove(&entry.tick);
+        self.bytes -= key.len() + entry.value.len();
+        self.untag(key, &entry.tags);
+        Some(entry)
+    }
+
+    fn untag(&mut self, key: &str, tags: &[String]) {
+        for tag in tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+    }
+
+    // Expulsa la entrada menos usada
+    fn evict_one(&mut self) -> bool {
+        let Some((_, key)) = self.lru.pop_first() else { return false };
+        metrics().eviction(&key);
+        if let Some(entry) = self.entries.remove(&key) {
+            self.bytes -= key.len() + entry.value.len();
+            self.untag(&key, &entry.tags);
+        }
+        true
+    }
+}
+
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+    max_entries: usize,
+    max_bytes: Option<usize>,
+}
+
+impl MemoryCache {
+    pub fn new(max_entries: usize, max_bytes: Option<usize>) -> Self {
+        Self { inner: Mutex::new(Inner::default()), max_entries: max_entries.max(1), max_bytes }
+    }
+
+    // memory://?max_entries=10000&max_mb=64 (ambos opcionales)
+    pub fn from_url(url: &str) -> anyhow::Result<Self> {
+        let mut max_entries = DEFAULT_MAX_ENTRIES;
+        let mut max_bytes = None;
+
+        let query = url.split_once('?').map(|(_, q)| q).unwrap_or("");
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let n: usize = v.parse().map_err(|_| anyhow!("invalid value for {k}: {v:?}"))?;
+            match k {
+                "max_entries" => max_entries = n,
+                "max_mb" => max_bytes = Some(n * 1024 * 1024),
+                "max_bytes" => max_bytes = Some(n),
+                _ => bail!("unknown cache option {k:?}"),
+            }
+        }
+
+        Ok(Self::new(max_entries, max_bytes))
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // una entrada a medio escribir no deja el mapa inconsistente: seguimos
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn insert(&self, key: &str, value: &[u8], ttl: Option<Duration>, tags: &[String]) {
+        let size = key.len() + value.len();
+        let mut inner = self.lock();
+        inner.remove(key);
+        // una entrada que no cabe ni sola no se guarda
+        if self.max_bytes.is_some_and(|max| size > max) {
+            return;
+        }
+
+        while inner.entries.len() >= self.max_entries
+            || self.max_bytes.is_some_and(|max| inner.bytes + size > max)
+        {
+            if !inner.evict_one() {
+                break;
+            }
+        }
+
+        let tick = inner.next_tick();
+        let expires = ttl.map(|d| Instant::now() + d);
+        for tag in tags {
+            inner.tags.entry(tag.clone()).or_default().insert(key.to_string());
+        }
+        inner.lru.insert(tick, key.to_string());
+        inner.entries.insert(key.to_string(), Entry { value: value.to_vec(), expires, tick, tags: tags.to_vec() });
+        inner.bytes += size;
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut inner = self.lock();
+        let now = Instant::now();
+        let old_tick = match inner.entries.get(key) {
+            Some(e) if e.expires.is_some_and(|t| t <= now) => {
+                inner.remove(key);
+                return None;
+            }
+            Some(e) => e.tick,
+            None => return None,
+        };
+
+        // pasa a ser la más reciente
+        let tick = inner.next_tick();
+        inner.lru.remove(&old_tick);
+        inner.lru.insert(tick, key.to_string());
+        let entry = inner.entries.get_mut(key)?;
+        entry.tick = tick;
+        Some(entry.value.clone())
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) {
+        self.insert(key, value, ttl, &[]);
+    }
+
+    async fn set_tagged(&self, key: &str, value: &[u8], ttl: Option<Duration>, tags: &[String]) {
+        self.insert(key, value, ttl, tags);
+    }
+
+    async fn invalidate_tags(&self, tags: &[String]) {
+        let mut inner = self.lock();
+        for tag in tags {
+            let Some(keys) = inner.tags.remove(tag) else { continue };
+            for k in keys {
+                inner.remove(&k);
+            }
+        }
+    }
+
+    async fn del(&self, key: &str) {
+        self.lock().remove(key);
+    }
+
+    async fn keys(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let inner = self.lock();
+        let now = Instant::now();
+        inner
+            .entries
+            .range(prefix.to_string()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .filter(|(_, e)| e.expires.is_none_or(|t| t > now))
+            .take(limit)
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    async fn del_prefix(&self, prefix: &str) {
+        let mut inner = self.lock();
+        let keys: Vec<String> = inner
+            .entries
+            .range(prefix.to_string()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in keys {
+            inner.remove(&k);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(t: &[&str]) -> Vec<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_entry() {
+        let cache = MemoryCache::new(3, None);
+        for k in ["a", "b", "c"] {
+            cache.set(k, b"v", None).await;
+        }
+        // leer "a" la vuelve la más reciente: sale "b"
+        assert!(cache.get("a").await.is_some());
+        cache.set("d", b"v", None).await;
+        assert!(cache.get("b").await.is_none());
+        for k in ["a", "c", "d"] {
+            assert!(cache.get(k).await.is_some(), "{k}");
+        }
+
+        // reescribir una clave no expulsa a nadie
+        cache.set("a", b"v2", None).await;
+        assert_eq!(cache.get("a").await.unwrap(), b"v2");
+        assert!(cache.get("c").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_dropped_when_read() {
+        let cache = MemoryCache::new(10, None);
+        cache.set("short", b"v", Some(Duration::from_millis(20))).await;
+        cache.set("long", b"v", Some(Duration::from_secs(60))).await;
+        cache.set("forever", b"v", None).await;
+        assert!(cache.get("short").await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(cache.keys("", 10).await, ["forever", "long"]);
+        // sigue ocupando hasta que alguien la lee
+        assert_eq!(cache.lock().entries.len(), 3);
+        assert!(cache.get("short").await.is_none());
+        assert_eq!(cache.lock().entries.len(), 2);
+        assert!(cache.get("long").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn max_bytes_evicts_until_the_new_entry_fits() {
+        // clave (1 byte) + valor (9 bytes) = 10 bytes por entrada
+        let cache = MemoryCache::new(100, Some(25));
+        cache.set("a", &[0; 9], None).await;
+        cache.set("b", &[0; 9], None).await;
+        cache.set("c", &[0; 9], None).await;
+        assert!(cache.get("a").await.is_none());
+        assert_eq!(cache.lock().bytes, 20);
+
+        // una entrada más grande que el tope no se guarda ni expulsa nada
+        cache.set("huge", &[0; 30], None).await;
+        assert!(cache.get("huge").await.is_none());
+        assert!(cache.get("b").await.is_some() && cache.get("c").await.is_some());
+
+        cache.del("b").await;
+        assert_eq!(cache.lock().bytes, 10);
+    }
+
+    #[tokio::test]
+    async fn del_prefix_only_removes_that_family() {
+        let cache = MemoryCache::new(10, None);
+        for k in ["books:1", "books:2", "bookshelf", "authors:1"] {
+            cache.set(k, b"v", None).await;
+        }
+        cache.del_prefix("books:").await;
+        assert_eq!(cache.keys("", 10).await, ["authors:1", "bookshelf"]);
+        assert_eq!(cache.lock().lru.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn invalidating_a_tag_removes_its_entries() {
+        let cache = MemoryCache::new(10, None);
+        cache.set_tagged("book:1", b"v", None, &tags(&["book:1", "books"])).await;
+        cache.set_tagged("book:2", b"v", None, &tags(&["book:2", "books"])).await;
+        cache.set_tagged("home", b"v", None, &tags(&["home"])).await;
+
+        cache.invalidate_tags(&tags(&["book:1"])).await;
+        assert!(cache.get("book:1").await.is_none());
+        assert!(cache.get("book:2").await.is_some());
+
+        cache.invalidate_tags(&tags(&["books", "unknown"])).await;
+        assert_eq!(cache.keys("", 10).await, ["home"]);
+        // los tags de las entradas borradas no quedan colgando
+        assert_eq!(cache.lock().tags.keys().collect::<Vec<_>>(), ["home"]);
+
+        // una entrada reescrita sin tags ya no cae con el tag anterior
+        cache.set_tagged("home", b"v", None, &tags(&["home"])).await;
+        cache.set("home", b"v2", None).await;
+        cache.invalidate_tags(&tags(&["home"])).await;
+        assert_eq!(cache.get("home").await.unwrap(), b"v2");
+    }
+
+    #[test]
+    fn parses_limits_from_the_url() {
+        let cache = MemoryCache::from_url("memory://?max_entries=5&max_mb=2").unwrap();
+        assert_eq!((cache.max_entries, cache.max_bytes), (5, Some(2 * 1024 * 1024)));
+        assert!(MemoryCache::from_url("memory://?max_entries=x").is_err());
+        assert!(MemoryCache::from_url("memory://?ttl=5").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+// Métricas de la cache por familia de claves: aciertos, fallos, escrituras,
+// expulsiones, errores e histogramas de latencia (lectura y recálculo).
+// Globales al proceso: las anotan AppState y los backends (expulsiones del
+// LRU, errores de Redis). Se exponen en /metrics y en /admin/cache.
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Serialize;
+
+// Familias de claves; el formato de cada clave lo define AppState (db.rs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFamily {
+    AuthorsSummary,
+    BookAvg,
+    Home,
+    Search,
+    Suggest,
+    Other,
+}
+
+impl CacheFamily {
+    pub const ALL: [CacheFamily; 6] = [Self::AuthorsSummary, Self::BookAvg, Self::Home, Self::Search, Self::Suggest, Self::Other];
+
+    pub fn of(key: &str) -> Self {
+        if key == "authors:summary" {
+            Self::AuthorsSummary
+        } else if key.starts_with("book:") && key.ends_with(":avg_score") {
+            Self::BookAvg
+        } else if key.starts_with("home:") {
+            Self::Home
+        } else if key.starts_with("search:suggest:") {
+            Self::Suggest
+        } else if key.starts_with("search:") {
+            Self::Search
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AuthorsSummary => "authors_summary",
+            Self::BookAvg => "book_avg",
+            Self::Home => "home",
+            Self::Search => "search",
+            Self::Suggest => "suggest",
+            Self::Other => "other",
+        }
+    }
+
+    // Prefijos que cubren todas las claves de la familia (para listar y
+    // vaciar); "other" no tiene un prefijo propio.
+    pub fn prefixes(&self) -> &'static [&'static str] {
+        match self {
+            Self::AuthorsSummary => &["authors:summary"],
+            Self::BookAvg => &["book:"],
+            Self::Home => &["home:"],
+            Self::Search => &["search:books:", "search:authors:", "search:reviews:"],
+            Self::Suggest => &["search:suggest:"],
+            Self::Other => &[],
+        }
+    }
+
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|f| f == self).unwrap_or(0)
+    }
+}
+
+// Límites superiores de los buckets, en segundos (+Inf aparte)
+const BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, d: Duration) {
+        let secs = d.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|b| secs <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn mean_ms(&self) -> Option<f64> {
+        let count = self.count.load(Ordering::Relaxed);
+        (count > 0).then(|| self.sum_micros.load(Ordering::Relaxed) as f64 / count as f64 / 1000.0)
+    }
+
+    // Formato Prometheus: buckets acumulados, _sum y _count
+    fn render(&self, out: &mut String, name: &str, family: &str) {
+        let mut cumulative = 0;
+        for (le, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{family=\"{family}\",le=\"{le}\"}} {cumulative}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{family=\"{family}\",le=\"+Inf\"}} {count}");
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{name}_sum{{family=\"{family}\"}} {sum}");
+        let _ = writeln!(out, "{name}_count{{family=\"{family}\"}} {count}");
+    }
+}
+
+#[derive(Default)]
+struct FamilyMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    // servidos vencidos mientras se refrescan (también cuentan como hits)
+    stale: AtomicU64,
+    sets: AtomicU64,
+    evictions: AtomicU64,
+    errors: AtomicU64,
+    // lectura de la cache
+    get_latency: Histogram,
+    // recálculo contra Mongo tras un miss
+    load_latency: Histogram,
+}
+
+// Para recorrer las métricas de una familia al renderizar
+type CounterField = fn(&FamilyMetrics) -> &AtomicU64;
+type HistogramField = fn(&FamilyMetrics) -> &Histogram;
+
+#[derive(Default)]
+pub struct CacheMetrics {
+    families: [FamilyMetrics; CacheFamily::ALL.len()],
+}
+
+// Resumen de una familia para la página de administración
+#[derive(Serialize)]
+pub struct FamilySnapshot {
+    pub family: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+    pub stale: u64,
+    pub hit_rate: Option<f64>,
+    pub sets: u64,
+    pub evictions: u64,
+    pub errors: u64,
+    pub get_ms: Option<f64>,
+    pub load_ms: Option<f64>,
+}
+
+pub fn metrics() -> &'static CacheMetrics {
+    static METRICS: OnceLock<CacheMetrics> = OnceLock::new();
+    METRICS.get_or_init(CacheMetrics::default)
+}
+
+impl CacheMetrics {
+    fn family(&self, key: &str) -> &FamilyMetrics {
+        &self.families[CacheFamily::of(key).index()]
+    }
+
+    pub fn hit(&self, key: &str, took: Duration) {
+        let f = self.family(key);
+        f.hits.fetch_add(1, Ordering::Relaxed);
+        f.get_latency.observe(took);
+    }
+
+    pub fn miss(&self, key: &str, took: Duration) {
+        let f = self.family(key);
+        f.misses.fetch_add(1, Ordering::Relaxed);
+        f.get_latency.observe(took);
+    }
+
+    pub fn stale(&self, key: &str) {
+        self.family(key).stale.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, key: &str) {
+        self.family(key).sets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn eviction(&self, key: &str) {
+        self.family(key).evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn error(&self, key: &str) {
+        self.family(key).errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn load(&self, key: &str, took: Duration) {
+        self.family(key).load_latency.observe(took);
+    }
+
+    pub fn snapshot(&self) -> Vec<FamilySnapshot> {
+        CacheFamily::ALL
+            .iter()
+            .map(|family| {
+                let f = &self.families[family.index()];
+                let hits = f.hits.load(Ordering::Relaxed);
+                let misses = f.misses.load(Ordering::Relaxed);
+                FamilySnapshot {
+                    family: family.name(),
+                    hits,
+                    misses,
+                    stale: f.stale.load(Ordering::Relaxed),
+                    hit_rate: (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+                    sets: f.sets.load(Ordering::Relaxed),
+                    evictions: f.evictions.load(Ordering::Relaxed),
+                    errors: f.errors.load(Ordering::Relaxed),
+                    get_ms: f.get_latency.mean_ms(),
+                    load_ms: f.load_latency.mean_ms(),
+                }
+            })
+            .collect()
+    }
+
+    // Exposición en formato de texto de Prometheus
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counters: [(&str, &str, CounterField); 4] = [
+            ("cache_sets_total", "Values written to the cache", |f| &f.sets),
+            ("cache_evictions_total", "Entries evicted from the in-process cache to make room", |f| &f.evictions),
+            ("cache_errors_total", "Cache backend errors and undecodable entries", |f| &f.errors),
+            ("cache_stale_total", "Stale values served while refreshing in the background", |f| &f.stale),
+        ];
+
+        let _ = writeln!(out, "# HELP cache_requests_total Cache lookups by key family and result");
+        let _ = writeln!(out, "# TYPE cache_requests_total counter");
+        for family in CacheFamily::ALL {
+            let f = &self.families[family.index()];
+            let name = family.name();
+            let _ = writeln!(out, "cache_requests_total{{family=\"{name}\",result=\"hit\"}} {}", f.hits.load(Ordering::Relaxed));
+            let _ = writeln!(out, "cache_requests_total{{family=\"{name}\",result=\"miss\"}} {}", f.misses.load(Ordering::Relaxed));
+        }
+
+        for (metric, help, field) in counters {
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "# TYPE {metric} counter");
+            for family in CacheFamily::ALL {
+                let value = field(&self.families[family.index()]).load(Ordering::Relaxed);
+                let _ = writeln!(out, "{metric}{{family=\"{}\"}} {value}", family.name());
+            }
+        }
+
+        let histograms: [(&str, &str, HistogramField); 2] = [
+            ("cache_get_duration_seconds", "Time to read a value from the cache", |f| &f.get_latency),
+            ("cache_load_duration_seconds", "Time to compute a value after a miss", |f| &f.load_latency),
+        ];
+        for (metric, help, field) in histograms {
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "# TYPE {metric} histogram");
+            for family in CacheFamily::ALL {
+                field(&self.families[family.index()]).render(&mut out, metric, family.name());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_fall_into_their_family() {
+        let cases = [
+            ("authors:summary", CacheFamily::AuthorsSummary),
+            ("book:65f0c1:avg_score", CacheFamily::BookAvg),
+            ("home:top_rated", CacheFamily::Home),
+            ("search:suggest:ray:5", CacheFamily::Suggest),
+            ("search:books:auto:rayuela:1", CacheFamily::Search),
+            ("book:65f0c1", CacheFamily::Other),
+            ("lock:home:top_rated", CacheFamily::Other),
+        ];
+        for (key, family) in cases {
+            assert_eq!(CacheFamily::of(key), family, "{key}");
+            // los prefijos de la familia cubren sus claves
+            if family != CacheFamily::Other {
+                assert!(family.prefixes().iter().any(|p| key.starts_with(p)), "{key}");
+            }
+        }
+        for family in CacheFamily::ALL {
+            assert_eq!(CacheFamily::parse(family.name()), Some(family));
+        }
+        assert_eq!(CacheFamily::parse("nope"), None);
+    }
+
+    #[test]
+    fn counts_per_family_and_hit_rate() {
+        let m = CacheMetrics::default();
+        m.hit("home:top_rated", Duration::from_micros(300));
+        m.hit("home:top_selling", Duration::from_micros(300));
+        m.hit("home:top_selling", Duration::from_micros(300));
+        m.miss("home:top_rated", Duration::from_micros(900));
+        m.stale("home:top_rated");
+        m.set("authors:summary");
+        m.eviction("book:1:avg_score");
+        m.error("search:books:auto:x:1");
+
+        let snapshot = m.snapshot();
+        let family = |name: &str| snapshot.iter().find(|f| f.family == name).unwrap();
+        let home = family("home");
+        assert_eq!((home.hits, home.misses, home.stale), (3, 1, 1));
+        assert_eq!(home.hit_rate, Some(0.75));
+        assert!((home.get_ms.unwrap() - 0.45).abs() < 1e-9);
+        assert_eq!(home.load_ms, None);
+        assert_eq!(family("authors_summary").sets, 1);
+        assert_eq!(family("book_avg").evictions, 1);
+        assert_eq!(family("search").errors, 1);
+        // sin lecturas no hay tasa
+        assert_eq!(family("suggest").hit_rate, None);
+    }
+
+    #[test]
+    fn renders_prometheus_counters_and_cumulative_buckets() {
+        let m = CacheMetrics::default();
+        m.hit("authors:summary", Duration::from_micros(400));
+        m.miss("authors:summary", Duration::from_millis(3));
+        m.load("authors:summary", Duration::from_secs(10));
+
+        let out = m.render_prometheus();
+        let lines: Vec<&str> = out.lines().collect();
+        for expected in [
+            r#"cache_requests_total{family="authors_summary",result="hit"} 1"#,
+            r#"cache_requests_total{family="authors_summary",result="miss"} 1"#,
+            r#"cache_sets_total{family="home"} 0"#,
+            // 0,4 ms cae en el primer bucket, 3 ms en el de 5 ms; ambos acumulados
+            r#"cache_get_duration_seconds_bucket{family="authors_summary",le="0.0005"} 1"#,
+            r#"cache_get_duration_seconds_bucket{family="authors_summary",le="0.0025"} 1"#,
+            r#"cache_get_duration_seconds_bucket{family="authors_summary",le="0.005"} 2"#,
+            r#"cache_get_duration_seconds_count{family="authors_summary"} 2"#,
+            r#"cache_get_duration_seconds_sum{family="authors_summary"} 0.0034"#,
+            // más allá del último bucket solo cuenta en +Inf
+            r#"cache_load_duration_seconds_bucket{family="authors_summary",le="2.5"} 0"#,
+            r#"cache_load_duration_seconds_bucket{family="authors_summary",le="+Inf"} 1"#,
+            "# TYPE cache_load_duration_seconds histogram",
+        ] {
+            assert!(lines.contains(&expected), "{expected}");
+        }
+    }
+}
@@ -3,10 +3,29 @@ use std::time::Duration;
 
 #[async_trait]
 pub trait Cache: Send + Sync {
+    // false => no guarda nada (no vale la pena precalentar)
+    fn enabled(&self) -> bool { true }
+
     async fn get(&self, _key: &str) -> Option<Vec<u8>> { None }
     async fn set(&self, _key: &str, _value: &[u8], _ttl: Option<Duration>) {}
+    // Borrado por clave / prefijo. La app invalida por tags; estos quedan
+    // para el L1 de TieredCache y para vaciar familias desde /admin/cache.
+    #[allow(dead_code)]
     async fn del(&self, _key: &str) {}
     async fn del_prefix(&self, _prefix: &str) {}
+    // Hasta `limit` claves que empiezan por `prefix` (para /admin/cache)
+    async fn keys(&self, _prefix: &str, _limit: usize) -> Vec<String> { vec![] }
+    // set + registra la clave bajo cada tag, para borrarla con invalidate_tags
+    async fn set_tagged(&self, key: &str, value: &[u8], ttl: Option<Duration>, _tags: &[String]) {
+        self.set(key, value, ttl).await
+    }
+    // Borra todas las claves registradas bajo alguno de los tags
+    async fn invalidate_tags(&self, _tags: &[String]) {}
+    // Lock distribuido para calcular `key` en una sola instancia. Devuelve un
+    // token para `unlock`, o None si otra instancia lo tiene. Sin backend que
+    // coordine (o si falla), siempre se concede.
+    async fn lock(&self, _key: &str, _ttl: Duration) -> Option<String> { Some(String::new()) }
+    async fn unlock(&self, _key: &str, _token: &str) {}
 }
 
 // No-op: no cachea nada
@@ -14,11 +33,19 @@ pub struct NoopCache;
 
 #[async_trait]
 impl Cache for NoopCache {
+    fn enabled(&self) -> bool { false }
+
     async fn get(&self, _key: &str) -> Option<Vec<u8>> { None }
     async fn set(&self, _key: &str, _value: &[u8], _ttl: Option<Duration>) {}
     async fn del(&self, _key: &str) {}
     async fn del_prefix(&self, _prefix: &str) {}
 }
 
+pub mod codec;
+pub mod flight;
+pub mod memory;
+pub mod metrics;
+#[cfg(feature = "redis-cache")]
+pub mod redis;
 #[cfg(feature = "redis-cache")]
-pub mod redis;
\ No newline at end of file
+pub mod tiered;
\ No newline at end of file
@@ -1,54 +1,354 @@
-use std::time::Duration;
-use redis::{AsyncCommands, aio::Connection};
-use tokio::sync::Mutex;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, RedisResult};
+
+use super::metrics::metrics;
 use super::Cache;
 
+// Claves por iteración de SCAN (y por UNLINK)
+const SCAN_BATCH: usize = 500;
+
+// Tope por comando: una cache lenta no debe frenar la página
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+// Reintentos de reconexión del ConnectionManager (backoff exponencial)
+const RECONNECT_RETRIES: usize = 3;
+
+// Circuit breaker: tras BREAKER_THRESHOLD fallos seguidos no se habla con
+// Redis durante BREAKER_COOLDOWN (todo es un miss); luego se vuelve a probar.
+const BREAKER_THRESHOLD: u32 = 5;
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(10);
+
+// Prefijo de las claves de lock (SET NX PX)
+const LOCK_PREFIX: &str = "lock:";
+// Borra el lock solo si sigue siendo nuestro
+const UNLOCK_SCRIPT: &str = r#"if redis.call("GET", KEYS[1]) == ARGV[1] then return redis.call("DEL", KEYS[1]) else return 0 end"#;
+
+// Conjunto de claves de cada tag (SADD al guardar)
+const TAG_PREFIX: &str = "tag:";
+// Vida de los conjuntos de tags: más que cualquier TTL de entrada; se renueva
+// en cada SADD, así no crecen para siempre con claves ya vencidas
+const TAG_TTL: Duration = Duration::from_secs(24 * 3600);
+// "tags-of:<clave>": los tags de una entrada (separados por \n), con su mismo TTL,
+// para que el L1 de TieredCache la registre con ellos al copiarla de Redis
+const TAGS_OF_PREFIX: &str = "tags-of:";
+// RENAME solo si existe (si no, RENAME da error)
+const RENAME_SCRIPT: &str = r#"if redis.call("EXISTS", KEYS[1]) == 1 then redis.call("RENAME", KEYS[1], KEYS[2]) return 1 else return 0 end"#;
+
+// Escapa los comodines de MATCH: las claves llevan texto del usuario
+fn glob_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl Breaker {
+    fn allows(&self) -> bool {
+        let open_until = self.open_until.lock().unwrap_or_else(|e| e.into_inner());
+        open_until.is_none_or(|t| Instant::now() >= t)
+    }
+
+    fn success(&self) {
+        if self.failures.swap(0, Ordering::Relaxed) >= BREAKER_THRESHOLD {
+            println!("[cache] Redis is back, closing circuit breaker");
+        }
+        *self.open_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    // Tras el cooldown, un fallo más vuelve a abrirlo enseguida
+    fn failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= BREAKER_THRESHOLD {
+            if failures == BREAKER_THRESHOLD {
+                eprintln!("[cache] Redis unhealthy, opening circuit breaker for {:?}", BREAKER_COOLDOWN);
+            }
+            *self.open_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + BREAKER_COOLDOWN);
+        }
+    }
+}
+
+// ConnectionManager es multiplexado (se clona por comando, sin mutex) y
+// reconecta solo si Redis se reinicia.
 pub struct RedisCache {
-    conn: Mutex<Connection>,
+    conn: ConnectionManager,
+    breaker: Breaker,
+    // locks distribuidos (CACHE_LOCKS); si no, `lock` siempre concede
+    locks: bool,
 }
 
 impl RedisCache {
-    pub async fn new(url: &str) -> anyhow::Result<Self> {
+    pub async fn new(url: &str, locks: bool) -> anyhow::Result<Self> {
         let client = redis::Client::open(url)?;
-        // En redis 0.25 usamos una conexión Tokio simple
-        let conn = client.get_tokio_connection().await?;
-        Ok(Self { conn: Mutex::new(conn) })
+        let conn = ConnectionManager::new_with_backoff_and_timeouts(
+            client,
+            2,
+            100,
+            RECONNECT_RETRIES,
+            COMMAND_TIMEOUT,
+            CONNECT_TIMEOUT,
+        )
+        .await?;
+        Ok(Self { conn, breaker: Breaker::default(), locks })
+    }
+
+    // Ejecuta un comando con timeout y breaker. None si falló o si el
+    // breaker está abierto: para quien llama, es un miss. `key` solo sirve
+    // para atribuir el error a su familia en las métricas.
+    async fn run<T, F, Fut>(&self, op: &str, key: &str, f: F) -> Option<T>
+    where
+        F: FnOnce(ConnectionManager) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        if !self.breaker.allows() {
+            return None;
+        }
+        match tokio::time::timeout(COMMAND_TIMEOUT, f(self.conn.clone())).await {
+            Ok(Ok(v)) => {
+                self.breaker.success();
+                Some(v)
+            }
+            Ok(Err(e)) => {
+                eprintln!("[cache] Redis {op} failed: {e}");
+                metrics().error(key);
+                self.breaker.failure();
+                None
+            }
+            Err(_) => {
+                eprintln!("[cache] Redis {op} timed out");
+                metrics().error(key);
+                self.breaker.failure();
+                None
+            }
+        }
+    }
+
+    // GET + PTTL + tags en un viaje: el L1 no debe sobrevivir a la entrada de
+    // Redis y tiene que poder invalidarla por tag
+    pub async fn get_with_meta(&self, key: &str) -> Option<(Vec<u8>, Option<Duration>, Vec<String>)> {
+        let tags_key = format!("{TAGS_OF_PREFIX}{key}");
+        let (value, ms, tags): (Option<Vec<u8>>, i64, Option<String>) = self
+            .run("GET", key, |mut c| {
+                let tags_key = &tags_key;
+                async move { redis::pipe().get(key).pttl(key).get(tags_key).query_async(&mut c).await }
+            })
+            .await?;
+        let tags = tags.map(|t| t.split('\n').filter(|t| !t.is_empty()).map(String::from).collect()).unwrap_or_default();
+        // PTTL -1: sin expiración
+        value.map(|v| (v, u64::try_from(ms).ok().map(Duration::from_millis), tags))
+    }
+
+    pub async fn publish(&self, channel: &str, message: &str) {
+        let _: Option<()> = self.run("PUBLISH", "", |mut c| async move { c.publish(channel, message).await }).await;
     }
 }
 
 #[async_trait::async_trait]
 impl Cache for RedisCache {
     async fn get(&self, key: &str) -> Option<Vec<u8>> {
-        let mut conn = self.conn.lock().await;
-        match conn.get::<_, Vec<u8>>(key).await {
-            Ok(v) => Some(v),
-            Err(_) => None,
-        }
+        self.run("GET", key, |mut c| async move { c.get::<_, Option<Vec<u8>>>(key).await }).await.flatten()
     }
 
     async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) {
-        let mut conn = self.conn.lock().await;
-        let _: redis::RedisResult<()> = match ttl {
-            Some(d) => conn.set_ex(key, value, d.as_secs()).await,
-            None => conn.set(key, value).await,
-        };
+        let _: Option<()> = self
+            .run("SET", key, |mut c| async move {
+                match ttl {
+                    Some(d) => c.set_ex(key, value, d.as_secs().max(1)).await,
+                    None => c.set(key, value).await,
+                }
+            })
+            .await;
     }
 
     async fn del(&self, key: &str) {
-        let mut conn = self.conn.lock().await;
-        let _: redis::RedisResult<()> = conn.del(key).await;
+        let _: Option<()> = self.run("DEL", key, |mut c| async move { c.del(key).await }).await;
     }
 
+    // SCAN + UNLINK por lotes: no bloquea Redis como KEYS y UNLINK libera la
+    // memoria en segundo plano.
     async fn del_prefix(&self, prefix: &str) {
-        // Sencillo (no óptimo): KEYS prefix* y DEL
-        let mut conn = self.conn.lock().await;
-        let pattern = format!("{}*", prefix);
-        let keys_res: redis::RedisResult<Vec<String>> = conn.keys(&pattern).await;
-        if let Ok(keys) = keys_res {
+        let pattern = format!("{}*", glob_escape(prefix));
+        let mut cursor: u64 = 0;
+        loop {
+            let scanned: Option<(u64, Vec<String>)> = self
+                .run("SCAN", prefix, |mut c| {
+                    let pattern = &pattern;
+                    async move {
+                        redis::cmd("SCAN")
+                            .arg(cursor)
+                            .arg("MATCH")
+                            .arg(pattern)
+                            .arg("COUNT")
+                            .arg(SCAN_BATCH)
+                            .query_async(&mut c)
+                            .await
+                    }
+                })
+                .await;
+            let Some((next, keys)) = scanned else { return };
             if !keys.is_empty() {
-                let _: redis::RedisResult<()> = conn.del(keys).await;
+                let _: Option<()> =
+                    self.run("UNLINK", prefix, |mut c| async move { redis::cmd("UNLINK").arg(keys).query_async(&mut c).await }).await;
+            }
+
+            if next == 0 {
+                return;
+            }
+            cursor = next;
+        }
+    }
+
+    // SET y SADD en una transacción: no hay momento en que la entrada exista
+    // sin estar registrada en sus tags
+    async fn set_tagged(&self, key: &str, value: &[u8], ttl: Option<Duration>, tags: &[String]) {
+        let tags_key = format!("{TAGS_OF_PREFIX}{key}");
+        let tags_value = tags.join("\n");
+        let _: Option<()> = self
+            .run("SET", key, |mut c| async move {
+                let mut pipe = redis::pipe();
+                pipe.atomic();
+                match ttl {
+                    Some(d) => {
+                        let secs = d.as_secs().max(1);
+                        pipe.set_ex(key, value, secs).ignore().set_ex(&tags_key, &tags_value, secs).ignore()
+                    }
+                    None => pipe.set(key, value).ignore().set(&tags_key, &tags_value).ignore(),
+                };
+                for tag in tags {
+                    let tag_key = format!("{TAG_PREFIX}{tag}");
+                    pipe.sadd(&tag_key, key).ignore().expire(&tag_key, TAG_TTL.as_secs() as i64).ignore();
+                }
+                pipe.query_async(&mut c).await
+            })
+            .await;
+    }
+
+    // El conjunto se renombra antes de recorrerlo: lo que se registre mientras
+    // tanto va a un conjunto nuevo y no se pierde.
+    async fn invalidate_tags(&self, tags: &[String]) {
+        for tag in tags {
+            let tag_key = format!("{TAG_PREFIX}{tag}");
+            let dropping = format!("{tag_key}:dropping:{}", uuid::Uuid::new_v4());
+            let renamed: Option<i64> = self
+                .run("RENAME", "", |mut c| {
+                    let (tag_key, dropping) = (&tag_key, &dropping);
+                    async move { redis::Script::new(RENAME_SCRIPT).key(tag_key).key(dropping).invoke_async(&mut c).await }
+                })
+                .await;
+            // sin claves registradas (o sin Redis): nada que borrar
+            if renamed != Some(1) {
+                continue;
             }
+
+            let mut cursor: u64 = 0;
+            loop {
+                let scanned: Option<(u64, Vec<String>)> = self
+                    .run("SSCAN", "", |mut c| {
+                        let dropping = &dropping;
+                        async move {
+                            redis::cmd("SSCAN").arg(dropping).arg(cursor).arg("COUNT").arg(SCAN_BATCH).query_async(&mut c).await
+                        }
+                    })
+                    .await;
+                let Some((next, keys)) = scanned else { break };
+                if !keys.is_empty() {
+                    // la entrada y sus tags
+                    let tag_keys: Vec<String> = keys.iter().map(|k| format!("{TAGS_OF_PREFIX}{k}")).collect();
+                    let _: Option<()> = self
+                        .run("UNLINK", "", |mut c| async move { redis::cmd("UNLINK").arg(keys).arg(tag_keys).query_async(&mut c).await })
+                        .await;
+                }
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+            let _: Option<()> = self.run("UNLINK", "", |mut c| {
+                let dropping = &dropping;
+                async move { redis::cmd("UNLINK").arg(dropping).query_async(&mut c).await }
+            }).await;
+        }
+    }
+
+    async fn keys(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let pattern = format!("{}*", glob_escape(prefix));
+        let mut out = Vec::new();
+        let mut cursor: u64 = 0;
+        while out.len() < limit {
+            let scanned: Option<(u64, Vec<String>)> = self
+                .run("SCAN", prefix, |mut c| {
+                    let pattern = &pattern;
+                    async move {
+                        redis::cmd("SCAN").arg(cursor).arg("MATCH").arg(pattern).arg("COUNT").arg(SCAN_BATCH).query_async(&mut c).await
+                    }
+                })
+                .await;
+            let Some((next, keys)) = scanned else { break };
+            out.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        out.truncate(limit);
+        out.sort();
+        out
+    }
+
+    async fn lock(&self, key: &str, ttl: Duration) -> Option<String> {
+        if !self.locks {
+            return Some(String::new());
+        }
+        let token = uuid::Uuid::new_v4().to_string();
+        let lock_key = format!("{LOCK_PREFIX}{key}");
+        let acquired: Option<Option<String>> = self
+            .run("LOCK", key, |mut c| {
+                let (lock_key, token) = (&lock_key, &token);
+                async move {
+                    redis::cmd("SET")
+                        .arg(lock_key)
+                        .arg(token)
+                        .arg("NX")
+                        .arg("PX")
+                        .arg(ttl.as_millis() as u64)
+                        .query_async(&mut c)
+                        .await
+                }
+            })
+            .await;
+        match acquired {
+            // "OK": es nuestro; nil: lo tiene otra instancia
+            Some(Some(_)) => Some(token),
+            Some(None) => None,
+            // Redis no responde: que calcule cada uno
+            None => Some(String::new()),
         }
     }
-}
\ No newline at end of file
+
+    async fn unlock(&self, key: &str, token: &str) {
+        if !self.locks || token.is_empty() {
+            return;
+        }
+        let lock_key = format!("{LOCK_PREFIX}{key}");
+        let _: Option<i64> = self
+            .run("UNLOCK", key, |mut c| {
+                let lock_key = &lock_key;
+                async move { redis::Script::new(UNLOCK_SCRIPT).key(lock_key).arg(token).invoke_async(&mut c).await }
+            })
+            .await;
+    }
+}
@@ -0,0 +1,361 @@
+// Cache en dos niveles: L1 en memoria del proceso delante de Redis (L2).
+// Las lecturas calientes no salen del proceso; los del/del_prefix/tags se
+// publican por pub/sub para que las otras réplicas borren su L1.
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+use super::memory::MemoryCache;
+use super::redis::RedisCache;
+use super::Cache;
+
+// Canal de invalidaciones compartido por todas las réplicas
+const CHANNEL: &str = "cache:invalidate";
+// Tope de vida en L1: acota lo que dura una entrada vieja si se pierde un mensaje
+const L1_MAX_TTL: Duration = Duration::from_secs(30);
+// Espera antes de volver a suscribirse si se cae la conexión
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(2);
+
+pub struct TieredCache {
+    l1: Arc<MemoryCache>,
+    l2: RedisCache,
+    // para ignorar nuestros propios mensajes
+    instance: String,
+}
+
+impl TieredCache {
+    pub async fn new(url: &str, l1: MemoryCache, locks: bool) -> anyhow::Result<Self> {
+        let l2 = RedisCache::new(url, locks).await?;
+        let l1 = Arc::new(l1);
+        let instance = Uuid::new_v4().to_string();
+
+        let client = redis::Client::open(url)?;
+        tokio::spawn(listen(client, l1.clone(), instance.clone()));
+
+        Ok(Self { l1, l2, instance })
+    }
+
+    fn l1_ttl(ttl: Option<Duration>) -> Duration {
+        ttl.map_or(L1_MAX_TTL, |d| d.min(L1_MAX_TTL))
+    }
+
+    // Mensaje: "<instancia> <k|p|t> <clave, prefijo o tag>"
+    async fn broadcast(&self, op: char, key: &str) {
+        self.l2.publish(CHANNEL, &format!("{} {op} {key}", self.instance)).await;
+    }
+}
+
+// Escucha las invalidaciones de las otras réplicas. Si se corta la
+// suscripción pudimos perder mensajes: se vacía el L1 y se reintenta.
+async fn listen(client: redis::Client, l1: Arc<MemoryCache>, instance: String) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => match pubsub.subscribe(CHANNEL).await {
+                Ok(()) => {
+                    let mut messages = pubsub.on_message();
+                    while let Some(msg) = messages.next().await {
+                        let Ok(payload) = msg.get_payload::<String>() else { continue };
+                        let mut it = payload.splitn(3, ' ');
+                        match (it.next(), it.next(), it.next()) {
+                            (Some(from), _, _) if from == instance => {}
+                            (_, Some("k"), Some(key)) => l1.del(key).await,
+                            (_, Some("p"), Some(prefix)) => l1.del_prefix(prefix).await,
+                            (_, Some("t"), Some(tag)) => l1.invalidate_tags(&[tag.to_string()]).await,
+                            _ => eprintln!("[cache] Ignoring invalidation message {payload:?}"),
+                        }
+                    }
+                    eprintln!("[cache] Invalidation subscription lost, flushing L1");
+                }
+                Err(e) => eprintln!("[cache] SUBSCRIBE {CHANNEL} failed: {e}"),
+            },
+            Err(e) => eprintln!("[cache] Pub/sub connection failed: {e}"),
+        }
+        l1.del_prefix("").await;
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+#[async_trait]
+impl Cache for TieredCache {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if let Some(v) = self.l1.get(key).await {
+            return Some(v);
+        }
+        // con sus tags: si no, invalidate_tags (y los mensajes 't') no la verían en L1
+        let (value, ttl, tags) = self.l2.get_with_meta(key).await?;
+        self.l1.set_tagged(key, &value, Some(Self::l1_ttl(ttl)), &tags).await;
+        Some(value)
+    }
+
+    // Un set también invalida: otra réplica puede tener el valor anterior en L1
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) {
+        self.l2.set(key, value, ttl).await;
+        self.l1.set(key, value, Some(Self::l1_ttl(ttl))).await;
+        self.broadcast('k', key).await;
+    }
+
+    async fn del(&self, key: &str) {
+        self.l2.del(key).await;
+        self.l1.del(key).await;
+        self.broadcast('k', key).await;
+    }
+
+    async fn del_prefix(&self, prefix: &str) {
+        self.l2.del_prefix(prefix).await;
+        self.l1.del_prefix(prefix).await;
+        self.broadcast('p', prefix).await;
+    }
+
+    async fn set_tagged(&self, key: &str, value: &[u8], ttl: Option<Duration>, tags: &[String]) {
+        self.l2.set_tagged(key, value, ttl, tags).await;
+        self.l1.set_tagged(key, value, Some(Self::l1_ttl(ttl)), tags).await;
+        self.broadcast('k', key).await;
+    }
+
+    async fn invalidate_tags(&self, tags: &[String]) {
+        self.l2.invalidate_tags(tags).await;
+        self.l1.invalidate_tags(tags).await;
+        for tag in tags {
+            self.broadcast('t', tag).await;
+        }
+    }
+
+    async fn keys(&self, prefix: &str, limit: usize) -> Vec<String> {
+        self.l2.keys(prefix, limit).await
+    }
+
+    async fn lock(&self, key: &str, ttl: Duration) -> Option<String> {
+        self.l2.lock(key, ttl).await
+    }
+
+    async fn unlock(&self, key: &str, token: &str) {
+        self.l2.unlock(key, token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    // Redis mínimo en memoria: lo justo de RESP2 para RedisCache y el pub/sub
+    // de TieredCache (sin expiraciones: PTTL siempre -1)
+    #[derive(Default)]
+    struct Store {
+        strings: HashMap<String, Vec<u8>>,
+        sets: HashMap<String, HashSet<String>>,
+        subscribers: Vec<mpsc::UnboundedSender<Vec<u8>>>,
+    }
+
+    enum Reply {
+        Status(&'static str),
+        Int(i64),
+        Bulk(Option<Vec<u8>>),
+        Array(Vec<Reply>),
+    }
+
+    impl Reply {
+        fn bulk(s: &str) -> Self {
+            Reply::Bulk(Some(s.as_bytes().to_vec()))
+        }
+
+        fn encode(&self, out: &mut Vec<u8>) {
+            match self {
+                Reply::Status(s) => out.extend(format!("+{s}\r\n").as_bytes()),
+                Reply::Int(n) => out.extend(format!(":{n}\r\n").as_bytes()),
+                Reply::Bulk(None) => out.extend(b"$-1\r\n"),
+                Reply::Bulk(Some(b)) => {
+                    out.extend(format!("${}\r\n", b.len()).as_bytes());
+                    out.extend(b);
+                    out.extend(b"\r\n");
+                }
+                Reply::Array(items) => {
+                    out.extend(format!("*{}\r\n", items.len()).as_bytes());
+                    items.iter().for_each(|i| i.encode(out));
+                }
+            }
+        }
+    }
+
+    fn text(arg: &[u8]) -> String {
+        String::from_utf8_lossy(arg).into_owned()
+    }
+
+    fn remove(store: &mut Store, key: &str) -> bool {
+        store.strings.remove(key).is_some() | store.sets.remove(key).is_some()
+    }
+
+    fn exec(store: &Mutex<Store>, args: &[Vec<u8>], conn: &mpsc::UnboundedSender<Vec<u8>>) -> Reply {
+        let mut store = store.lock().unwrap();
+        let cmd = text(&args[0]).to_uppercase();
+        let arg = |i: usize| text(&args[i]);
+        match cmd.as_str() {
+            "GET" => Reply::Bulk(store.strings.get(&arg(1)).cloned()),
+            "SET" => {
+                store.strings.insert(arg(1), args[2].clone());
+                Reply::Status("OK")
+            }
+            "SETEX" => {
+                store.strings.insert(arg(1), args[3].clone());
+                Reply::Status("OK")
+            }
+            "PTTL" => Reply::Int(if store.strings.contains_key(&arg(1)) { -1 } else { -2 }),
+            "SADD" => {
+                let set = store.sets.entry(arg(1)).or_default();
+                Reply::Int(args[2..].iter().filter(|m| set.insert(text(m))).count() as i64)
+            }
+            "EXPIRE" => Reply::Int(1),
+            "SSCAN" => {
+                let members = store.sets.get(&arg(1)).into_iter().flatten().map(|m| Reply::bulk(m)).collect();
+                Reply::Array(vec![Reply::bulk("0"), Reply::Array(members)])
+            }
+            "SCAN" => {
+                let prefix = arg(3).trim_end_matches('*').to_string();
+                let keys = store.strings.keys().chain(store.sets.keys()).filter(|k| k.starts_with(&prefix)).map(|k| Reply::bulk(k)).collect();
+                Reply::Array(vec![Reply::bulk("0"), Reply::Array(keys)])
+            }
+            "DEL" | "UNLINK" => Reply::Int(args[1..].iter().filter(|k| remove(&mut store, &text(k))).count() as i64),
+            // EVALSHA <sha> <numkeys>: con 2 claves es RENAME_SCRIPT, con 1 UNLOCK_SCRIPT
+            "EVALSHA" if arg(2) == "2" => match store.sets.remove(&arg(3)) {
+                Some(set) => {
+                    store.sets.insert(arg(4), set);
+                    Reply::Int(1)
+                }
+                None => Reply::Int(0),
+            },
+            "EVALSHA" => {
+                let ours = store.strings.get(&arg(3)) == Some(&args[4]);
+                Reply::Int(if ours && remove(&mut store, &arg(3)) { 1 } else { 0 })
+            }
+            "PUBLISH" => {
+                let mut msg = Vec::new();
+                Reply::Array(vec![Reply::bulk("message"), Reply::Bulk(Some(args[1].clone())), Reply::Bulk(Some(args[2].clone()))])
+                    .encode(&mut msg);
+                store.subscribers.retain(|s| s.send(msg.clone()).is_ok());
+                Reply::Int(store.subscribers.len() as i64)
+            }
+            "SUBSCRIBE" => {
+                store.subscribers.push(conn.clone());
+                Reply::Array(vec![Reply::bulk("subscribe"), Reply::Bulk(Some(args[1].clone())), Reply::Int(1)])
+            }
+            "PING" => Reply::Status("PONG"),
+            _ => Reply::Status("OK"),
+        }
+    }
+
+    async fn read_command<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<Vec<u8>>> {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok().filter(|&n| n > 0)?;
+        let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+            let mut buf = vec![0; len + 2];
+            reader.read_exact(&mut buf).await.ok()?;
+            buf.truncate(len);
+            args.push(buf);
+        }
+        Some(args)
+    }
+
+    async fn stub() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store = Arc::new(Mutex::new(Store::default()));
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = socket.into_split();
+                    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+                    tokio::spawn(async move {
+                        while let Some(bytes) = rx.recv().await {
+                            if write.write_all(&bytes).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                    let mut reader = BufReader::new(read);
+                    // MULTI..EXEC: se contesta QUEUED y EXEC devuelve todo junto
+                    let mut queued: Option<Vec<Reply>> = None;
+                    while let Some(args) = read_command(&mut reader).await {
+                        let reply = match (text(&args[0]).to_uppercase().as_str(), queued.as_mut()) {
+                            ("MULTI", _) => {
+                                queued = Some(Vec::new());
+                                Reply::Status("OK")
+                            }
+                            ("EXEC", _) => Reply::Array(queued.take().unwrap_or_default()),
+                            (_, Some(q)) => {
+                                q.push(exec(&store, &args, &tx));
+                                Reply::Status("QUEUED")
+                            }
+                            (_, None) => exec(&store, &args, &tx),
+                        };
+                        let mut out = Vec::new();
+                        reply.encode(&mut out);
+                        if tx.send(out).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        format!("redis://{addr}")
+    }
+
+    async fn tiered(url: &str) -> TieredCache {
+        let cache = TieredCache::new(url, MemoryCache::new(100, None), false).await.unwrap();
+        // que el listener llegue a suscribirse
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cache
+    }
+
+    #[tokio::test]
+    async fn l2_hit_keeps_its_tags_in_l1() {
+        let url = stub().await;
+        let writer = tiered(&url).await;
+        let reader = tiered(&url).await;
+        let tags = vec!["book:1".to_string()];
+
+        writer.set_tagged("home:page", b"v1", Some(Duration::from_secs(60)), &tags).await;
+        // L1 vacío: viene de Redis y queda en L1
+        assert_eq!(reader.get("home:page").await.as_deref(), Some(&b"v1"[..]));
+
+        reader.invalidate_tags(&tags).await;
+        assert_eq!(reader.get("home:page").await, None);
+    }
+
+    #[tokio::test]
+    async fn tag_invalidation_from_another_replica_reaches_refilled_l1() {
+        let url = stub().await;
+        let writer = tiered(&url).await;
+        let reader = tiered(&url).await;
+        let tags = vec!["author:7".to_string()];
+
+        writer.set_tagged("authors:summary", b"v1", None, &tags).await;
+        assert_eq!(reader.get("authors:summary").await.as_deref(), Some(&b"v1"[..]));
+
+        // llega por pub/sub como 't author:7'
+        writer.invalidate_tags(&tags).await;
+        let mut gone = false;
+        for _ in 0..20 {
+            if reader.get("authors:summary").await.is_none() {
+                gone = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(gone, "the reader's L1 kept an entry whose tag was invalidated");
+    }
+}
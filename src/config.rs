@@ -4,7 +4,19 @@ pub struct AppConfig {
     pub mongo_uri: String,
     pub db_name: String,
     pub cache_url: Option<String>,
+    #[cfg(feature = "redis-cache")]
+    pub cache_l1_url: Option<String>,
+    #[cfg(feature = "redis-cache")]
+    pub cache_locks: bool,
+    pub cache_codec: String,
+    pub cache_compress_above: String,
+    pub cache_warmup: bool,
+    pub cache_warmup_queries: Vec<String>,
     pub search_url: Option<String>,
+    #[cfg(feature = "search-opensearch")]
+    pub search_index: String,
+    pub search_language: String,
+    // aún sin usar
     pub static_dir: String,
     pub serve_static_from_app: bool,
 }
@@ -18,10 +30,27 @@ impl AppConfig {
         let serve_static_from_app = get("SERVE_STATIC", "app") == "app";
 
         Self {
-            mongo_uri: get("MONGO_URI", "mongodb://localhost:27017"),
+            mongo_uri: get("MONGO_URI", "mongodb://localhost:27017/?directConnection=true"),
             db_name: get("DB_NAME", "bookreview_dev"),
             cache_url: env::var("CACHE_URL").ok(),
+            #[cfg(feature = "redis-cache")]
+            cache_l1_url: env::var("CACHE_L1").ok(),
+            #[cfg(feature = "redis-cache")]
+            cache_locks: matches!(get("CACHE_LOCKS", "false").as_str(), "true" | "1"),
+            cache_codec: get("CACHE_CODEC", "msgpack"),
+            cache_compress_above: get("CACHE_COMPRESS_ABOVE", "1024"),
+            cache_warmup: matches!(get("CACHE_WARMUP", "true").as_str(), "true" | "1"),
+            // búsquedas populares separadas por comas
+            cache_warmup_queries: get("CACHE_WARMUP_QUERIES", "")
+                .split(',')
+                .map(str::trim)
+                .filter(|q| !q.is_empty())
+                .map(String::from)
+                .collect(),
             search_url: env::var("SEARCH_URL").ok(),
+            #[cfg(feature = "search-opensearch")]
+            search_index: get("SEARCH_INDEX", "bookreview"),
+            search_language: get("SEARCH_LANGUAGE", "english"),
             static_dir: get("STATIC_DIR", "./static"),
             serve_static_from_app,
         }
@@ -1,50 +1,334 @@
 use crate::config::AppConfig;
 use std::sync::Arc;
 use futures_util::stream::TryStreamExt;
-use serde::{Serialize, de::DeserializeOwned};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::future::Future;
 
 use mongodb::{
     bson::doc,
+    error::ErrorKind,
     options::{ClientOptions, IndexOptions},
     Client, Database, IndexModel,
 };
-use crate::models::{AuthorSummary, TopRatedBook, TopSellingBook, SearchResult, PaginatedSearchResults, BookWithAuthor};
+use crate::models::{Author, Book, Review, AuthorSummary, TopRatedBook, TopSellingBook, PaginatedSearchResults, BookWithAuthor,
+    SearchHitKind, SearchItem, GroupedSearchResults,
+    Suggestions};
+use crate::repo::{AuthorFilter, AuthorRepo, BookFilter, BookRepo, Repo, ReviewFilter, ReviewRepo, SaleRepo, SearchBase, SearchPage};
+use crate::repo::memory::MemoryRepo;
+use crate::repo::mongo::{suggest_collation, MongoRepo};
 
-use crate::cache::{Cache, NoopCache};
+use crate::cache::memory::{MemoryCache, DEFAULT_MAX_ENTRIES};
+use crate::cache::metrics::{metrics, CacheFamily};
+use crate::cache::codec::{self, Codec};
+use crate::cache::{flight::SingleFlight, Cache, NoopCache};
 #[cfg(feature = "redis-cache")]
-use crate::cache::redis::RedisCache;
-use crate::search::{SearchEngine, NoopSearch};
+use crate::cache::{redis::RedisCache, tiered::TieredCache};
+use crate::search::{SearchEngine, NoopSearch, BookDoc, ReviewDoc};
+use crate::search::memory::MemorySearch;
+use crate::search::query::{self as query_parser, SearchMode};
+use crate::search::facets::FacetSelection;
+use crate::search::highlight;
+#[cfg(feature = "search-opensearch")]
+use crate::search::opensearch::OpenSearch;
 
 
-fn build_search(cfg: &AppConfig) -> Arc<dyn SearchEngine> {
-    if let Some(url) = &cfg.search_url {
-        if !url.is_empty() {
-            eprintln!("SEARCH_URL set ({}). Using NoopSearch for now.", url);
+// memory://?max_entries=N&max_mb=M => cache del proceso (LRU + TTL);
+// redis://... => Redis (feature redis-cache); "none" => sin cache.
+// Sin CACHE_URL, o si Redis no arranca, se usa el de memoria.
+// Con Redis, CACHE_L1=memory://?max_entries=N antepone un L1 por proceso.
+async fn build_cache(cfg: &AppConfig) -> Arc<dyn Cache> {
+    let url = cfg.cache_url.as_deref().map(str::trim).filter(|u| !u.is_empty()).unwrap_or("memory://");
+
+    if url == "none" {
+        println!("[cache] Disabled");
+        return Arc::new(NoopCache);
+    }
+
+    #[cfg(feature = "redis-cache")]
+    if url.starts_with("redis://") || url.starts_with("rediss://") {
+        let l1_url = cfg.cache_l1_url.as_deref().map(str::trim).filter(|u| !u.is_empty() && *u != "none");
+        if let Some(l1_url) = l1_url {
+            match MemoryCache::from_url(l1_url) {
+                Ok(l1) => {
+                    let l1_entries = l1.max_entries();
+                    match TieredCache::new(url, l1, cfg.cache_locks).await {
+                        Ok(c) => {
+                            println!("[cache] Using Redis at {} with in-process L1 (max {} entries)", url, l1_entries);
+                            return Arc::new(c);
+                        }
+                        Err(e) => eprintln!("[cache] Redis + L1 init failed: {e}"),
+                    }
+                }
+                Err(e) => eprintln!("[cache] Bad CACHE_L1 ({e}). Using Redis without L1."),
+            }
+        }
+
+        match RedisCache::new(url, cfg.cache_locks).await {
+            Ok(c) => {
+                println!("[cache] Using Redis at {}", url);
+                return Arc::new(c);
+            }
+            Err(e) => eprintln!("[cache] Redis init failed: {e}. Falling back to in-memory cache."),
         }
     }
+
+    let memory = if url.starts_with("memory") {
+        MemoryCache::from_url(url).unwrap_or_else(|e| {
+            eprintln!("[cache] Bad CACHE_URL ({e}). Using in-memory defaults.");
+            MemoryCache::new(DEFAULT_MAX_ENTRIES, None)
+        })
+    } else {
+        if !url.starts_with("redis") {
+            eprintln!("CACHE_URL set ({}) but no matching cache backend. Using in-memory cache.", url);
+        } else if cfg!(not(feature = "redis-cache")) {
+            eprintln!("[cache] CACHE_URL is Redis but the redis-cache feature is off. Using in-memory cache.");
+        }
+        MemoryCache::new(DEFAULT_MAX_ENTRIES, None)
+    };
+    println!("[cache] Using in-memory cache (max {} entries)", memory.max_entries());
+    Arc::new(memory)
+}
+
+// CACHE_CODEC=msgpack (por defecto) | json; CACHE_COMPRESS_ABOVE=N bytes | off
+fn build_codec(cfg: &AppConfig) -> Codec {
+    let format = codec::Format::parse(&cfg.cache_codec).unwrap_or_else(|| {
+        eprintln!("[cache] Unknown CACHE_CODEC {:?}. Using msgpack.", cfg.cache_codec);
+        codec::Format::MessagePack
+    });
+    let compress_above = match cfg.cache_compress_above.trim() {
+        "off" | "none" => None,
+        n => Some(n.parse().unwrap_or_else(|_| {
+            eprintln!("[cache] Bad CACHE_COMPRESS_ABOVE {n:?}. Using {}.", codec::DEFAULT_COMPRESS_ABOVE);
+            codec::DEFAULT_COMPRESS_ABOVE
+        })),
+    };
+    let codec = Codec::new(format, compress_above);
+    println!("[cache] Encoding values as {}", codec.describe());
+    codec
+}
+
+async fn build_search(cfg: &AppConfig) -> Arc<dyn SearchEngine> {
+    let Some(url) = cfg.search_url.as_deref().filter(|u| !u.is_empty()) else {
+        return Arc::new(NoopSearch);
+    };
+
+    if url.starts_with("memory") {
+        println!("[search] Using in-memory index");
+        return Arc::new(MemorySearch::new());
+    }
+
+    #[cfg(feature = "search-opensearch")]
+    if url.starts_with("http://") || url.starts_with("https://") {
+        match OpenSearch::new(url, &cfg.search_index).await {
+            Ok(s) => {
+                println!("[search] Using OpenSearch at {url} (index prefix {})", cfg.search_index);
+                return Arc::new(s);
+            }
+            Err(e) => {
+                eprintln!("[search] OpenSearch init failed: {e}. Falling back to Noop.");
+                return Arc::new(NoopSearch);
+            }
+        }
+    }
+
+    eprintln!("SEARCH_URL set ({}) but no matching search backend. Using NoopSearch.", url);
     Arc::new(NoopSearch)
 }
 
+// Tamaño de lote para la carga inicial del índice
+const SEARCH_BATCH: usize = 500;
+
+// Carga inicial del índice de búsqueda con todos los libros y reseñas.
+pub async fn populate_search(books: &dyn BookRepo, reviews: &dyn ReviewRepo, search: &dyn SearchEngine) -> anyhow::Result<()> {
+    let books: Vec<BookDoc> = books
+        .list(&BookFilter::default())
+        .await?
+        .into_iter()
+        .filter_map(|b| Some(BookDoc { id: b.id?.to_hex(), title: b.title, summary: b.summary.unwrap_or_default() }))
+        .collect();
+    for batch in books.chunks(SEARCH_BATCH) {
+        search.index_books(batch).await?;
+    }
+
+    let reviews: Vec<ReviewDoc> = reviews
+        .list(&ReviewFilter::default())
+        .await?
+        .into_iter()
+        .filter_map(|r| Some(ReviewDoc { id: r.id?.to_hex(), book_id: r.book_id.to_hex(), content: r.text, score: r.score }))
+        .collect();
+    for batch in reviews.chunks(SEARCH_BATCH) {
+        search.index_reviews(batch).await?;
+    }
+
+    println!("[search] Indexed {} books and {} reviews", books.len(), reviews.len());
+    Ok(())
+}
+
+#[derive(Clone)]
 pub struct AppState {
-    pub db: Database,
+    // Datos: Mongo o en memoria (MONGO_URI=memory://)
+    pub authors: Arc<dyn AuthorRepo>,
+    pub books: Arc<dyn BookRepo>,
+    pub reviews: Arc<dyn ReviewRepo>,
+    pub sales: Arc<dyn SaleRepo>,
     pub cache: Arc<dyn Cache>,
+    pub codec: Codec,
+    // cálculos en curso por clave de cache (anti-estampida)
+    pub flights: Arc<SingleFlight>,
     pub search: Arc<dyn SearchEngine>,
+    // Idioma para $text (stemming y stop words), p.ej. "english", "spanish"
+    pub search_language: String,
+    // Búsquedas que precalienta `warm_up` (CACHE_WARMUP_QUERIES)
+    pub warmup_queries: Vec<String>,
+}
+
+// Valor en cache con su vencimiento "blando" (ms Unix): pasado ese momento
+// se sirve igual pero se refresca
+#[derive(Serialize, Deserialize)]
+struct Stamped<T> {
+    fresh_until: i64,
+    value: T,
+}
+
+impl<T> Stamped<T> {
+    fn is_fresh(&self) -> bool {
+        chrono::Utc::now().timestamp_millis() < self.fresh_until
+    }
 }
 
-pub async fn ensure_indexes(db: &Database) -> mongodb::error::Result<()> {
+// Resultado de `AppState::warm_up`
+#[derive(Default)]
+pub struct WarmupReport {
+    pub keys: usize,
+    pub failed: usize,
+    pub took: std::time::Duration,
+}
+
+impl WarmupReport {
+    fn record<T>(&mut self, what: &str, result: anyhow::Result<T>) {
+        match result {
+            Ok(_) => self.keys += 1,
+            Err(e) => {
+                eprintln!("[cache] Warm-up of {what} failed: {e}");
+                self.failed += 1;
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for WarmupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} keys in {} ms", self.keys, self.took.as_millis())?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+// Página pedida para cada grupo de /search
+#[derive(Debug, Clone, Copy)]
+pub struct SearchPages {
+    pub books: i64,
+    pub authors: i64,
+    pub reviews: i64,
+}
+
+// Largo máximo del fragmento de reseña en los resultados
+const SNIPPET_CHARS: usize = 200;
+
+// Índice de texto con pesos por campo; el idioma define el stemming.
+// Solo puede haber uno por colección; si cambió la configuración, se recrea.
+async fn ensure_text_index(
+    col: &mongodb::Collection<mongodb::bson::Document>,
+    name: &str,
+    weights: mongodb::bson::Document,
+    language: &str,
+) -> mongodb::error::Result<()> {
+    let mut keys = mongodb::bson::Document::new();
+    for field in weights.keys() {
+        keys.insert(field, "text");
+    }
+    let text_idx = || IndexModel::builder()
+        .keys(keys.clone())
+        .options(
+            IndexOptions::builder()
+                .name(name.to_string())
+                .weights(weights.clone())
+                .default_language(language.to_string())
+                .build(),
+        )
+        .build();
+
+    if let Err(e) = col.create_index(text_idx()).await {
+        if !matches!(*e.kind, ErrorKind::Command(ref c) if c.code == 85 || c.code == 86) {
+            return Err(e);
+        }
+        eprintln!("[indexes] Text index options changed on {}, recreating", col.name());
+        let existing: Vec<IndexModel> = col.list_indexes().await?.try_collect().await?;
+        for idx in existing {
+            let is_text = idx.keys.values().any(|v| v.as_str() == Some("text"));
+            if let (true, Some(old)) = (is_text, idx.options.and_then(|o| o.name)) {
+                col.drop_index(old).await?;
+            }
+        }
+        let _ = col.create_index(text_idx()).await?;
+    }
+    Ok(())
+}
+
+// Migración: date_of_birth / publication_date guardadas como "YYYY-MM-DD"
+// pasan a Date (medianoche UTC). Solo toca los strings con esa forma, así que es
+// idempotente y nunca pierde datos: el resto (o una fecha imposible como
+// 2023-02-30, que se queda como estaba) se deja intacto y se cuenta en el log.
+// Las agregaciones lo tratan como sin fecha (bson_date::if_date).
+pub async fn migrate_dates(db: &Database) -> mongodb::error::Result<()> {
+    for (collection, field) in [("authors", "date_of_birth"), ("books", "publication_date")] {
+        let col = db.collection::<mongodb::bson::Document>(collection);
+        let to_date = doc! {
+            "$dateFromString": {
+                "dateString": format!("${field}"),
+                "format": "%Y-%m-%d",
+                "onError": format!("${field}")
+            }
+        };
+        let res = col
+            .update_many(
+                doc! { field: { "$type": "string", "$regex": r"^\d{4}-\d{2}-\d{2}$" } },
+                vec![doc! { "$set": { field: to_date } }],
+            )
+            .await?;
+        let skipped = col.count_documents(doc! { field: { "$type": "string" } }).await?;
+        if res.modified_count > 0 || skipped > 0 {
+            eprintln!(
+                "[db] MIGRATE {collection}.{field}: {} documents to dates, {skipped} left as strings (not YYYY-MM-DD)",
+                res.modified_count
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn ensure_indexes(db: &Database, search_language: &str) -> mongodb::error::Result<()> {
     // ========== BOOKS ==========
     let books = db.collection::<mongodb::bson::Document>("books");
 
-    let text_idx = IndexModel::builder()
-        .keys(doc! { "title": "text", "summary": "text" })
-        .build();
-    let _ = books.create_index(text_idx).await?;
+    // el título pesa más que el resumen
+    ensure_text_index(&books, "books_text", doc! { "title": 3, "summary": 1 }, search_language).await?;
 
     let author_idx = IndexModel::builder()
         .keys(doc! { "author_id": 1 })
         .build();
     let _ = books.create_index(author_idx).await?;
 
+    // Autocompletado: rango por prefijo sobre el título, sin distinguir
+    // mayúsculas ni acentos (las consultas usan la misma collation)
+    let title_prefix_idx = IndexModel::builder()
+        .keys(doc! { "title": 1 })
+        .options(IndexOptions::builder().name("books_title_prefix".to_string()).collation(suggest_collation()).build())
+        .build();
+    let _ = books.create_index(title_prefix_idx).await?;
+
 
     let total_sales_idx = IndexModel::builder()
         .keys(doc! { "total_sales": -1 })
@@ -63,6 +347,18 @@ pub async fn ensure_indexes(db: &Database) -> mongodb::error::Result<()> {
         .build();
     let _ = reviews.create_index(reviews_idx).await?;
 
+    ensure_text_index(&reviews, "reviews_text", doc! { "text": 1 }, search_language).await?;
+
+    // ========== AUTHORS ==========
+    let authors = db.collection::<mongodb::bson::Document>("authors");
+
+    let name_prefix_idx = IndexModel::builder()
+        .keys(doc! { "name": 1 })
+        .options(IndexOptions::builder().name("authors_name_prefix".to_string()).collation(suggest_collation()).build())
+        .build();
+    let _ = authors.create_index(name_prefix_idx).await?;
+    ensure_text_index(&authors, "authors_text", doc! { "name": 3, "country": 2, "description": 1 }, search_language).await?;
+
     // ========== SALES ==========
     let sales = db.collection::<mongodb::bson::Document>("sales");
 
@@ -87,717 +383,699 @@ pub async fn ensure_indexes(db: &Database) -> mongodb::error::Result<()> {
 }
 
 pub async fn init_db() -> AppState {
-    let cfg = AppConfig::from_env();
+    init_state(AppConfig::from_env()).await
+}
 
-    let mut opts = ClientOptions::parse(&cfg.mongo_uri).await.expect("Invalid MONGO_URI");
-    opts.app_name = Some("bookreview".into());
+// MONGO_URI=memory:// => datos en el proceso (se pierden al parar), sin Mongo
+pub async fn init_state(cfg: AppConfig) -> AppState {
+    let repo: Arc<dyn Repo> = if cfg.mongo_uri.starts_with("memory") {
+        println!("[db] Using in-memory store (nothing is persisted)");
+        Arc::new(MemoryRepo::new())
+    } else {
+        let mut opts = ClientOptions::parse(&cfg.mongo_uri).await.expect("Invalid MONGO_URI");
+        opts.app_name = Some("bookreview".into());
 
-    let client = Client::with_options(opts).expect("Cannot create Mongo client");
-    let db = client.database(&cfg.db_name);
+        let client = Client::with_options(opts).expect("Cannot create Mongo client");
+        let db = client.database(&cfg.db_name);
 
-    // Build cache adapter (Redis if feature enabled and CACHE_URL present; otherwise Noop)
-    #[cfg(feature = "redis-cache")]
-    let cache: Arc<dyn Cache> = if let Some(url) = cfg.cache_url.clone() {
-        match RedisCache::new(&url).await {
-            Ok(c) => {
-                println!("[cache] Using Redis at {}", url);
-                Arc::new(c)
-            }
-            Err(e) => {
-                eprintln!("[cache] Redis init failed: {e}. Falling back to Noop.");
-                Arc::new(NoopCache)
-            }
+        if let Err(e) = migrate_dates(&db).await {
+            eprintln!("Failed to migrate dates: {e}");
         }
-    } else {
-        Arc::new(NoopCache)
+        if let Err(e) = ensure_indexes(&db, &cfg.search_language).await {
+            eprintln!("Failed to create indexes: {e}");
+        }
+        Arc::new(MongoRepo::new(db))
     };
 
-    #[cfg(not(feature = "redis-cache"))]
-    let cache: Arc<dyn Cache> = Arc::new(NoopCache);
+    let cache = build_cache(&cfg).await;
 
-    // Build search adapter (Noop for now; will switch to ES/OpenSearch behind a feature)
-    let search = build_search(&cfg);
+    // Build search adapter (memory:// => índice en memoria; http(s):// => OpenSearch; otherwise Noop)
+    let search = build_search(&cfg).await;
 
-    if let Err(e) = ensure_indexes(&db).await {
-        eprintln!("Failed to create indexes: {e}");
+    if search.enabled() {
+        if let Err(e) = populate_search(repo.as_ref(), repo.as_ref(), search.as_ref()).await {
+            eprintln!("[search] Initial indexing failed: {e}");
+        }
     }
 
-    AppState { db, cache, search }
+    let state = AppState {
+        authors: repo.clone(),
+        books: repo.clone(),
+        reviews: repo.clone(),
+        sales: repo,
+        cache,
+        codec: build_codec(&cfg),
+        flights: Arc::new(SingleFlight::default()),
+        search,
+        search_language: cfg.search_language,
+        warmup_queries: cfg.cache_warmup_queries,
+    };
+
+    // En segundo plano: el servidor atiende mientras tanto
+    if cfg.cache_warmup && state.cache.enabled() {
+        let state = state.clone();
+        tokio::spawn(async move { state.warm_up().await });
+    }
+
+    state
 }
 
 impl AppState {
     // Claves / prefijos
     pub const AUTHORS_SUMMARY_CACHE_KEY: &'static str = "authors:summary";
-    fn key_author(author_id: &str) -> String { format!("author:{author_id}") }
+    pub const TOP_RATED_CACHE_KEY: &'static str = "home:top_rated";
+    pub const TOP_SELLING_CACHE_KEY: &'static str = "home:top_selling";
     pub fn key_book_avg(book_id: &str) -> String { format!("book:{book_id}:avg_score") }
-    fn key_search(q: &str, page: i64, per_page: i64) -> String {
+
+    // Tags de invalidación. Cada valor cacheado declara de qué depende y cada
+    // escritura declara qué tocó (ver `invalidate_tags`):
+    // - book:<id> / author:<id>: valores de un libro o autor concreto
+    // - authors / books / reviews / sales: cualquier cambio en esa colección
+    // - search: todas las búsquedas y sugerencias
+    pub const TAG_SEARCH: &'static str = "search";
+    pub const TAG_AUTHORS: &'static str = "authors";
+    pub const TAG_BOOKS: &'static str = "books";
+    pub const TAG_REVIEWS: &'static str = "reviews";
+    pub const TAG_SALES: &'static str = "sales";
+    pub fn tag_book(book_id: &mongodb::bson::oid::ObjectId) -> String { format!("book:{}", book_id.to_hex()) }
+    pub fn tag_author(author_id: &mongodb::bson::oid::ObjectId) -> String { format!("author:{}", author_id.to_hex()) }
+    // Lo que depende de todas las colecciones (resúmenes, búsquedas)
+    fn tags_all_collections() -> Vec<String> {
+        [Self::TAG_AUTHORS, Self::TAG_BOOKS, Self::TAG_REVIEWS, Self::TAG_SALES].map(String::from).to_vec()
+    }
+    // Top por nota: reseñas, y título / autor de cada libro
+    fn tags_top_rated() -> Vec<String> {
+        [Self::TAG_REVIEWS, Self::TAG_BOOKS, Self::TAG_AUTHORS].map(String::from).to_vec()
+    }
+    // Top por ventas: ventas, libros y nombre / ventas de cada autor
+    fn tags_top_selling() -> Vec<String> {
+        [Self::TAG_SALES, Self::TAG_BOOKS, Self::TAG_AUTHORS].map(String::from).to_vec()
+    }
+    // Lo que depende de cualquier dato y además de la búsqueda
+    fn tags_search() -> Vec<String> {
+        let mut tags = Self::tags_all_collections();
+        tags.push(Self::TAG_SEARCH.to_string());
+        tags
+    }
+    fn key_search(kind: SearchHitKind, q: &str, mode: SearchMode, facets: &FacetSelection, page: i64, per_page: i64) -> String {
         let norm = q.trim().to_lowercase().replace(char::is_whitespace, "+");
-        format!("search:books:q:{norm}:p:{page}:pp:{per_page}")
+        let kind = match kind {
+            SearchHitKind::Book => "books",
+            SearchHitKind::Author => "authors",
+            SearchHitKind::Review => "reviews",
+        };
+        let mode = mode.as_str();
+        let facets = facets.cache_key();
+        format!("search:{kind}:q:{norm}:m:{mode}{facets}:p:{page}:pp:{per_page}")
+    }
+
+    fn key_suggest(prefix: &str, limit: i64) -> String {
+        let norm = prefix.trim().to_lowercase().replace(char::is_whitespace, "+");
+        format!("search:suggest:{norm}:n:{limit}")
     }
 
     // TTLs (ajústalos a gusto)
     const TTL_AUTHORS_SUMMARY: std::time::Duration = std::time::Duration::from_secs(300);
-    const TTL_AUTHOR: std::time::Duration = std::time::Duration::from_secs(600);
     const TTL_BOOK_AVG: std::time::Duration = std::time::Duration::from_secs(120);
     const TTL_SEARCH: std::time::Duration = std::time::Duration::from_secs(300);
+    const TTL_SUGGEST: std::time::Duration = std::time::Duration::from_secs(60);
+    const TTL_TOP_BOOKS: std::time::Duration = std::time::Duration::from_secs(300);
+
+    // Stale-while-revalidate: pasado el TTL, el valor se sigue sirviendo
+    // durante esta ventana mientras una tarea lo recalcula en segundo plano
+    const STALE_AUTHORS_SUMMARY: std::time::Duration = std::time::Duration::from_secs(300);
+    const STALE_SEARCH: std::time::Duration = std::time::Duration::from_secs(120);
+    const STALE_TOP_BOOKS: std::time::Duration = std::time::Duration::from_secs(120);
+
+    // Lock distribuido (CACHE_LOCKS): cuánto puede durar un cálculo, y cuánto
+    // esperamos a que otra instancia deje el valor antes de calcularlo igual
+    const LOCK_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+    const LOCK_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
+    const LOCK_POLL: std::time::Duration = std::time::Duration::from_millis(100);
 
-    // --- Helpers JSON <-> bytes ---
-    async fn cache_get_json<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
-        if let Some(bytes) = self.cache.get(key).await {
-            serde_json::from_slice(&bytes).ok()
-        } else {
-            None
+    // Máximo de hits que pedimos al motor de búsqueda (para contar y paginar)
+    const MAX_SEARCH_HITS: usize = 1000;
+
+    // Autocompletado: prefijo mínimo y tope de sugerencias por tipo
+    pub const SUGGEST_MIN_CHARS: usize = 2;
+    pub const SUGGEST_MAX_LIMIT: i64 = 10;
+
+    // Resultados por página en /search
+    const SEARCH_PER_PAGE_BOOKS: i64 = 10;
+    const SEARCH_PER_PAGE_OTHERS: i64 = 5;
+    // Página más alta que se atiende (?page= viene del usuario); más allá no
+    // hay resultados y cada página distinta sería otra clave de cache
+    const MAX_SEARCH_PAGE: i64 = 1000;
+
+    // --- Helpers valor <-> bytes (formato según `codec`) ---
+    // Lectura que cuenta en las métricas (hit/miss y latencia)
+    async fn cache_get_value<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let started = std::time::Instant::now();
+        let value = self.cache_peek_value(key).await;
+        match value {
+            Some(_) => metrics().hit(key, started.elapsed()),
+            None => metrics().miss(key, started.elapsed()),
         }
+        value
     }
-    async fn cache_set_json<T: Serialize>(&self, key: &str, value: &T, ttl: Option<std::time::Duration>) {
-        if let Ok(bytes) = serde_json::to_vec(value) {
-            self.cache.set(key, &bytes, ttl).await;
+    // Lectura sin contar, para los re-chequeos tras esperar turno
+    async fn cache_peek_value<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let bytes = self.cache.get(key).await?;
+        match self.codec.decode(&bytes) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                eprintln!("[cache] Undecodable entry {key}: {e}");
+                metrics().error(key);
+                None
+            }
+        }
+    }
+    async fn cache_set_value<T: Serialize>(&self, key: &str, value: &T, ttl: Option<std::time::Duration>, tags: &[String]) {
+        match self.codec.encode(value) {
+            Ok(bytes) => {
+                self.cache.set_tagged(key, &bytes, ttl, tags).await;
+                metrics().set(key);
+            }
+            Err(e) => {
+                eprintln!("[cache] Cannot encode {key}: {e}");
+                metrics().error(key);
+            }
         }
     }
-    pub async fn cache_del_key(&self, key: &str) { self.cache.del(key).await; }
-    pub async fn cache_del_pref(&self, prefix: &str) { self.cache.del_prefix(prefix).await; }
 
-    //get_authors_summary sin cache
-    pub async fn get_authors_summary(&self) -> mongodb::error::Result<Vec<AuthorSummary>> {
-        let pipeline = vec![
-            // Lookup books for each author
-            doc! {
-                "$lookup": {
-                    "from": "books",
-                    "localField": "_id",
-                    "foreignField": "author_id",
-                    "as": "books"
-                }
-            },
-            // Unwind books to get individual book documents
-            doc! {
-                "$unwind": {
-                    "path": "$books",
-                    "preserveNullAndEmptyArrays": true
-                }
-            },
-            // Lookup reviews for each book
-            doc! {
-                "$lookup": {
-                    "from": "reviews",
-                    "localField": "books._id",
-                    "foreignField": "book_id",
-                    "as": "reviews"
-                }
-            },
-            // Lookup sales for each book
-            doc! {
-                "$lookup": {
-                    "from": "sales",
-                    "localField": "books._id",
-                    "foreignField": "book_id",
-                    "as": "sales"
-                }
-            },
-            // Group by author to calculate aggregated values
-            doc! {
-                "$group": {
-                    "_id": "$_id",
-                    "name": { "$first": "$name" },
-                    "published_books": {
-                        "$sum": {
-                            "$cond": [
-                                { "$ne": ["$books", null] },
-                                1,
-                                0
-                            ]
-                        }
-                    },
-                    "total_reviews": { "$push": "$reviews" },
-                    "total_sales_data": { "$push": "$sales" }
-                }
-            },
-            // Calculate average score and total sales
-            doc! {
-                "$addFields": {
-                    "all_scores": {
-                        "$reduce": {
-                            "input": "$total_reviews",
-                            "initialValue": [],
-                            "in": { "$concatArrays": ["$$value", "$$this"] }
-                        }
-                    },
-                    "all_sales": {
-                        "$reduce": {
-                            "input": "$total_sales_data",
-                            "initialValue": [],
-                            "in": { "$concatArrays": ["$$value", "$$this"] }
-                        }
+    // Cache con protección contra estampidas:
+    // - fresco (< ttl): se sirve
+    // - viejo (< ttl + stale): se sirve y una sola tarea lo refresca de fondo
+    // - ausente: un solo cálculo por clave en el proceso (single-flight) y,
+    //   con CACHE_LOCKS, uno solo entre instancias
+    // `compute` recibe una copia del estado para poder correr en otra tarea.
+    async fn cached_swr<T, F, Fut>(&self, key: &str, tags: Vec<String>, ttl: std::time::Duration, stale: std::time::Duration, compute: F) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: FnOnce(AppState) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        // hits, misses y vencidos se cuentan en metrics() (ver /metrics)
+        if let Some(entry) = self.cache_get_value::<Stamped<T>>(key).await {
+            if !entry.is_fresh() {
+                metrics().stale(key);
+                let (state, key) = (self.clone(), key.to_string());
+                tokio::spawn(async move {
+                    // ya lo está refrescando otra tarea
+                    let Some(_flight) = state.flights.try_lock(&key) else { return };
+                    if let Err(e) = state.cache_compute(&key, &tags, ttl, stale, compute).await {
+                        eprintln!("[cache] Background refresh of {key} failed: {e}");
                     }
-                }
-            },
-            doc! {
-                "$addFields": {
-                    "average_score": {
-                        "$cond": [
-                            { "$gt": [{ "$size": "$all_scores" }, 0] },
-                            { "$avg": "$all_scores.score" },
-                            0.0
-                        ]
-                    },
-                    "total_sales": {
-                        "$sum": "$all_sales.units"
+                });
+            }
+            return Ok(entry.value);
+        }
+
+        let _flight = self.flights.lock(key).await;
+        // quien tenía el turno ya lo dejó en la cache
+        if let Some(entry) = self.cache_peek_value::<Stamped<T>>(key).await {
+            return Ok(entry.value);
+        }
+        self.cache_compute(key, &tags, ttl, stale, compute).await
+    }
+
+    async fn cache_compute<T, F, Fut>(&self, key: &str, tags: &[String], ttl: std::time::Duration, stale: std::time::Duration, compute: F) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(AppState) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let token = match self.cache.lock(key, Self::LOCK_TTL).await {
+            Some(token) => token,
+            None => {
+                // otra instancia lo está calculando: esperamos su resultado
+                let deadline = std::time::Instant::now() + Self::LOCK_WAIT;
+                while std::time::Instant::now() < deadline {
+                    tokio::time::sleep(Self::LOCK_POLL).await;
+                    if let Some(entry) = self.cache_peek_value::<Stamped<T>>(key).await {
+                        if entry.is_fresh() {
+                            return Ok(entry.value);
+                        }
                     }
                 }
-            },
-            doc! {
-                "$project": {
-                    "author_id": "$_id",
-                    "name": 1,
-                    "published_books": 1,
-                    "average_score": { "$round": ["$average_score", 2] },
-                    "total_sales": 1
-                }
-            },
-            // Sort by name
-            doc! {
-                "$sort": {
-                    "name": 1
-                }
-            }
-        ];
-
-        let authors_collection = self.db.collection::<mongodb::bson::Document>("authors");
-        let cursor = authors_collection.aggregate(pipeline).await?;
-        
-        let documents: Vec<mongodb::bson::Document> = cursor.try_collect().await?;
-        let mut summaries = Vec::new();
-        
-        for doc in documents {
-            if let Ok(summary) = mongodb::bson::from_document::<AuthorSummary>(doc) {
-                summaries.push(summary);
+                String::new()
             }
+        };
+
+        let started = std::time::Instant::now();
+        let result = compute(self.clone()).await;
+        metrics().load(key, started.elapsed());
+        if let Ok(value) = &result {
+            let entry = Stamped { fresh_until: chrono::Utc::now().timestamp_millis() + ttl.as_millis() as i64, value };
+            self.cache_set_value(key, &entry, Some(ttl + stale), tags).await;
         }
-        
-        Ok(summaries)
+        self.cache.unlock(key, &token).await;
+        result
     }
 
-    //get_authors_summary + cache
-    pub async fn get_authors_summary_cached(&self) -> mongodb::error::Result<Vec<AuthorSummary>> {
-        if let Some(cached) = self.cache_get_json::<Vec<AuthorSummary>>(Self::AUTHORS_SUMMARY_CACHE_KEY).await {
-            eprintln!("[cache] HIT {}", Self::AUTHORS_SUMMARY_CACHE_KEY);
-            return Ok(cached);
-        }
-        eprintln!("[cache] MISS {} -> Mongo", Self::AUTHORS_SUMMARY_CACHE_KEY);
-        let data = self.get_authors_summary().await?;
-        self.cache_set_json(
+    // Recalcula `key` aunque esté en la cache (warm-up), con el mismo turno
+    // que las peticiones para no calcularlo dos veces a la vez
+    async fn cache_refresh<T, F, Fut>(&self, key: &str, tags: &[String], ttl: std::time::Duration, stale: std::time::Duration, compute: F) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(AppState) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let _flight = self.flights.lock(key).await;
+        self.cache_compute(key, tags, ttl, stale, compute).await
+    }
+
+    // Precalienta lo primero que se pide tras un deploy o un seeder:
+    // authors:summary, los tops de la home, los promedios por libro (los
+    // siembra el top por nota) y la primera página de las búsquedas populares. Recalcula aunque ya
+    // haya valor: tras escrituras masivas directas en Mongo puede estar viejo.
+    pub async fn warm_up(&self) -> WarmupReport {
+        let started = std::time::Instant::now();
+        let mut report = WarmupReport::default();
+        eprintln!("[cache] WARMUP started ({} queries)", self.warmup_queries.len());
+
+        let summary = self.cache_refresh(
             Self::AUTHORS_SUMMARY_CACHE_KEY,
-            &data,
-            Some(Self::TTL_AUTHORS_SUMMARY)
+            &Self::tags_all_collections(),
+            Self::TTL_AUTHORS_SUMMARY,
+            Self::STALE_AUTHORS_SUMMARY,
+            |s| async move { s.get_authors_summary().await },
         ).await;
-        Ok(data)
+        report.record(Self::AUTHORS_SUMMARY_CACHE_KEY, summary);
+
+        // el top por nota siembra además los promedios de sus libros
+        let top_rated = self.cache_refresh(
+            Self::TOP_RATED_CACHE_KEY,
+            &Self::tags_top_rated(),
+            Self::TTL_TOP_BOOKS,
+            Self::STALE_TOP_BOOKS,
+            |s| async move { s.get_top_rated_books().await },
+        ).await;
+        match top_rated {
+            Ok(books) => report.keys += 1 + books.len(),
+            Err(e) => report.record::<()>(Self::TOP_RATED_CACHE_KEY, Err(e)),
+        }
+
+        let top_selling = self.cache_refresh(
+            Self::TOP_SELLING_CACHE_KEY,
+            &Self::tags_top_selling(),
+            Self::TTL_TOP_BOOKS,
+            Self::STALE_TOP_BOOKS,
+            |s| async move { s.get_top_selling_books().await },
+        ).await;
+        report.record(Self::TOP_SELLING_CACHE_KEY, top_selling);
+
+        let facets = FacetSelection::default();
+        for query in &self.warmup_queries {
+            for (kind, per_page) in [
+                (SearchHitKind::Book, Self::SEARCH_PER_PAGE_BOOKS),
+                (SearchHitKind::Author, Self::SEARCH_PER_PAGE_OTHERS),
+                (SearchHitKind::Review, Self::SEARCH_PER_PAGE_OTHERS),
+            ] {
+                let key = Self::key_search(kind, query, SearchMode::Auto, &facets, 1, per_page);
+                let q = query.clone();
+                let result = self.cache_refresh(&key, &Self::tags_search(), Self::TTL_SEARCH, Self::STALE_SEARCH, move |s| async move {
+                    s.search(kind, &q, SearchMode::Auto, &FacetSelection::default(), 1, per_page).await
+                }).await;
+                report.record(&key, result);
+            }
+        }
+
+        report.took = started.elapsed();
+        eprintln!("[cache] WARMUP done: {report}");
+        report
     }
 
-    // --- Promedio sin cache ---
-    pub async fn get_book_average_score(&self, book_id: &mongodb::bson::oid::ObjectId) -> mongodb::error::Result<f64> {
-        use futures_util::TryStreamExt;
+    // Hasta `limit` claves de una familia (para /admin/cache)
+    pub async fn cache_family_keys(&self, family: CacheFamily, limit: usize) -> Vec<String> {
+        let mut keys = Vec::new();
+        for prefix in family.prefixes() {
+            keys.extend(self.cache.keys(prefix, limit - keys.len()).await);
+            if keys.len() >= limit {
+                break;
+            }
+        }
+        keys
+    }
 
-        #[derive(serde::Deserialize)]
-        struct AvgDoc { avg: Option<f64> }
+    // Vacía una familia entera (p.ej. tras cambiar el formato de sus valores)
+    pub async fn cache_flush_family(&self, family: CacheFamily) {
+        eprintln!("[cache] FLUSH {}", family.name());
+        for prefix in family.prefixes() {
+            self.cache.del_prefix(prefix).await;
+        }
+    }
 
-        let reviews = self.db.collection::<mongodb::bson::Document>("reviews");
-        let pipeline = vec![
-            doc! { "$match": { "book_id": book_id } },
-            doc! { "$group": { "_id": null, "avg": { "$avg": "$score" } } },
-        ];
+    // Borra todo lo cacheado bajo alguno de los tags
+    pub async fn invalidate_tags(&self, tags: &[String]) {
+        eprintln!("[cache] INVALIDATE {}", tags.join(", "));
+        self.cache.invalidate_tags(tags).await;
+    }
 
-        let mut cur = reviews.aggregate(pipeline).await?;
-        if let Some(doc) = cur.try_next().await? {
-            let avg = mongodb::bson::from_document::<AvgDoc>(doc).ok().and_then(|d| d.avg).unwrap_or(0.0);
-            Ok(avg)
-        } else {
-            Ok(0.0)
+    // --- Helpers de indexación (mantienen el motor de búsqueda al día) ---
+    // El write en Mongo ya se hizo: si el índice falla lo registramos y seguimos,
+    // la próxima reindexación al arrancar lo corrige.
+    pub async fn search_index_book(&self, b: &Book) {
+        if let Some(id) = b.id {
+            let id = id.to_hex();
+            if let Err(e) = self.search.index_book(&id, &b.title, b.summary.as_deref().unwrap_or("")).await {
+                eprintln!("[search] index_book {id} failed: {e}");
+            }
+        }
+    }
+    pub async fn search_delete_book(&self, book_id: &mongodb::bson::oid::ObjectId) {
+        if let Err(e) = self.search.delete_book(&book_id.to_hex()).await {
+            eprintln!("[search] delete_book {book_id} failed: {e}");
+        }
+    }
+    pub async fn search_index_review(&self, r: &Review) {
+        if let Some(id) = r.id {
+            let id = id.to_hex();
+            if let Err(e) = self.search.index_review(&id, &r.book_id.to_hex(), &r.text, r.score).await {
+                eprintln!("[search] index_review {id} failed: {e}");
+            }
+        }
+    }
+    pub async fn search_delete_review(&self, review_id: &mongodb::bson::oid::ObjectId) {
+        if let Err(e) = self.search.delete_review(&review_id.to_hex()).await {
+            eprintln!("[search] delete_review {review_id} failed: {e}");
         }
     }
 
+    //get_authors_summary sin cache
+    pub async fn get_authors_summary(&self) -> anyhow::Result<Vec<AuthorSummary>> {
+        self.authors.summary().await
+    }
+
+    //get_authors_summary + cache (single-flight + stale-while-revalidate)
+    pub async fn get_authors_summary_cached(&self) -> anyhow::Result<Vec<AuthorSummary>> {
+        self.cached_swr(
+            Self::AUTHORS_SUMMARY_CACHE_KEY,
+            Self::tags_all_collections(),
+            Self::TTL_AUTHORS_SUMMARY,
+            Self::STALE_AUTHORS_SUMMARY,
+            |s| async move { s.get_authors_summary().await },
+        ).await
+    }
+
+    // --- Promedio sin cache ---
+    pub async fn get_book_average_score(&self, book_id: &mongodb::bson::oid::ObjectId) -> anyhow::Result<f64> {
+        self.reviews.average_score(book_id).await
+    }
+
     // --- Promedio con cache ---
-    pub async fn get_book_average_score_cached(&self, book_id: &mongodb::bson::oid::ObjectId) -> mongodb::error::Result<f64> {
+    pub async fn get_book_average_score_cached(&self, book_id: &mongodb::bson::oid::ObjectId) -> anyhow::Result<f64> {
         let key = Self::key_book_avg(&book_id.to_hex());
 
-        if let Some(avg) = self.cache_get_json::<f64>(&key).await {
-            eprintln!("[cache] HIT {key}");
+        if let Some(avg) = self.cache_get_value::<f64>(&key).await {
             return Ok(avg);
         }
 
-        eprintln!("[cache] MISS {key} -> Mongo");
+        let started = std::time::Instant::now();
         let avg = self.get_book_average_score(book_id).await?;
-        self.cache_set_json(&key, &avg, Some(Self::TTL_BOOK_AVG)).await;
+        metrics().load(&key, started.elapsed());
+        self.cache_set_value(&key, &avg, Some(Self::TTL_BOOK_AVG), &[Self::tag_book(book_id)]).await;
         Ok(avg)
     }
 
-    pub async fn get_top_rated_books(&self) -> mongodb::error::Result<Vec<TopRatedBook>> {
-        let pipeline = vec![
-            // Start with books
-            doc! {
-                "$lookup": {
-                    "from": "reviews",
-                    "localField": "_id",
-                    "foreignField": "book_id",
-                    "as": "reviews"
-                }
-            },
-            // Only include books that have reviews
-            doc! {
-                "$match": {
-                    "reviews": { "$ne": [] }
-                }
-            },
-            // Calculate average score for each book
-            doc! {
-                "$addFields": {
-                    "average_score": { "$avg": "$reviews.score" },
-                    "total_reviews": { "$size": "$reviews" }
-                }
-            },
-            // Sort by average score (highest first)
-            doc! {
-                "$sort": {
-                    "average_score": -1,
-                    "total_reviews": -1  // Secondary sort by number of reviews
-                }
-            },
-            // Take top 10
-            doc! {
-                "$limit": 10
-            },
-            // Lookup author information
-            doc! {
-                "$lookup": {
-                    "from": "authors",
-                    "localField": "author_id",
-                    "foreignField": "_id",
-                    "as": "author"
-                }
-            },
-            // Unwind author
-            doc! {
-                "$unwind": "$author"
-            },
-            // Add highest and lowest rated reviews
-            doc! {
-                "$addFields": {
-                    "highest_rated_review": {
-                        "$arrayElemAt": [
-                            {
-                                "$filter": {
-                                    "input": "$reviews",
-                                    "as": "review",
-                                    "cond": {
-                                        "$eq": [
-                                            "$$review.score",
-                                            { "$max": "$reviews.score" }
-                                        ]
-                                    }
-                                }
-                            },
-                            0
-                        ]
-                    },
-                    "lowest_rated_review": {
-                        "$arrayElemAt": [
-                            {
-                                "$filter": {
-                                    "input": "$reviews",
-                                    "as": "review",
-                                    "cond": {
-                                        "$eq": [
-                                            "$$review.score",
-                                            { "$min": "$reviews.score" }
-                                        ]
-                                    }
-                                }
-                            },
-                            0
-                        ]
-                    }
-                }
-            },
-            doc! {
-                "$project": {
-                    "book_id": "$_id",
-                    "title": 1,
-                    "author_name": "$author.name",
-                    "average_score": { "$round": ["$average_score", 2] },
-                    "total_reviews": 1,
-                    "highest_rated_review": {
-                        "text": "$highest_rated_review.text",
-                        "score": "$highest_rated_review.score",
-                        "up_votes": "$highest_rated_review.up_votes"
-                    },
-                    "lowest_rated_review": {
-                        "text": "$lowest_rated_review.text",
-                        "score": "$lowest_rated_review.score",
-                        "up_votes": "$lowest_rated_review.up_votes"
-                    }
-                }
-            }
-        ];
-
-        let books_collection = self.db.collection::<mongodb::bson::Document>("books");
-        let cursor = books_collection.aggregate(pipeline).await?;
-        
-        let documents: Vec<mongodb::bson::Document> = cursor.try_collect().await?;
-        let mut top_books = Vec::new();
-        
-        for doc in documents {
-            if let Ok(book) = mongodb::bson::from_document::<TopRatedBook>(doc) {
-                top_books.push(book);
-            }
-        }
+    pub async fn get_top_rated_books(&self) -> anyhow::Result<Vec<TopRatedBook>> {
+        let top_books = self.books.top_rated().await?;
 
-        // Seed per-book average cache using the scores computed by this pipeline.
+        // Seed per-book average cache using the scores computed by this query.
         // This makes calls to `get_book_average_score_cached` a HIT.
         for b in &top_books {
             let key = Self::key_book_avg(&b.book_id.to_hex());
             // store the average 
-            self.cache_set_json(&key, &b.average_score, Some(Self::TTL_BOOK_AVG)).await;
-            eprintln!("[cache] SEED {key}");
+            self.cache_set_value(&key, &b.average_score, Some(Self::TTL_BOOK_AVG), &[Self::tag_book(&b.book_id)]).await;
         }
         Ok(top_books)
     }
 
-    pub async fn get_top_selling_books(&self) -> mongodb::error::Result<Vec<TopSellingBook>> {
-        let pipeline = vec![
-            // Start with books
-            doc! {
-                "$lookup": {
-                    "from": "sales",
-                    "localField": "_id",
-                    "foreignField": "book_id",
-                    "as": "sales"
-                }
-            },
-            // Calculate total sales for each book
-            doc! {
-                "$addFields": {
-                    "book_total_sales": { "$sum": "$sales.units" },
-                    "publication_year": {
-                        "$cond": [
-                            { "$ne": ["$publication_date", null] },
-                            {
-                                "$toInt": {
-                                    "$arrayElemAt": [
-                                        { "$split": ["$publication_date", "-"] },
-                                        0
-                                    ]
-                                }
-                            },
-                            null
-                        ]
-                    }
-                }
-            },
-            // Only include books with sales
-            doc! {
-                "$match": {
-                    "book_total_sales": { "$gt": 0 }
-                }
-            },
-            // Sort by total sales (highest first) and limit to top 50
-            doc! {
-                "$sort": {
-                    "book_total_sales": -1
-                }
-            },
-            doc! {
-                "$limit": 50
-            },
-            // Lookup author information
-            doc! {
-                "$lookup": {
-                    "from": "authors",
-                    "localField": "author_id",
-                    "foreignField": "_id",
-                    "as": "author"
-                }
-            },
-            doc! {
-                "$unwind": "$author"
-            },
-            // Calculate author total sales (all their books)
-            doc! {
-                "$lookup": {
-                    "from": "books",
-                    "localField": "author._id",
-                    "foreignField": "author_id",
-                    "as": "author_books"
-                }
-            },
-            doc! {
-                "$lookup": {
-                    "from": "sales",
-                    "localField": "author_books._id",
-                    "foreignField": "book_id",
-                    "as": "author_sales"
-                }
-            },
-            doc! {
-                "$addFields": {
-                    "author_total_sales": { "$sum": "$author_sales.units" }
-                }
-            },
-            doc! {
-                "$lookup": {
-                    "from": "books",
-                    "let": { "pub_year": "$publication_year" },
-                    "pipeline": [
-                        {
-                            "$addFields": {
-                                "book_pub_year": {
-                                    "$cond": [
-                                        { "$ne": ["$publication_date", null] },
-                                        {
-                                            "$toInt": {
-                                                "$arrayElemAt": [
-                                                    { "$split": ["$publication_date", "-"] },
-                                                    0
-                                                ]
-                                            }
-                                        },
-                                        null
-                                    ]
-                                }
-                            }
-                        },
-                        {
-                            "$match": {
-                                "$expr": { "$eq": ["$book_pub_year", "$$pub_year"] }
-                            }
-                        },
-                        {
-                            "$lookup": {
-                                "from": "sales",
-                                "localField": "_id",
-                                "foreignField": "book_id",
-                                "as": "book_sales"
-                            }
-                        },
-                        {
-                            "$addFields": {
-                                "total_sales": { "$sum": "$book_sales.units" }
-                            }
-                        },
-                        {
-                            "$sort": { "total_sales": -1 }
-                        },
-                        {
-                            "$limit": 5
-                        },
-                        {
-                            "$project": { "_id": 1 }
-                        }
-                    ],
-                    "as": "top_5_same_year"
-                }
-            },
-            doc! {
-                "$addFields": {
-                    "was_top_5_in_publication_year": {
-                        "$cond": [
-                            { "$ne": ["$publication_year", null] },
-                            {
-                                "$in": [
-                                    "$_id",
-                                    { "$map": {
-                                        "input": "$top_5_same_year",
-                                        "as": "book",
-                                        "in": "$$book._id"
-                                    }}
-                                ]
-                            },
-                            false
-                        ]
-                    }
-                }
-            },
-            doc! {
-                "$project": {
-                    "book_id": "$_id",
-                    "title": 1,
-                    "author_name": "$author.name",
-                    "publication_date": 1,
-                    "book_total_sales": 1,
-                    "author_total_sales": 1,
-                    "was_top_5_in_publication_year": 1
-                }
-            }
-        ];
-
-        let books_collection = self.db.collection::<mongodb::bson::Document>("books");
-        let cursor = books_collection.aggregate(pipeline).await?;
-        
-        let documents: Vec<mongodb::bson::Document> = cursor.try_collect().await?;
-        let mut top_selling_books = Vec::new();
-        
-        for doc in documents {
-            if let Ok(book) = mongodb::bson::from_document::<TopSellingBook>(doc) {
-                top_selling_books.push(book);
-            }
-        }
-        
-        Ok(top_selling_books)
+    // Tops de la home + cache (single-flight + stale-while-revalidate); se
+    // invalidan con cualquier escritura de reseñas / ventas (ver tags_top_*)
+    pub async fn get_top_rated_books_cached(&self) -> anyhow::Result<Vec<TopRatedBook>> {
+        self.cached_swr(
+            Self::TOP_RATED_CACHE_KEY,
+            Self::tags_top_rated(),
+            Self::TTL_TOP_BOOKS,
+            Self::STALE_TOP_BOOKS,
+            |s| async move { s.get_top_rated_books().await },
+        ).await
+    }
+
+    pub async fn get_top_selling_books_cached(&self) -> anyhow::Result<Vec<TopSellingBook>> {
+        self.cached_swr(
+            Self::TOP_SELLING_CACHE_KEY,
+            Self::tags_top_selling(),
+            Self::TTL_TOP_BOOKS,
+            Self::STALE_TOP_BOOKS,
+            |s| async move { s.get_top_selling_books().await },
+        ).await
+    }
+
+    pub async fn get_top_selling_books(&self) -> anyhow::Result<Vec<TopSellingBook>> {
+        self.books.top_selling().await
     }
 
     // --- Búsqueda con cache (para "common queries") ---
-    pub async fn search_books_cached(
+    pub async fn search_cached(
         &self,
+        kind: SearchHitKind,
         query: &str,
+        mode: SearchMode,
+        facets: &FacetSelection,
         page: i64,
         per_page: i64
-    ) -> mongodb::error::Result<PaginatedSearchResults> {
-        let key = Self::key_search(query, page, per_page);
-
-        if let Some(cached) = self.cache_get_json::<PaginatedSearchResults>(&key).await {
-            eprintln!("[cache] HIT {key}");
-            return Ok(cached);
-        }
-
-        eprintln!("[cache] MISS {key} -> Mongo");
-        let data = self.search_books(query, page, per_page).await?;
-        self.cache_set_json(&key, &data, Some(Self::TTL_SEARCH)).await;
-        Ok(data)
+    ) -> anyhow::Result<PaginatedSearchResults> {
+        let page = page.clamp(1, Self::MAX_SEARCH_PAGE);
+        let key = Self::key_search(kind, query, mode, facets, page, per_page);
+        let (query, facets) = (query.to_string(), facets.clone());
+        self.cached_swr(&key, Self::tags_search(), Self::TTL_SEARCH, Self::STALE_SEARCH, move |s| async move {
+            s.search(kind, &query, mode, &facets, page, per_page).await
+        }).await
     }
 
-    pub async fn search_books(&self, query: &str, page: i64, per_page: i64) -> mongodb::error::Result<PaginatedSearchResults> {
-        let skip = (page - 1) * per_page;
-        
-        // Split the query into words and create search terms
-        let search_terms: Vec<&str> = query.split_whitespace().collect();
-        
-        if search_terms.is_empty() {
-            return Ok(PaginatedSearchResults {
-                results: vec![],
-                current_page: page,
-                total_pages: 0,
-                total_results: 0,
-                has_next: false,
-                has_prev: false,
-                query: query.to_string(),
-            });
-        }
-
-        // Create regex patterns for each search term
-        let regex_patterns: Vec<mongodb::bson::Document> = search_terms
-            .iter()
-            .map(|term| doc! { 
-                "$or": [
-                    { "title": { "$regex": term, "$options": "i" } },
-                    { "summary": { "$regex": term, "$options": "i" } }
-                ]
-            })
-            .collect();
+    // Búsqueda global: libros, autores y reseñas, cada grupo con su página.
+    // Las facetas solo filtran los libros.
+    pub async fn search_all_cached(&self, query: &str, mode: SearchMode, facets: &FacetSelection, pages: SearchPages) -> anyhow::Result<GroupedSearchResults> {
+        let no_facets = FacetSelection::default();
+        let (books, authors, reviews) = tokio::join!(
+            self.search_cached(SearchHitKind::Book, query, mode, facets, pages.books, Self::SEARCH_PER_PAGE_BOOKS),
+            self.search_cached(SearchHitKind::Author, query, mode, &no_facets, pages.authors, Self::SEARCH_PER_PAGE_OTHERS),
+            self.search_cached(SearchHitKind::Review, query, mode, &no_facets, pages.reviews, Self::SEARCH_PER_PAGE_OTHERS),
+        );
+        Ok(GroupedSearchResults {
+            query: query.to_string(),
+            mode: mode.as_str().to_string(),
+            books: books?,
+            authors: authors?,
+            reviews: reviews?,
+        })
+    }
 
-        let search_filter = doc! {
-            "$and": regex_patterns
-        };
+    // Orden de preferencia: prefix => regex; si hay motor externo => motor;
+    // si no => índice de texto (y en modo auto, regex cuando no encuentra nada).
+    pub async fn search(&self, kind: SearchHitKind, query: &str, mode: SearchMode, facets: &FacetSelection, page: i64, per_page: i64) -> anyhow::Result<PaginatedSearchResults> {
+        if query_parser::parse(query).is_empty() {
+            return Ok(Self::search_page(kind, SearchPage::default(), query, mode, page, per_page));
+        }
+        let regex = |fuzzy| SearchBase::Regex { query: query.to_string(), fuzzy };
+        if mode == SearchMode::Prefix {
+            return self.search_run(kind, &regex(false), query, mode, facets, page, per_page).await;
+        }
 
-        // Count total results
-        let books_collection = self.db.collection::<mongodb::bson::Document>("books");
-        let total_results = books_collection.count_documents(search_filter.clone()).await?;
-        let total_pages = (total_results as f64 / per_page as f64).ceil() as i64;
-
-        // Get paginated results
-        let pipeline = vec![
-            doc! { "$match": search_filter },
-            doc! {
-                "$lookup": {
-                    "from": "authors",
-                    "localField": "author_id",
-                    "foreignField": "_id",
-                    "as": "author"
+        // Los autores no están en el motor: siempre el repo
+        if self.search.enabled() && kind != SearchHitKind::Author {
+            // el motor recibe solo las palabras; frases y excluidos los filtra el repo
+            let words = query_parser::engine_query(query);
+            let hits = match kind {
+                SearchHitKind::Review => self.search.search_reviews(&words, Self::MAX_SEARCH_HITS).await,
+                _ => self.search.search_books(&words, Self::MAX_SEARCH_HITS).await,
+            };
+            match hits {
+                Ok(hits) => {
+                    let ranked = hits
+                        .iter()
+                        .filter_map(|h| Some((mongodb::bson::oid::ObjectId::parse_str(&h.id).ok()?, h.score as f64)))
+                        .collect();
+                    let base = SearchBase::Ranked { hits: ranked, query: query.to_string() };
+                    return self.search_run(kind, &base, query, mode, facets, page, per_page).await;
                 }
-            },
-            doc! { "$unwind": "$author" },
-            doc! {
-                "$project": {
-                    "book_id": "$_id",
-                    "title": 1,
-                    "author_name": "$author.name",
-                    "summary": 1,
-                    "publication_date": 1
-                }
-            },
-            doc! { "$sort": { "title": 1 } },
-            doc! { "$skip": skip },
-            doc! { "$limit": per_page }
-        ];
-
-        let cursor = books_collection.aggregate(pipeline).await?;
-        let documents: Vec<mongodb::bson::Document> = cursor.try_collect().await?;
-        
-        let mut results = Vec::new();
-        for doc in documents {
-            if let Ok(result) = mongodb::bson::from_document::<SearchResult>(doc) {
-                results.push(result);
+                // Motor caído: degradamos a la búsqueda del repo
+                Err(e) => eprintln!("[search] {kind:?} search failed, falling back to the database: {e}"),
             }
         }
 
-        Ok(PaginatedSearchResults {
-            results,
+        let text = SearchBase::Text { query: query.to_string(), language: self.search_language.clone() };
+        let results = self.search_run(kind, &text, query, mode, facets, page, per_page).await?;
+        if mode != SearchMode::Auto || results.total_results > 0 {
+            return Ok(results);
+        }
+
+        let results = self.search_run(kind, &regex(false), query, mode, facets, page, per_page).await?;
+        if results.total_results > 0 || !query_parser::parse(query).terms.iter().any(|t| query_parser::fuzzy_pattern(t).is_some()) {
+            return Ok(results);
+        }
+
+        // Último recurso: tolerar una edición por término (typos); solo si algún
+        // término tiene patrón, si no sería la misma búsqueda marcada como fuzzy
+        let mut results = self.search_run(kind, &regex(true), query, mode, facets, page, per_page).await?;
+        results.fuzzy = true;
+        Ok(results)
+    }
+
+    fn search_page(kind: SearchHitKind, found: SearchPage, query: &str, mode: SearchMode, page: i64, per_page: i64) -> PaginatedSearchResults {
+        let total_pages = (found.total as f64 / per_page as f64).ceil() as i64;
+        PaginatedSearchResults {
+            kind,
+            results: Self::highlighted(found.items, query),
             current_page: page,
             total_pages,
-            total_results: total_results as i64,
+            total_results: found.total,
             has_next: page < total_pages,
             has_prev: page > 1,
             query: query.to_string(),
-        })
+            mode: mode.as_str().to_string(),
+            facets: found.facets,
+            fuzzy: false,
+        }
     }
 
-    // Get all authors for dropdown selection
-    pub async fn get_all_authors(&self) -> mongodb::error::Result<Vec<crate::models::Author>> {
-        let collection = self.db.collection::<crate::models::Author>("authors");
-        let cursor = collection.find(doc! {}).await?;
-        let authors: Vec<crate::models::Author> = cursor.try_collect().await?;
-        Ok(authors)
+    // Las facetas solo filtran los libros
+    #[allow(clippy::too_many_arguments)]
+    async fn search_run(&self, kind: SearchHitKind, base: &SearchBase, query: &str, mode: SearchMode, facets: &FacetSelection, page: i64, per_page: i64) -> anyhow::Result<PaginatedSearchResults> {
+        let skip = (page.max(1) - 1).saturating_mul(per_page);
+        let found = match kind {
+            SearchHitKind::Book => self.books.search(base, facets, skip, per_page).await?,
+            SearchHitKind::Author => self.authors.search(base, skip, per_page).await?,
+            SearchHitKind::Review => self.reviews.search(base, skip, per_page).await?,
+        };
+        Ok(Self::search_page(kind, found, query, mode, page, per_page))
     }
 
-    // Get all books with author names for dropdown selection
-    pub async fn get_all_books_with_authors(&self) -> mongodb::error::Result<Vec<BookWithAuthor>> {
-        let pipeline = vec![
-            doc! {
-                "$lookup": {
-                    "from": "authors",
-                    "localField": "author_id",
-                    "foreignField": "_id",
-                    "as": "author"
-                }
-            },
-            doc! {
-                "$unwind": "$author"
-            },
-            doc! {
-                "$project": {
-                    "_id": 1,
-                    "title": 1,
-                    "author_name": "$author.name"
+    // Coincidencias resaltadas en título / resumen; las reseñas, recortadas
+    // alrededor de la primera
+    fn highlighted(items: Vec<SearchItem>, query: &str) -> Vec<SearchItem> {
+        items
+            .into_iter()
+            .map(|item| match item {
+                SearchItem::Book(mut b) => {
+                    b.highlights = highlight::book_highlights(&b.title, b.summary.as_deref(), query);
+                    SearchItem::Book(b)
                 }
-            },
-            doc! {
-                "$sort": {
-                    "title": 1
+                SearchItem::Review(mut r) => {
+                    r.snippet = query_parser::snippet(&r.snippet, query, SNIPPET_CHARS);
+                    SearchItem::Review(r)
                 }
-            }
-        ];
-
-        let collection = self.db.collection::<mongodb::bson::Document>("books");
-        let cursor = collection.aggregate(pipeline).await?;
-        let docs: Vec<mongodb::bson::Document> = cursor.try_collect().await?;
-        
-        let books: Vec<BookWithAuthor> = docs.iter()
-            .filter_map(|doc| {
-                Some(BookWithAuthor {
-                    id: doc.get_object_id("_id").ok()?.to_string(),
-                    title: doc.get_str("title").ok()?.to_string(),
-                    author_name: doc.get_str("author_name").ok()?.to_string(),
-                })
+                author => author,
             })
-            .collect();
+            .collect()
+    }
 
-        Ok(books)
+    // --- Autocompletado (títulos y autores por prefijo) ---
+    pub async fn suggest_cached(&self, prefix: &str, limit: i64) -> anyhow::Result<Suggestions> {
+        let key = Self::key_suggest(prefix, limit);
+
+        if let Some(cached) = self.cache_get_value::<Suggestions>(&key).await {
+            return Ok(cached);
+        }
+
+        let started = std::time::Instant::now();
+        let data = self.suggest(prefix, limit).await?;
+        metrics().load(&key, started.elapsed());
+        let tags = [Self::TAG_SEARCH, Self::TAG_AUTHORS, Self::TAG_BOOKS].map(String::from);
+        self.cache_set_value(&key, &data, Some(Self::TTL_SUGGEST), &tags).await;
+        Ok(data)
+    }
+
+    // Hasta `limit` títulos y `limit` nombres de autor que empiezan por `prefix`
+    pub async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Suggestions> {
+        let prefix = prefix.trim();
+        if prefix.chars().count() < Self::SUGGEST_MIN_CHARS {
+            return Ok(Suggestions { query: prefix.to_string(), suggestions: vec![] });
+        }
+
+        let (books, authors) = tokio::join!(
+            self.books.suggest(prefix, limit),
+            self.authors.suggest(prefix, limit),
+        );
+        let mut suggestions = books?;
+        suggestions.extend(authors?);
+
+        Ok(Suggestions { query: prefix.to_string(), suggestions })
+    }
+
+    // Get all authors for dropdown selection
+    pub async fn get_all_authors(&self) -> anyhow::Result<Vec<Author>> {
+        self.authors.list(&AuthorFilter::default()).await
+    }
+
+    // Get all books with author names for dropdown selection
+    pub async fn get_all_books_with_authors(&self) -> anyhow::Result<Vec<BookWithAuthor>> {
+        self.books.list_with_authors().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use futures_util::future::BoxFuture;
+
+    use super::*;
+
+    async fn state() -> AppState {
+        let mut cfg = AppConfig::from_env();
+        cfg.mongo_uri = "memory://".into();
+        cfg.cache_url = Some("memory://".into());
+        cfg.cache_warmup = false;
+        cfg.search_url = None;
+        init_state(cfg).await
     }
-}
\ No newline at end of file
+
+    // Cuenta las cargas; cada una tarda 50 ms y devuelve su número
+    fn loader(loads: &Arc<AtomicUsize>) -> impl FnOnce(AppState) -> BoxFuture<'static, anyhow::Result<usize>> + Send + 'static {
+        let loads = loads.clone();
+        move |_| {
+            Box::pin(async move {
+                let n = loads.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(n)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_load_once() {
+        let state = state().await;
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let calls = (0..20).map(|_| state.cached_swr("test:coalesce", vec![], Duration::from_secs(60), Duration::from_secs(60), loader(&loads)));
+        let values = futures_util::future::join_all(calls).await;
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(values.into_iter().all(|v| v.unwrap() == 1));
+    }
+
+    #[tokio::test]
+    async fn stale_values_are_served_while_one_task_refreshes() {
+        let state = state().await;
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (ttl, stale) = (Duration::from_millis(20), Duration::from_secs(60));
+
+        assert_eq!(state.cached_swr("test:swr", vec![], ttl, stale, loader(&loads)).await.unwrap(), 1);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        // vencido pero dentro de `stale`: todos reciben el valor viejo al momento
+        let calls = (0..10).map(|_| state.cached_swr("test:swr", vec![], ttl, stale, loader(&loads)));
+        let values = tokio::time::timeout(Duration::from_millis(40), futures_util::future::join_all(calls)).await.unwrap();
+        assert!(values.into_iter().all(|v| v.unwrap() == 1));
+
+        // y una sola tarea de fondo lo recalcula
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        let fresh = state.cached_swr("test:swr", vec![], Duration::from_secs(60), stale, loader(&loads)).await.unwrap();
+        assert_eq!(fresh, 2);
+    }
+}
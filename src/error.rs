@@ -0,0 +1,152 @@
+// Error de la aplicación para los handlers de src/routes. El mismo error se
+// responde como página (templates/error) en la UI y como problem document
+// (RFC 7807, application/problem+json) bajo /api.
+use std::fmt;
+
+use mongodb::bson::oid::ObjectId;
+use mongodb::error::{ErrorKind, WriteFailure};
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::{json, Json, Value};
+use rocket::Request;
+use rocket_dyn_templates::Template;
+
+// Código de Mongo para una clave única repetida
+const DUPLICATE_KEY: i32 = 11000;
+
+#[derive(Debug)]
+pub enum AppError {
+    // Lo que no existe: "author", "book"...
+    NotFound(String),
+    // Datos de entrada inválidos (422)
+    Validation(String),
+    // Choca con algo que ya existe, p.ej. la venta de (book_id, year)
+    Conflict(String),
+    // Mongo (o el backend que sea) falló: se loguea y no se muestra
+    Unavailable(anyhow::Error),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn not_found(what: &str) -> Self {
+        AppError::NotFound(what.to_string())
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict(message.into())
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            AppError::NotFound(_) => Status::NotFound,
+            AppError::Validation(_) => Status::UnprocessableEntity,
+            AppError::Conflict(_) => Status::Conflict,
+            AppError::Unavailable(_) => Status::ServiceUnavailable,
+        }
+    }
+
+    // Lo que ve el cliente (el detalle de Unavailable solo va al log)
+    pub fn detail(&self) -> String {
+        match self {
+            AppError::NotFound(what) => format!("{what} not found"),
+            AppError::Validation(m) | AppError::Conflict(m) => m.clone(),
+            AppError::Unavailable(_) => "the database is not available right now, try again later".into(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Unavailable(e) => write!(f, "backend error: {e:#}"),
+            _ => f.write_str(&self.detail()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// Los repos devuelven anyhow: si dentro viene un AppError (p.ej. el conflicto
+// del backend en memoria) se conserva; una clave duplicada de Mongo es un
+// conflicto; lo demás, backend no disponible.
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<AppError>() {
+            Ok(app) => return app,
+            Err(e) => e,
+        };
+        if let Some(mongo) = e.downcast_ref::<mongodb::error::Error>() {
+            if is_duplicate_key(mongo) {
+                return AppError::conflict("a record with the same key already exists");
+            }
+        }
+        AppError::Unavailable(e)
+    }
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    match e.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(we)) => we.code == DUPLICATE_KEY,
+        ErrorKind::Command(ce) => ce.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+// Cuerpo RFC 7807; también lo usan los catchers de la API
+pub fn problem(status: Status, detail: &str) -> Value {
+    json!({
+        "type": "about:blank",
+        "title": status.reason().unwrap_or("Error"),
+        "status": status.code,
+        "detail": detail,
+    })
+}
+
+pub fn problem_content_type() -> ContentType {
+    ContentType::new("application", "problem+json")
+}
+
+impl<'r> Responder<'r, 'static> for AppError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if let AppError::Unavailable(e) = &self {
+            eprintln!("[error] {} {}: {e:#}", req.method(), req.uri());
+        }
+
+        let path = req.uri().path();
+        if path.starts_with("/api") {
+            return Response::build_from(Json(problem(status, &self.detail())).respond_to(req)?)
+                .status(status)
+                .header(problem_content_type())
+                .ok();
+        }
+
+        // la página ofrece volver al listado de la sección (/authors, /books...)
+        let section = path.segments().next().unwrap_or_default();
+        let back = if section.is_empty() { "/".to_string() } else { format!("/{section}") };
+        let ctx = json!({
+            "status": status.code,
+            "title": status.reason().unwrap_or("Error"),
+            "detail": self.detail(),
+            "back": back,
+        });
+        Response::build_from(Template::render("error", ctx).respond_to(req)?)
+            .status(status)
+            .ok()
+    }
+}
+
+// Un id inválido en la ruta es, para el cliente, un recurso inexistente.
+pub fn path_oid(id: &str, what: &str) -> AppResult<ObjectId> {
+    ObjectId::parse_str(id).map_err(|_| AppError::not_found(what))
+}
+
+// Un id inválido en un formulario, cuerpo o query es un error de validación.
+pub fn field_oid(value: &str, field: &str) -> AppResult<ObjectId> {
+    ObjectId::parse_str(value).map_err(|_| AppError::validation(format!("{field} is not a valid id")))
+}
@@ -0,0 +1,272 @@
+#![allow(clippy::needless_return)]
+#[macro_use] extern crate rocket;
+
+use rocket::{Rocket, Build, State};
+use rocket::http::Method;
+use rocket_dyn_templates::Template;
+use rocket_cors::{CorsOptions, AllowedOrigins, AllowedHeaders};
+use serde_json::json;
+use rocket::request::FlashMessage;
+
+// Declaramos módulos (públicos: main.rs y los tests de tests/ usan la app
+// desde fuera del crate)
+pub mod config;
+pub mod cache;
+pub mod search;
+pub mod db;
+pub mod repo;
+pub mod error;
+pub mod models;
+pub mod static_files;
+pub mod upload;
+pub mod routes {
+    pub mod authors;
+    pub mod books;
+    pub mod reviews;
+    pub mod sales;
+    pub mod tables;
+    pub mod search;
+    pub mod admin;
+    pub mod forms;
+    pub mod api;
+}
+
+// ------- Rutas base -------
+#[get("/")]
+async fn home(state: &State<db::AppState>) -> Template {   
+    let authors_summary = match state.get_authors_summary_cached().await {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            eprintln!("Error getting authors summary: {}", e);
+            vec![]
+        }
+    };
+
+    let top_rated_books = match state.get_top_rated_books_cached().await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("Error getting top rated books: {}", e);
+            vec![]
+        }
+    };
+
+    let top_selling_books = match state.get_top_selling_books_cached().await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("Error getting top selling books: {}", e);
+            vec![]
+        }
+    };
+
+    let context = json!({
+        "authors": authors_summary,
+        "top_rated_books": top_rated_books,
+        "top_selling_books": top_selling_books,
+        "search_results": null,
+        "show_search_results": false
+    });
+    
+    Template::render("home", &context)
+}
+
+// Cada grupo (libros / autores / reseñas) pagina por separado.
+// El resto del query string son las facetas de libros (year_from, author, ...).
+#[get("/search?<q>&<page>&<authors_page>&<reviews_page>&<mode>&<facets..>")]
+async fn search_route(
+    q: String,
+    page: Option<i64>,
+    authors_page: Option<i64>,
+    reviews_page: Option<i64>,
+    mode: Option<String>,
+    facets: search::facets::FacetSelection,
+    state: &State<db::AppState>,
+) -> Template {
+    let pages = db::SearchPages {
+        books: page.unwrap_or(1).max(1),
+        authors: authors_page.unwrap_or(1).max(1),
+        reviews: reviews_page.unwrap_or(1).max(1),
+    };
+    let mode = search::query::SearchMode::parse(mode.as_deref());
+    let facets = facets.normalized();
+
+    let search_results = match state.search_all_cached(&q, mode, &facets, pages).await {
+        Ok(results) => Some(results),
+        Err(e) => {
+            eprintln!("Error searching books: {}", e);
+            None
+        }
+    };
+
+    let authors_summary = match state.get_authors_summary_cached().await {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            eprintln!("Error getting authors summary: {}", e);
+            vec![]
+        }
+    };
+
+    let top_rated_books = match state.get_top_rated_books_cached().await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("Error getting top rated books: {}", e);
+            vec![]
+        }
+    };
+
+    let top_selling_books = match state.get_top_selling_books_cached().await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("Error getting top selling books: {}", e);
+            vec![]
+        }
+    };
+
+    let context = json!({
+        "authors": authors_summary,
+        "top_rated_books": top_rated_books,
+        "top_selling_books": top_selling_books,
+        "search_results": search_results,
+        "show_search_results": true,
+        "facets_selected": facets,
+        "facet_qs": facets.query_string(None),
+        "facet_links": facets.links()
+    });
+    
+    Template::render("home", &context)
+}
+
+/// GET /books/avg/<id>
+#[get("/books/avg/<id>")]
+async fn book_avg(id: &str, state: &State<db::AppState>) -> String {
+    use mongodb::bson::oid::ObjectId;
+
+    let oid = match ObjectId::parse_str(id) {
+        Ok(o) => o,
+        Err(_) => return "invalid id".into(),
+    };
+
+    match state.get_book_average_score_cached(&oid).await {
+        Ok(avg) => format!("{:.4}", avg),
+        Err(e) => {
+            eprintln!("[avg] error for {id}: {e}");
+            "error".into()
+        }
+    }
+}
+
+#[get("/health")]
+fn health() -> &'static str {
+    "ok"
+}
+
+// GET /metrics
+// Métricas de la cache en formato de texto de Prometheus
+#[get("/metrics")]
+fn metrics() -> (rocket::http::ContentType, String) {
+    let content_type = rocket::http::ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (content_type, cache::metrics::metrics().render_prometheus())
+}
+
+
+#[get("/upload")]
+async fn upload_page(flash: Option<FlashMessage<'_>>, state: &State<db::AppState>) -> Template {
+    let serve_static = static_files::should_serve_static();
+    let uploads_dir = upload::get_uploads_dir();
+    
+    // Fetch all authors and books for dropdowns
+    let authors = match state.get_all_authors().await {
+        Ok(authors) => authors.into_iter().map(|author| {
+            json!({
+                "id": author.id.map(|id| id.to_string()).unwrap_or_default(),
+                "name": author.name,
+                "country": author.country
+            })
+        }).collect::<Vec<_>>(),
+        Err(e) => {
+            eprintln!("Error fetching authors: {}", e);
+            vec![]
+        }
+    };
+    
+    let books = match state.get_all_books_with_authors().await {
+        Ok(books) => books,
+        Err(e) => {
+            eprintln!("Error fetching books: {}", e);
+            vec![]
+        }
+    };
+    
+    let mut context = json!({
+        "serve_static": serve_static,
+        "uploads_dir": uploads_dir,
+        "authors": authors,
+        "books": books
+    });
+    
+    if let Some(flash) = flash {
+        context["flash"] = json!({
+            "kind": flash.kind(),
+            "message": flash.message()
+        });
+    }
+    
+    Template::render("upload", &context)
+}
+
+fn cors() -> rocket_cors::Cors {
+    let allowed_origins = AllowedOrigins::all();
+
+    CorsOptions {
+        allowed_origins,
+        allowed_methods: vec![
+            Method::Get,
+            Method::Post,
+            Method::Put,
+            Method::Delete,
+            Method::Patch,
+            Method::Options,
+        ].into_iter().map(From::from).collect(),
+        allowed_headers: AllowedHeaders::some(&[
+            "Content-Type",
+            "Accept",
+            "Authorization",
+        ]),
+        allow_credentials: true,
+        ..Default::default()
+    }
+    .to_cors()
+    .expect("error building CORS")
+}
+
+// La app montada sobre un estado ya creado: los tests con rocket::local la
+// levantan con db::init_state y MONGO_URI=memory://
+pub fn app(state: db::AppState) -> Rocket<Build> {
+    let mut rocket_builder = rocket::build()
+        .manage(state)
+        .attach(Template::fairing())
+        .attach(cors())
+        .mount("/", routes![home, search_route, health, metrics, upload_page, book_avg])
+        .mount("/authors", routes::authors::routes())
+        .mount("/books", routes::books::routes())
+        .mount("/reviews", routes::reviews::routes())
+        .mount("/sales", routes::sales::routes())
+        .mount("/upload", upload::get_upload_routes())
+        .mount("/admin", routes::admin::routes())
+        // API JSON versionada
+        .mount("/api/v1/authors", routes::api::authors::routes())
+        .mount("/api/v1/books", routes::api::books::routes())
+        .mount("/api/v1/reviews", routes::api::reviews::routes())
+        .mount("/api/v1/sales", routes::api::sales::routes())
+        .mount("/api/search", routes::api::search::routes())
+        .register("/api", routes::api::catchers());
+
+    // Only serve static files if not behind reverse proxy
+    if static_files::should_serve_static() {
+        println!("Serving static files from application");
+        rocket_builder = rocket_builder.mount("/static", static_files::get_static_routes());
+    } else {
+        println!("Static files will be served by reverse proxy");
+    }
+
+    rocket_builder
+}
\ No newline at end of file
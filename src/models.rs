@@ -1,30 +1,70 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use mongodb::bson::{oid::ObjectId};
 
+// Fechas sin hora (nacimiento, publicación). En Mongo son Date a medianoche UTC
+// para poder usar $year, rangos e índices; en JSON/plantillas, "YYYY-MM-DD".
+pub mod bson_date {
+    use chrono::{NaiveDate, NaiveTime};
+    use mongodb::bson::{doc, Bson, DateTime, Document};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn to_bson(date: NaiveDate) -> DateTime {
+        DateTime::from_millis(date.and_time(NaiveTime::MIN).and_utc().timestamp_millis())
+    }
+
+    pub fn from_bson(date: DateTime) -> Option<NaiveDate> {
+        chrono::DateTime::from_timestamp_millis(date.timestamp_millis()).map(|d| d.date_naive())
+    }
+
+    pub fn serialize<S: Serializer>(date: &Option<NaiveDate>, s: S) -> Result<S::Ok, S::Error> {
+        date.map(to_bson).serialize(s)
+    }
+
+    // También lee el string "YYYY-MM-DD" de los documentos aún sin migrar. Un
+    // string que no es fecha se lee como None: los updates lo apartan antes a
+    // `<campo>_raw` (MongoRepo::keep_raw_date) para no pisarlo con null.
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NaiveDate>, D::Error> {
+        Ok(match Option::<Bson>::deserialize(d)? {
+            Some(Bson::DateTime(date)) => from_bson(date),
+            Some(Bson::String(s)) => NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok(),
+            _ => None,
+        })
+    }
+
+    // Expresión de agregación que solo se evalúa si `field` es Date (null si no):
+    // $year / $dateToString fallan con los strings que la migración no convirtió
+    pub fn if_date(field: &str, expr: Document) -> Document {
+        doc! { "$cond": [{ "$eq": [{ "$type": format!("${field}") }, "date"] }, expr, null] }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Author {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub name: String,
-    pub date_of_birth: Option<String>,
+    #[serde(default, with = "bson_date")]
+    pub date_of_birth: Option<NaiveDate>,
     pub country: Option<String>,
     pub description: Option<String>,
     pub image_path: Option<String>,  // Path to author's image
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Book {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub author_id: ObjectId,            // relación 1–N (Book -> Author)
     pub title: String,
     pub summary: Option<String>,
-    pub publication_date: Option<String>,
+    #[serde(default, with = "bson_date")]
+    pub publication_date: Option<NaiveDate>,
     pub total_sales: Option<i64>, 
     pub cover_image_path: Option<String>,  // Path to book's cover image
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Review {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -34,7 +74,7 @@ pub struct Review {
     pub up_votes: i64,       // >= 0
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Sale {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -75,7 +115,7 @@ pub struct TopSellingBook {
     pub book_id: ObjectId,
     pub title: String,
     pub author_name: String,
-    pub publication_date: Option<String>,
+    pub publication_date: Option<NaiveDate>,   // el pipeline la devuelve con $dateToString
     pub book_total_sales: i64,
     pub author_total_sales: i64,
     pub was_top_5_in_publication_year: bool,
@@ -87,18 +127,124 @@ pub struct SearchResult {
     pub title: String,
     pub author_name: String,
     pub summary: Option<String>,
-    pub publication_date: Option<String>,
+    pub publication_date: Option<NaiveDate>,   // ídem
+    #[serde(default)]
+    pub score: Option<f64>,   // relevancia (textScore / BM25); None en modo prefijo
+    #[serde(default)]
+    pub highlights: Vec<Highlight>,   // fragmentos de título / resumen con las coincidencias
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HighlightPart {
+    pub text: String,
+    pub hit: bool,            // true => palabra que coincide con la búsqueda
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Highlight {
+    pub field: String,        // "title" | "summary"
+    pub parts: Vec<HighlightPart>,
+    pub truncated_start: bool,    // el fragmento no empieza al principio del texto
+    pub truncated_end: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorSearchResult {
+    pub author_id: ObjectId,
+    pub name: String,
+    pub country: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewSearchResult {
+    pub review_id: ObjectId,
+    pub book_id: ObjectId,
+    pub book_title: String,
+    pub snippet: String,      // fragmento del texto alrededor de la coincidencia
+    pub review_score: i32,    // 1..5 (la nota de la reseña, no la relevancia)
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+// Tipo de resultado de la búsqueda global
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchHitKind {
+    Book,
+    Author,
+    Review,
+}
+
+// Un resultado, etiquetado con su tipo: {"kind": "book", "title": ...}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SearchItem {
+    Book(SearchResult),
+    Author(AuthorSearchResult),
+    Review(ReviewSearchResult),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaginatedSearchResults {
-    pub results: Vec<SearchResult>,
+    pub kind: SearchHitKind,
+    pub results: Vec<SearchItem>,
     pub current_page: i64,
     pub total_pages: i64,
     pub total_results: i64,
     pub has_next: bool,
     pub has_prev: bool,
     pub query: String,
+    #[serde(default)]
+    pub mode: String,
+    #[serde(default)]
+    pub facets: Option<FacetCounts>,   // solo en el grupo de libros
+    #[serde(default)]
+    pub fuzzy: bool,          // sin coincidencias exactas: resultados aproximados (typos)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FacetBucket {
+    pub value: String,
+    pub label: String,
+    pub count: i64,
+    pub selected: bool,
+    pub query: String,        // "&author=..." para el enlace que la selecciona
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct FacetCounts {
+    pub years: Vec<FacetBucket>,      // por décadas
+    pub authors: Vec<FacetBucket>,
+    pub countries: Vec<FacetBucket>,
+    pub scores: Vec<FacetBucket>,     // nota media
+    pub sales: Vec<FacetBucket>,      // tramo de ventas
+}
+
+// /search: un grupo por tipo, cada uno con su propia paginación
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupedSearchResults {
+    pub query: String,
+    pub mode: String,
+    pub books: PaginatedSearchResults,
+    pub authors: PaginatedSearchResults,
+    pub reviews: PaginatedSearchResults,
+}
+
+// Autocompletado de la caja de búsqueda
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub kind: SearchHitKind,    // book | author
+    pub id: String,
+    pub text: String,           // título o nombre
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Suggestions {
+    pub query: String,
+    pub suggestions: Vec<Suggestion>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -107,3 +253,29 @@ pub struct BookWithAuthor {
     pub title: String,
     pub author_name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use mongodb::bson::{self, doc, oid::ObjectId};
+
+    use super::Book;
+
+    fn book_with_date(date: impl Into<bson::Bson>) -> Book {
+        bson::from_document(doc! { "author_id": ObjectId::new(), "title": "Rayuela", "publication_date": date.into(), "total_sales": null }).unwrap()
+    }
+
+    #[test]
+    fn dates_read_from_bson_dates_and_unmigrated_strings() {
+        let date = NaiveDate::from_ymd_opt(1963, 6, 28).unwrap();
+        assert_eq!(book_with_date(super::bson_date::to_bson(date)).publication_date, Some(date));
+        assert_eq!(book_with_date("1963-06-28").publication_date, Some(date));
+    }
+
+    #[test]
+    fn a_string_that_is_not_a_date_reads_as_none() {
+        // el documento sigue siendo legible; el valor se conserva en Mongo
+        assert_eq!(book_with_date("junio de 1963").publication_date, None);
+        assert_eq!(book_with_date("2023-02-30").publication_date, None);
+    }
+}
@@ -0,0 +1,677 @@
+// Backend en memoria (MONGO_URI=memory://): las cuatro colecciones bajo un
+// mismo lock, así las cascadas son atómicas sin más. No persiste nada; sirve
+// para desarrollo y para levantar la app entera en tests.
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate};
+use mongodb::bson::oid::ObjectId;
+use regex::{Regex, RegexBuilder};
+
+use super::{AuthorFilter, AuthorRepo, BookFilter, BookRepo, Cascade, ReviewFilter, ReviewRepo, SaleFilter, SaleRepo, SearchBase, SearchPage};
+use crate::error::AppError;
+use crate::models::{
+    Author, AuthorSearchResult, AuthorSummary, Book, BookWithAuthor, Review, ReviewSearchResult, ReviewWithScore, Sale, SearchHitKind, SearchItem,
+    SearchResult, Suggestion, TopRatedBook, TopSellingBook,
+};
+use crate::search::facets::{self, BookFacets, FacetSelection};
+use crate::search::memory::tokenize;
+use crate::search::query::{self, fold, RegexClauses};
+
+// Mismos topes que las agregaciones de Mongo
+const TOP_RATED: usize = 10;
+const TOP_SELLING: usize = 50;
+const TOP_PER_YEAR: usize = 5;
+
+// Por ObjectId: el mismo orden de inserción que devuelve Mongo sin sort
+#[derive(Default)]
+struct Tables {
+    authors: BTreeMap<ObjectId, Author>,
+    books: BTreeMap<ObjectId, Book>,
+    reviews: BTreeMap<ObjectId, Review>,
+    sales: BTreeMap<ObjectId, Sale>,
+}
+
+impl Tables {
+    fn book_units(&self, book_id: &ObjectId) -> i64 {
+        self.sales.values().filter(|s| s.book_id == *book_id).map(|s| s.units).sum()
+    }
+
+    // books.total_sales a partir de las ventas actuales
+    fn store_book_total(&mut self, book_id: &ObjectId) {
+        let total = self.book_units(book_id);
+        if let Some(b) = self.books.get_mut(book_id) {
+            b.total_sales = Some(total);
+        }
+    }
+
+    fn book_reviews(&self, book_id: &ObjectId) -> Vec<&Review> {
+        self.reviews.values().filter(|r| r.book_id == *book_id).collect()
+    }
+
+    // Con el índice único de Mongo, (book_id, year) no se repite
+    fn sale_key_taken(&self, book_id: &ObjectId, year: i32, except: Option<&ObjectId>) -> Option<ObjectId> {
+        self.sales
+            .iter()
+            .find(|(id, s)| s.book_id == *book_id && s.year == year && Some(*id) != except)
+            .map(|(id, _)| *id)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryRepo {
+    tables: RwLock<Tables>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> anyhow::Result<RwLockReadGuard<'_, Tables>> {
+        self.tables.read().map_err(poisoned)
+    }
+
+    fn write(&self) -> anyhow::Result<RwLockWriteGuard<'_, Tables>> {
+        self.tables.write().map_err(poisoned)
+    }
+}
+
+// Un lock envenenado (panic a mitad de una escritura) se reporta como error
+fn poisoned<T>(_: T) -> anyhow::Error {
+    anyhow!("in-memory store lock poisoned")
+}
+
+// Como $regex con la opción "i"
+fn compile(p: &str) -> anyhow::Result<Regex> {
+    RegexBuilder::new(p)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| AppError::validation(format!("invalid pattern: {e}")).into())
+}
+
+// Patrón de los filtros de texto
+fn pattern(p: &Option<String>) -> anyhow::Result<Option<Regex>> {
+    p.as_deref().map(compile).transpose()
+}
+
+// Lo mismo que el índice único de Mongo, pero ya como conflicto
+fn duplicate_sale(sale: &Sale) -> AppError {
+    AppError::conflict(format!("a sale for book {} in {} already exists", sale.book_id, sale.year))
+}
+
+fn matches(re: &Option<Regex>, text: &str) -> bool {
+    re.as_ref().is_none_or(|re| re.is_match(text))
+}
+
+// Como $round: 2 decimales
+fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+fn average(scores: impl Iterator<Item = i32>) -> Option<f64> {
+    let (sum, n) = scores.fold((0i64, 0i64), |(sum, n), s| (sum + s as i64, n + 1));
+    (n > 0).then(|| sum as f64 / n as f64)
+}
+
+fn publication_year(date: &Option<NaiveDate>) -> Option<i32> {
+    date.map(|d| d.year())
+}
+
+// Prefijo sin distinguir mayúsculas ni acentos (la collation del índice en Mongo)
+fn suggest<'a>(kind: SearchHitKind, items: impl Iterator<Item = (&'a ObjectId, &'a str)>, prefix: &str, limit: i64) -> Vec<Suggestion> {
+    let prefix = fold(prefix);
+    let mut found: Vec<(String, &ObjectId, &str)> = items
+        .map(|(id, text)| (fold(text), id, text))
+        .filter(|(folded, _, _)| folded.starts_with(&prefix))
+        .collect();
+    found.sort();
+    found
+        .into_iter()
+        .take(limit.max(0) as usize)
+        .map(|(_, id, text)| Suggestion { kind, id: id.to_hex(), text: text.to_string() })
+        .collect()
+}
+
+/* ===== Búsqueda global ===== */
+
+// Un documento encontrado: su puesto en el ranking del motor y su relevancia
+#[derive(Debug, Clone, Copy)]
+struct Found {
+    rank: usize,
+    relevance: Option<f64>,
+}
+
+// SearchBase listo para recorrer los documentos. $text sin stemming: palabras
+// enteras sin acentos, y la relevancia suma las apariciones de los términos
+// por el peso del campo. $regex con los mismos patrones que en Mongo.
+enum Matcher {
+    Text { terms: Vec<String>, phrases: Vec<String>, excluded: Vec<String> },
+    Regex { required: Vec<Regex>, excluded: Vec<Regex> },
+    Ranked { hits: HashMap<ObjectId, Found>, regex: Box<Matcher> },
+}
+
+impl Matcher {
+    fn new(base: &SearchBase) -> anyhow::Result<Self> {
+        Ok(match base {
+            SearchBase::Text { query, .. } => {
+                let parsed = query::parse(query);
+                let words = |v: &[String]| v.iter().flat_map(|t| tokenize(t)).collect();
+                Self::Text {
+                    terms: words(&parsed.terms),
+                    phrases: parsed.phrases.iter().map(|p| fold(p)).collect(),
+                    excluded: words(&parsed.excluded),
+                }
+            }
+            SearchBase::Regex { query, fuzzy } => Self::regex(&query::regex_clauses(query, *fuzzy))?,
+            // frases y excluidos, que el motor no aplica
+            SearchBase::Ranked { hits, query } => Self::Ranked {
+                hits: hits.iter().enumerate().map(|(rank, (id, score))| (*id, Found { rank, relevance: Some(*score) })).collect(),
+                regex: Box::new(Self::regex(&query::ranked_clauses(query))?),
+            },
+        })
+    }
+
+    fn regex(clauses: &RegexClauses) -> anyhow::Result<Self> {
+        let compiled = |v: &[String]| v.iter().map(|p| compile(p)).collect::<anyhow::Result<Vec<_>>>();
+        Ok(Self::Regex { required: compiled(&clauses.required)?, excluded: compiled(&clauses.excluded)? })
+    }
+
+    // `fields`: los campos de texto con su peso en el índice (db::ensure_indexes).
+    // None si el documento no coincide.
+    fn check(&self, id: &ObjectId, fields: &[(Option<&str>, u32)]) -> Option<Found> {
+        let texts = fields.iter().filter_map(|(text, weight)| Some(((*text)?, *weight)));
+        match self {
+            // con frases, solo cuentan las frases; si no, basta un término
+            Self::Text { terms, phrases, excluded } => {
+                let fields: Vec<(String, Vec<String>, u32)> = texts.map(|(t, w)| (fold(t), tokenize(t), w)).collect();
+                if fields.iter().any(|(_, words, _)| words.iter().any(|w| excluded.contains(w))) {
+                    return None;
+                }
+                let relevance: f64 = fields
+                    .iter()
+                    .map(|(_, words, weight)| (words.iter().filter(|w| terms.contains(w)).count() as u32 * weight) as f64)
+                    .sum();
+                let matched = match phrases.is_empty() {
+                    true => relevance > 0.0,
+                    false => phrases.iter().all(|p| fields.iter().any(|(folded, _, _)| folded.contains(p.as_str()))),
+                };
+                matched.then_some(Found { rank: 0, relevance: Some(relevance) })
+            }
+            // cada patrón en algún campo (el $or de campos de Mongo)
+            Self::Regex { required, excluded } => {
+                let texts: Vec<&str> = texts.map(|(t, _)| t).collect();
+                let found = |re: &Regex| texts.iter().any(|t| re.is_match(t));
+                (required.iter().all(found) && !excluded.iter().any(found)).then_some(Found { rank: 0, relevance: None })
+            }
+            Self::Ranked { hits, regex } => regex.check(id, fields).and(hits.get(id).copied()),
+        }
+    }
+}
+
+// Ordena como la agregación de Mongo (ranking del motor; si no, relevancia y
+// luego el orden propio de la colección) y corta la página. Devuelve el total.
+fn page_of<T>(base: &SearchBase, mut found: Vec<(Found, T)>, order: impl Fn(&T, &T) -> Ordering, skip: i64, limit: i64) -> (Vec<(Found, T)>, i64) {
+    let total = found.len() as i64;
+    found.sort_by(|(a, x), (b, y)| match base {
+        SearchBase::Ranked { .. } => a.rank.cmp(&b.rank),
+        _ => b.relevance.partial_cmp(&a.relevance).unwrap_or(Ordering::Equal).then_with(|| order(x, y)),
+    });
+    let page = found.into_iter().skip(skip.max(0) as usize).take(limit.max(0) as usize).collect();
+    (page, total)
+}
+
+#[async_trait]
+impl AuthorRepo for MemoryRepo {
+    async fn list(&self, filter: &AuthorFilter) -> anyhow::Result<Vec<Author>> {
+        let name = pattern(&filter.name)?;
+        Ok(self.read()?.authors.values().filter(|a| matches(&name, &a.name)).cloned().collect())
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Author>> {
+        Ok(self.read()?.authors.get(id).cloned())
+    }
+
+    async fn insert(&self, author: &Author) -> anyhow::Result<ObjectId> {
+        let id = author.id.unwrap_or_default();
+        let mut author = author.clone();
+        author.id = Some(id);
+        self.write()?.authors.insert(id, author);
+        Ok(id)
+    }
+
+    async fn update(&self, id: &ObjectId, author: &Author) -> anyhow::Result<Option<Author>> {
+        let mut tables = self.write()?;
+        let Some(a) = tables.authors.get_mut(id) else { return Ok(None) };
+        a.name = author.name.clone();
+        a.date_of_birth = author.date_of_birth;
+        a.country = author.country.clone();
+        a.description = author.description.clone();
+        Ok(Some(a.clone()))
+    }
+
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade> {
+        let mut tables = self.write()?;
+        let book_ids: Vec<ObjectId> = tables.books.iter().filter(|(_, b)| b.author_id == *id).map(|(id, _)| *id).collect();
+        let review_ids: Vec<ObjectId> = tables.reviews.iter().filter(|(_, r)| book_ids.contains(&r.book_id)).map(|(id, _)| *id).collect();
+
+        tables.reviews.retain(|_, r| !book_ids.contains(&r.book_id));
+        tables.sales.retain(|_, s| !book_ids.contains(&s.book_id));
+        tables.books.retain(|_, b| b.author_id != *id);
+        let deleted = tables.authors.remove(id).is_some();
+        Ok(Cascade { deleted, book_ids, review_ids })
+    }
+
+    async fn summary(&self) -> anyhow::Result<Vec<AuthorSummary>> {
+        let tables = self.read()?;
+        let mut summaries: Vec<AuthorSummary> = tables
+            .authors
+            .iter()
+            .map(|(author_id, a)| {
+                let books: Vec<&ObjectId> = tables.books.iter().filter(|(_, b)| b.author_id == *author_id).map(|(id, _)| id).collect();
+                let scores = books.iter().flat_map(|id| tables.book_reviews(id)).map(|r| r.score);
+                AuthorSummary {
+                    author_id: *author_id,
+                    name: a.name.clone(),
+                    published_books: books.len() as i64,
+                    average_score: round2(average(scores).unwrap_or(0.0)),
+                    total_sales: books.iter().map(|id| tables.book_units(id)).sum(),
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(summaries)
+    }
+
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>> {
+        let tables = self.read()?;
+        Ok(suggest(SearchHitKind::Author, tables.authors.iter().map(|(id, a)| (id, a.name.as_str())), prefix, limit))
+    }
+
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        let matcher = Matcher::new(base)?;
+        let tables = self.read()?;
+        let found = tables
+            .authors
+            .iter()
+            .filter_map(|(id, a)| {
+                let fields = [(Some(a.name.as_str()), 3), (a.country.as_deref(), 2), (a.description.as_deref(), 1)];
+                Some((matcher.check(id, &fields)?, (id, a)))
+            })
+            .collect();
+
+        let (page, total) = page_of(base, found, |(_, a), (_, b)| a.name.cmp(&b.name), skip, limit);
+        let items = page
+            .into_iter()
+            .map(|(hit, (id, a))| {
+                SearchItem::Author(AuthorSearchResult {
+                    author_id: *id,
+                    name: a.name.clone(),
+                    country: a.country.clone(),
+                    description: a.description.clone(),
+                    score: hit.relevance,
+                })
+            })
+            .collect();
+        Ok(SearchPage { items, total, facets: None })
+    }
+}
+
+#[async_trait]
+impl BookRepo for MemoryRepo {
+    async fn list(&self, filter: &BookFilter) -> anyhow::Result<Vec<Book>> {
+        let title = pattern(&filter.title)?;
+        Ok(self
+            .read()?
+            .books
+            .values()
+            .filter(|b| matches(&title, &b.title) && filter.author_id.is_none_or(|aid| b.author_id == aid))
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Book>> {
+        Ok(self.read()?.books.get(id).cloned())
+    }
+
+    async fn insert(&self, book: &Book) -> anyhow::Result<ObjectId> {
+        let id = book.id.unwrap_or_default();
+        let mut book = book.clone();
+        book.id = Some(id);
+        self.write()?.books.insert(id, book);
+        Ok(id)
+    }
+
+    async fn update(&self, id: &ObjectId, book: &Book) -> anyhow::Result<Option<Book>> {
+        let mut tables = self.write()?;
+        let Some(b) = tables.books.get_mut(id) else { return Ok(None) };
+        b.title = book.title.clone();
+        b.author_id = book.author_id;
+        b.summary = book.summary.clone();
+        b.publication_date = book.publication_date;
+        Ok(Some(b.clone()))
+    }
+
+    async fn set_total_sales(&self, id: &ObjectId, total: i64) -> anyhow::Result<()> {
+        if let Some(b) = self.write()?.books.get_mut(id) {
+            b.total_sales = Some(total);
+        }
+        Ok(())
+    }
+
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade> {
+        let mut tables = self.write()?;
+        let review_ids: Vec<ObjectId> = tables.reviews.iter().filter(|(_, r)| r.book_id == *id).map(|(id, _)| *id).collect();
+
+        tables.reviews.retain(|_, r| r.book_id != *id);
+        tables.sales.retain(|_, s| s.book_id != *id);
+        let deleted = tables.books.remove(id).is_some();
+        Ok(Cascade { deleted, book_ids: vec![*id], review_ids })
+    }
+
+    async fn list_with_authors(&self) -> anyhow::Result<Vec<BookWithAuthor>> {
+        let tables = self.read()?;
+        let mut books: Vec<BookWithAuthor> = tables
+            .books
+            .iter()
+            .filter_map(|(id, b)| {
+                Some(BookWithAuthor {
+                    id: id.to_string(),
+                    title: b.title.clone(),
+                    author_name: tables.authors.get(&b.author_id)?.name.clone(),
+                })
+            })
+            .collect();
+        books.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(books)
+    }
+
+    async fn top_rated(&self) -> anyhow::Result<Vec<TopRatedBook>> {
+        let tables = self.read()?;
+        let mut rated: Vec<(ObjectId, f64, Vec<&Review>)> = tables
+            .books
+            .keys()
+            .filter_map(|id| {
+                let reviews = tables.book_reviews(id);
+                let avg = average(reviews.iter().map(|r| r.score))?;
+                Some((*id, avg, reviews))
+            })
+            .collect();
+        rated.sort_by(|a, b| b.1.total_cmp(&a.1).then(b.2.len().cmp(&a.2.len())));
+        rated.truncate(TOP_RATED);
+
+        // la primera reseña con la nota más alta / más baja
+        let pick = |reviews: &[&Review], score: Option<i32>| {
+            reviews.iter().find(|r| Some(r.score) == score).map(|r| ReviewWithScore {
+                text: r.text.clone(),
+                score: r.score,
+                up_votes: r.up_votes,
+            })
+        };
+        Ok(rated
+            .into_iter()
+            .filter_map(|(id, avg, reviews)| {
+                let book = &tables.books[&id];
+                let author = tables.authors.get(&book.author_id)?;
+                Some(TopRatedBook {
+                    book_id: id,
+                    title: book.title.clone(),
+                    author_name: author.name.clone(),
+                    average_score: round2(avg),
+                    total_reviews: reviews.len() as i64,
+                    highest_rated_review: pick(&reviews, reviews.iter().map(|r| r.score).max()),
+                    lowest_rated_review: pick(&reviews, reviews.iter().map(|r| r.score).min()),
+                })
+            })
+            .collect())
+    }
+
+    async fn top_selling(&self) -> anyhow::Result<Vec<TopSellingBook>> {
+        let tables = self.read()?;
+        let units: HashMap<ObjectId, i64> = tables.books.keys().map(|id| (*id, tables.book_units(id))).collect();
+
+        // top 5 de ventas de cada año de publicación
+        let mut by_year: HashMap<i32, Vec<ObjectId>> = HashMap::new();
+        for (id, b) in &tables.books {
+            if let Some(year) = publication_year(&b.publication_date) {
+                by_year.entry(year).or_default().push(*id);
+            }
+        }
+        for ids in by_year.values_mut() {
+            ids.sort_by_key(|id| std::cmp::Reverse(units[id]));
+            ids.truncate(TOP_PER_YEAR);
+        }
+
+        let mut selling: Vec<(&ObjectId, &Book)> = tables.books.iter().filter(|(id, _)| units[*id] > 0).collect();
+        selling.sort_by_key(|(id, _)| std::cmp::Reverse(units[*id]));
+        selling.truncate(TOP_SELLING);
+
+        Ok(selling
+            .into_iter()
+            .filter_map(|(id, b)| {
+                let author = tables.authors.get(&b.author_id)?;
+                let author_total_sales = tables.books.iter().filter(|(_, ab)| ab.author_id == b.author_id).map(|(aid, _)| units[aid]).sum();
+                let was_top_5_in_publication_year = publication_year(&b.publication_date)
+                    .is_some_and(|year| by_year[&year].contains(id));
+                Some(TopSellingBook {
+                    book_id: *id,
+                    title: b.title.clone(),
+                    author_name: author.name.clone(),
+                    publication_date: b.publication_date,
+                    book_total_sales: units[id],
+                    author_total_sales,
+                    was_top_5_in_publication_year,
+                })
+            })
+            .collect())
+    }
+
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>> {
+        let tables = self.read()?;
+        Ok(suggest(SearchHitKind::Book, tables.books.iter().map(|(id, b)| (id, b.title.as_str())), prefix, limit))
+    }
+
+    // Como en Mongo, un libro sin su autor no sale (el $unwind del join)
+    async fn search(&self, base: &SearchBase, facets: &FacetSelection, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        let matcher = Matcher::new(base)?;
+        let tables = self.read()?;
+        let found: Vec<(Found, (&ObjectId, &Book, &Author, BookFacets))> = tables
+            .books
+            .iter()
+            .filter_map(|(id, b)| {
+                let hit = matcher.check(id, &[(Some(b.title.as_str()), 3), (b.summary.as_deref(), 1)])?;
+                let author = tables.authors.get(&b.author_id)?;
+                let enriched = BookFacets {
+                    year: publication_year(&b.publication_date),
+                    author_id: b.author_id,
+                    author_name: author.name.clone(),
+                    author_country: author.country.clone(),
+                    avg_score: average(tables.book_reviews(id).iter().map(|r| r.score)),
+                    total_sales: b.total_sales.unwrap_or(0),
+                };
+                Some((hit, (id, b, author, enriched)))
+            })
+            .collect();
+
+        let counts = facets::count_books(&found.iter().map(|(_, (.., f))| f).collect::<Vec<_>>(), facets);
+        let selected = found.into_iter().filter(|(_, (.., f))| facets.matches(f, None)).collect();
+        let (page, total) = page_of(base, selected, |(_, a, ..), (_, b, ..)| a.title.cmp(&b.title), skip, limit);
+        let items = page
+            .into_iter()
+            .map(|(hit, (id, b, author, _))| {
+                SearchItem::Book(SearchResult {
+                    book_id: *id,
+                    title: b.title.clone(),
+                    author_name: author.name.clone(),
+                    summary: b.summary.clone(),
+                    publication_date: b.publication_date,
+                    score: hit.relevance,
+                    highlights: vec![],
+                })
+            })
+            .collect();
+        Ok(SearchPage { items, total, facets: Some(counts) })
+    }
+}
+
+#[async_trait]
+impl ReviewRepo for MemoryRepo {
+    async fn list(&self, filter: &ReviewFilter) -> anyhow::Result<Vec<Review>> {
+        let text = pattern(&filter.text)?;
+        Ok(self
+            .read()?
+            .reviews
+            .values()
+            .filter(|r| matches(&text, &r.text) && filter.book_id.is_none_or(|bid| r.book_id == bid))
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Review>> {
+        Ok(self.read()?.reviews.get(id).cloned())
+    }
+
+    async fn insert(&self, review: &Review) -> anyhow::Result<ObjectId> {
+        let id = review.id.unwrap_or_default();
+        let mut review = review.clone();
+        review.id = Some(id);
+        self.write()?.reviews.insert(id, review);
+        Ok(id)
+    }
+
+    async fn update(&self, id: &ObjectId, review: &Review) -> anyhow::Result<Option<Review>> {
+        let mut tables = self.write()?;
+        let Some(r) = tables.reviews.get_mut(id) else { return Ok(None) };
+        let prev = r.clone();
+        r.book_id = review.book_id;
+        r.text = review.text.clone();
+        r.score = review.score;
+        r.up_votes = review.up_votes;
+        Ok(Some(prev))
+    }
+
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Review>> {
+        Ok(self.write()?.reviews.remove(id))
+    }
+
+    async fn average_score(&self, book_id: &ObjectId) -> anyhow::Result<f64> {
+        let tables = self.read()?;
+        Ok(average(tables.book_reviews(book_id).iter().map(|r| r.score)).unwrap_or(0.0))
+    }
+
+    // Las más votadas primero; una reseña sin su libro no sale
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        let matcher = Matcher::new(base)?;
+        let tables = self.read()?;
+        let found = tables
+            .reviews
+            .iter()
+            .filter_map(|(id, r)| {
+                let hit = matcher.check(id, &[(Some(r.text.as_str()), 1)])?;
+                Some((hit, (id, r, tables.books.get(&r.book_id)?)))
+            })
+            .collect();
+
+        let (page, total) = page_of(base, found, |(_, a, _), (_, b, _)| b.up_votes.cmp(&a.up_votes), skip, limit);
+        let items = page
+            .into_iter()
+            .map(|(hit, (id, r, book))| {
+                SearchItem::Review(ReviewSearchResult {
+                    review_id: *id,
+                    book_id: r.book_id,
+                    book_title: book.title.clone(),
+                    snippet: r.text.clone(),
+                    review_score: r.score,
+                    score: hit.relevance,
+                })
+            })
+            .collect();
+        Ok(SearchPage { items, total, facets: None })
+    }
+}
+
+#[async_trait]
+impl SaleRepo for MemoryRepo {
+    async fn list(&self, filter: &SaleFilter) -> anyhow::Result<Vec<Sale>> {
+        Ok(self
+            .read()?
+            .sales
+            .values()
+            .filter(|s| filter.book_id.is_none_or(|bid| s.book_id == bid) && filter.year.is_none_or(|y| s.year == y))
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>> {
+        Ok(self.read()?.sales.get(id).cloned())
+    }
+
+    async fn find_by_key(&self, book_id: &ObjectId, year: i32, except: Option<&ObjectId>) -> anyhow::Result<Option<Sale>> {
+        let tables = self.read()?;
+        Ok(tables.sale_key_taken(book_id, year, except).map(|id| tables.sales[&id].clone()))
+    }
+
+    async fn insert(&self, sale: &Sale) -> anyhow::Result<ObjectId> {
+        let mut tables = self.write()?;
+        if tables.sale_key_taken(&sale.book_id, sale.year, None).is_some() {
+            return Err(duplicate_sale(sale).into());
+        }
+        let id = sale.id.unwrap_or_default();
+        tables.sales.insert(id, Sale { id: Some(id), ..sale.clone() });
+        Ok(id)
+    }
+
+    async fn update(&self, id: &ObjectId, sale: &Sale) -> anyhow::Result<Option<Sale>> {
+        let mut tables = self.write()?;
+        if tables.sale_key_taken(&sale.book_id, sale.year, Some(id)).is_some() {
+            return Err(duplicate_sale(sale).into());
+        }
+        let Some(s) = tables.sales.get_mut(id) else { return Ok(None) };
+        let prev = s.clone();
+        s.book_id = sale.book_id;
+        s.year = sale.year;
+        s.units = sale.units;
+        Ok(Some(prev))
+    }
+
+    async fn upsert(&self, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()> {
+        let mut tables = self.write()?;
+        match tables.sale_key_taken(book_id, year, None) {
+            Some(id) => tables.sales.get_mut(&id).expect("sale just found").units = units,
+            None => {
+                let id = ObjectId::new();
+                tables.sales.insert(id, Sale { id: Some(id), book_id: *book_id, year, units });
+            }
+        }
+        tables.store_book_total(book_id);
+        Ok(())
+    }
+
+    async fn merge_into(&self, id: &ObjectId, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()> {
+        let mut tables = self.write()?;
+        match tables.sale_key_taken(book_id, year, Some(id)) {
+            Some(target) => tables.sales.get_mut(&target).expect("sale just found").units = units,
+            None => {
+                let target = ObjectId::new();
+                tables.sales.insert(target, Sale { id: Some(target), book_id: *book_id, year, units });
+            }
+        }
+        let prev = tables.sales.remove(id);
+        if let Some(prev) = prev {
+            tables.store_book_total(&prev.book_id);
+        }
+        tables.store_book_total(book_id);
+        Ok(())
+    }
+
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>> {
+        Ok(self.write()?.sales.remove(id))
+    }
+
+    async fn total_units(&self, book_id: &ObjectId) -> anyhow::Result<i64> {
+        Ok(self.read()?.book_units(book_id))
+    }
+}
@@ -0,0 +1,151 @@
+// Acceso a datos: un trait por colección con el CRUD y las agregaciones que
+// usan los handlers. MongoRepo es el backend real; MemoryRepo guarda todo en
+// el proceso (MONGO_URI=memory://) para levantar la app sin Mongo, p.ej. en
+// tests con rocket::local.
+use async_trait::async_trait;
+use mongodb::bson::oid::ObjectId;
+
+use crate::models::{Author, AuthorSummary, Book, BookWithAuthor, FacetCounts, Review, Sale, SearchItem, Suggestion, TopRatedBook, TopSellingBook};
+use crate::search::facets::FacetSelection;
+
+pub mod memory;
+pub mod mongo;
+
+// Filtros de los listados. Los de texto son un patrón regex que se aplica
+// sin distinguir mayúsculas (ver query::list_pattern).
+#[derive(Debug, Default, Clone)]
+pub struct AuthorFilter {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BookFilter {
+    pub title: Option<String>,
+    pub author_id: Option<ObjectId>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ReviewFilter {
+    pub text: Option<String>,
+    pub book_id: Option<ObjectId>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SaleFilter {
+    pub book_id: Option<ObjectId>,
+    pub year: Option<i32>,
+}
+
+// Resultado de un borrado en cascada: lo borrado, para sacarlo del índice de
+// búsqueda e invalidar la cache
+#[derive(Debug, Default)]
+pub struct Cascade {
+    pub deleted: bool,          // false => no existía
+    pub book_ids: Vec<ObjectId>,
+    pub review_ids: Vec<ObjectId>,
+}
+
+// Cómo encuentra sus documentos una búsqueda global (la estrategia la elige
+// AppState::search)
+#[derive(Debug, Clone)]
+pub enum SearchBase {
+    // índice de texto: palabras (con stemming en Mongo), ordenado por relevancia
+    Text { query: String, language: String },
+    // coincidencias parciales (ver query::regex_clauses), por nombre/título
+    Regex { query: String, fuzzy: bool },
+    // hits del motor de búsqueda, en su orden; el repo filtra (frases y
+    // excluidos de `query`, ver query::ranked_clauses) e hidrata
+    Ranked { hits: Vec<(ObjectId, f64)>, query: String },
+}
+
+// Una página de resultados (sin resaltar), el total y, en libros, los conteos
+// por faceta
+#[derive(Debug, Default)]
+pub struct SearchPage {
+    pub items: Vec<SearchItem>,
+    pub total: i64,
+    pub facets: Option<FacetCounts>,
+}
+
+#[async_trait]
+pub trait AuthorRepo: Send + Sync {
+    async fn list(&self, filter: &AuthorFilter) -> anyhow::Result<Vec<Author>>;
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Author>>;
+    async fn insert(&self, author: &Author) -> anyhow::Result<ObjectId>;
+    // Reemplaza nombre, fecha, país y descripción; devuelve el autor ya cambiado
+    async fn update(&self, id: &ObjectId, author: &Author) -> anyhow::Result<Option<Author>>;
+    // El autor con sus libros y las reseñas y ventas de esos libros; o todo o nada
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade>;
+
+    // Libros publicados, nota media y ventas por autor, ordenado por nombre
+    async fn summary(&self) -> anyhow::Result<Vec<AuthorSummary>>;
+    // Nombres que empiezan por `prefix`, sin distinguir mayúsculas ni acentos
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>>;
+    // Búsqueda global por nombre, país y descripción
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage>;
+}
+
+#[async_trait]
+pub trait BookRepo: Send + Sync {
+    async fn list(&self, filter: &BookFilter) -> anyhow::Result<Vec<Book>>;
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Book>>;
+    async fn insert(&self, book: &Book) -> anyhow::Result<ObjectId>;
+    // Reemplaza título, autor, resumen y fecha (total_sales no: ver set_total_sales)
+    async fn update(&self, id: &ObjectId, book: &Book) -> anyhow::Result<Option<Book>>;
+    async fn set_total_sales(&self, id: &ObjectId, total: i64) -> anyhow::Result<()>;
+    // El libro con sus reseñas y ventas; o todo o nada
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade>;
+
+    // Para los <select>: libros con el nombre del autor, por título
+    async fn list_with_authors(&self) -> anyhow::Result<Vec<BookWithAuthor>>;
+    // Top 10 por nota media (con la mejor y la peor reseña)
+    async fn top_rated(&self) -> anyhow::Result<Vec<TopRatedBook>>;
+    // Top 50 por ventas (con las del autor y si fue top 5 de su año)
+    async fn top_selling(&self) -> anyhow::Result<Vec<TopSellingBook>>;
+    // Títulos que empiezan por `prefix`, sin distinguir mayúsculas ni acentos
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>>;
+    // Búsqueda global por título y resumen, filtrada y contada por facetas
+    async fn search(&self, base: &SearchBase, facets: &FacetSelection, skip: i64, limit: i64) -> anyhow::Result<SearchPage>;
+}
+
+#[async_trait]
+pub trait ReviewRepo: Send + Sync {
+    async fn list(&self, filter: &ReviewFilter) -> anyhow::Result<Vec<Review>>;
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Review>>;
+    async fn insert(&self, review: &Review) -> anyhow::Result<ObjectId>;
+    // Devuelve la reseña anterior (su libro también hay que invalidarlo)
+    async fn update(&self, id: &ObjectId, review: &Review) -> anyhow::Result<Option<Review>>;
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Review>>;
+
+    // 0.0 si el libro no tiene reseñas
+    async fn average_score(&self, book_id: &ObjectId) -> anyhow::Result<f64>;
+    // Búsqueda global por texto de la reseña
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage>;
+}
+
+// (book_id, year) es único
+#[async_trait]
+pub trait SaleRepo: Send + Sync {
+    async fn list(&self, filter: &SaleFilter) -> anyhow::Result<Vec<Sale>>;
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>>;
+    // La venta de ese libro y año, salvo `except`
+    async fn find_by_key(&self, book_id: &ObjectId, year: i32, except: Option<&ObjectId>) -> anyhow::Result<Option<Sale>>;
+    async fn insert(&self, sale: &Sale) -> anyhow::Result<ObjectId>;
+    // Devuelve la venta anterior
+    async fn update(&self, id: &ObjectId, sale: &Sale) -> anyhow::Result<Option<Sale>>;
+    // Crea la venta de (book_id, year) o le pone estas units si ya existe, y
+    // recalcula books.total_sales del libro en la misma transacción
+    async fn upsert(&self, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()>;
+    // Mueve la venta `id` a (book_id, year): upsert del destino, borrado del
+    // original y total_sales de los dos libros, o todo o nada
+    async fn merge_into(&self, id: &ObjectId, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()>;
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>>;
+
+    // Suma de units de un libro (books.total_sales)
+    async fn total_units(&self, book_id: &ObjectId) -> anyhow::Result<i64>;
+}
+
+// Un backend con las cuatro colecciones (AppState reparte un mismo Arc)
+pub trait Repo: AuthorRepo + BookRepo + ReviewRepo + SaleRepo {}
+
+impl<T: AuthorRepo + BookRepo + ReviewRepo + SaleRepo> Repo for T {}
@@ -0,0 +1,1221 @@
+// Backend MongoDB de los repositorios. Las escrituras que tocan varias
+// colecciones (cascadas, fusión de ventas) van en una transacción.
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    error::{ErrorKind, TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    options::{Collation, CollationStrength, ReturnDocument},
+    ClientSession, Collection, Database,
+};
+use serde::de::DeserializeOwned;
+
+use super::{AuthorFilter, AuthorRepo, BookFilter, BookRepo, Cascade, ReviewFilter, ReviewRepo, SaleFilter, SaleRepo, SearchBase, SearchPage};
+use crate::models::{bson_date, Author, AuthorSummary, Book, BookWithAuthor, Review, Sale, SearchHitKind, SearchItem, Suggestion, TopRatedBook, TopSellingBook};
+use crate::search::facets::{self, FacetSelection};
+use crate::search::query::{self as query_parser, RegexClauses};
+
+// Transacciones: intentos ante errores transitorios y espera entre ellos
+const TXN_ATTEMPTS: u32 = 3;
+const TXN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(50);
+
+// Autocompletado: presupuesto de tiempo por consulta (mejor nada que tarde)
+const SUGGEST_MAX_TIME: std::time::Duration = std::time::Duration::from_millis(150);
+
+// Collation de los índices de autocompletado (y de sus consultas): sin
+// distinguir mayúsculas ni acentos
+pub fn suggest_collation() -> Collation {
+    Collation::builder()
+        .locale("en".to_string())
+        .strength(CollationStrength::Primary)
+        .build()
+}
+
+#[derive(Clone)]
+pub struct MongoRepo {
+    db: Database,
+}
+
+impl MongoRepo {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    fn authors(&self) -> Collection<Author> {
+        self.db.collection("authors")
+    }
+    fn books(&self) -> Collection<Book> {
+        self.db.collection("books")
+    }
+    fn reviews(&self) -> Collection<Review> {
+        self.db.collection("reviews")
+    }
+    fn sales(&self) -> Collection<Sale> {
+        self.db.collection("sales")
+    }
+
+    // Corre `body` en una transacción multi-documento. Ante un error
+    // transitorio (elección de primario, conflicto de escritura) se aborta y
+    // se repite todo `body`; un commit de resultado incierto se reintenta.
+    // Sin replica set (mongod standalone) no hay transacciones: se avisa y se
+    // corre igual, sin atomicidad.
+    async fn transaction<T, F>(&self, what: &str, mut body: F) -> mongodb::error::Result<T>
+    where
+        F: for<'s> FnMut(&'s mut ClientSession) -> BoxFuture<'s, mongodb::error::Result<T>>,
+    {
+        let mut session = self.db.client().start_session().await?;
+        let mut attempt = 1;
+        loop {
+            let result = match session.start_transaction().await {
+                Ok(()) => match body(&mut session).await {
+                    Ok(value) => commit(&mut session).await.map(|()| value),
+                    Err(e) => {
+                        let _ = session.abort_transaction().await;
+                        Err(e)
+                    }
+                },
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Err(e) if transactions_unsupported(&e) => {
+                    eprintln!("[mongo] {what}: transactions are not supported by this deployment ({e}). Running without a transaction (use a replica set).");
+                    return body(&mut session).await;
+                }
+                Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) && attempt < TXN_ATTEMPTS => {
+                    eprintln!("[mongo] {what}: transient error, retrying ({attempt}/{TXN_ATTEMPTS}): {e}");
+                    tokio::time::sleep(TXN_BACKOFF * attempt).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    eprintln!("[mongo] {what} failed: {e}");
+                    return Err(e);
+                }
+                Ok(value) => return Ok(value),
+            }
+        }
+    }
+
+    // Agregación sobre `collection`; los documentos que no encajan en T se saltan
+    async fn aggregate<T: DeserializeOwned>(&self, collection: &str, pipeline: Vec<Document>) -> anyhow::Result<Vec<T>> {
+        let cursor = self.db.collection::<Document>(collection).aggregate(pipeline).await?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        Ok(documents.into_iter().filter_map(|d| mongodb::bson::from_document(d).ok()).collect())
+    }
+
+    // Rango [prefix, prefix + U+FFFF) con la collation del índice: U+FFFF tiene
+    // el peso máximo, así que el rango cubre todo lo que empieza por `prefix`.
+    // Si se pasa de SUGGEST_MAX_TIME no hay sugerencias de ese campo (no es un error).
+    async fn suggest_field(&self, kind: SearchHitKind, collection: &str, field: &str, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>> {
+        let upper = format!("{prefix}\u{ffff}");
+        let found: mongodb::error::Result<Vec<Document>> = async {
+            self.db
+                .collection::<Document>(collection)
+                .find(doc! { field: { "$gte": prefix, "$lt": upper } })
+                .projection(doc! { field: 1 })
+                .sort(doc! { field: 1 })
+                .limit(limit)
+                .collation(suggest_collation())
+                .max_time(SUGGEST_MAX_TIME)
+                .await?
+                .try_collect()
+                .await
+        }
+        .await;
+        let documents = match found {
+            Err(e) if max_time_expired(&e) => {
+                eprintln!("[suggest] {collection}.{field} took longer than {SUGGEST_MAX_TIME:?}, no suggestions");
+                return Ok(vec![]);
+            }
+            found => found?,
+        };
+
+        Ok(documents
+            .into_iter()
+            .filter_map(|d| {
+                Some(Suggestion {
+                    kind,
+                    id: d.get_object_id("_id").ok()?.to_hex(),
+                    text: d.get_str(field).ok()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    // Una sola agregación por grupo: filtro base (+ relevancia), campos
+    // calculados y un $facet con la página, el total y (con `facets`, en
+    // libros) los conteos por faceta.
+    async fn search_run(&self, target: &SearchTarget, base: &SearchBase, facets: Option<&FacetSelection>, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        let (mut pipeline, sort) = match base {
+            // $match con $text tiene que ser la primera etapa
+            SearchBase::Text { query, language } => (
+                vec![
+                    doc! { "$match": { "$text": { "$search": query, "$language": language } } },
+                    // "relevance" y no "score": pisaría la nota (1..5) de las reseñas
+                    doc! { "$addFields": { "relevance": { "$meta": "textScore" } } },
+                ],
+                target.sort_by_score(),
+            ),
+            SearchBase::Regex { query, fuzzy } => {
+                (vec![doc! { "$match": target.regex_filter(&query_parser::regex_clauses(query, *fuzzy)) }], target.sort.clone())
+            }
+            // Hits del motor: el orden lo da el ranking, Mongo filtra e hidrata
+            SearchBase::Ranked { hits, query } => {
+                let ids: Vec<ObjectId> = hits.iter().map(|(id, _)| *id).collect();
+                let scores: Vec<f64> = hits.iter().map(|(_, s)| *s).collect();
+                let mut stages = vec![doc! { "$match": { "_id": { "$in": &ids } } }];
+                let clauses = query_parser::ranked_clauses(query);
+                if !clauses.required.is_empty() || !clauses.excluded.is_empty() {
+                    stages.push(doc! { "$match": target.regex_filter(&clauses) });
+                }
+                stages.push(doc! { "$addFields": { "rank": { "$indexOfArray": [&ids, "$_id"] } } });
+                stages.push(doc! { "$addFields": { "relevance": { "$arrayElemAt": [scores, "$rank"] } } });
+                (stages, doc! { "rank": 1 })
+            }
+        };
+        if facets.is_some() {
+            pipeline.extend(facets::enrich_stages());
+        }
+
+        let selection = facets.map(|f| f.match_doc(None)).unwrap_or_default();
+        let mut results = vec![
+            doc! { "$match": selection.clone() },
+            doc! { "$sort": sort },
+            doc! { "$skip": skip },
+            doc! { "$limit": limit },
+        ];
+        results.extend(target.result_stages());
+
+        let mut facet = doc! {
+            "results": results,
+            "total": [{ "$match": selection }, { "$count": "n" }],
+        };
+        if let Some(f) = facets {
+            facet.extend(facets::count_pipelines(f));
+        }
+        pipeline.push(doc! { "$facet": facet });
+
+        let mut cursor = self.db.collection::<Document>(target.collection).aggregate(pipeline).await?;
+        let out = cursor.try_next().await?.unwrap_or_default();
+
+        let documents: Vec<Document> = out
+            .get_array("results")
+            .map(|a| a.iter().filter_map(|b| b.as_document().cloned()).collect())
+            .unwrap_or_default();
+        let total = out
+            .get_array("total")
+            .ok()
+            .and_then(|a| a.first())
+            .and_then(|b| b.as_document())
+            .and_then(|d| d.get("n"))
+            .and_then(|n| n.as_i32().map(i64::from).or(n.as_i64()))
+            .unwrap_or(0);
+
+        Ok(SearchPage {
+            items: target.to_items(documents),
+            total,
+            facets: facets.map(|f| facets::counts_from(&out, f)),
+        })
+    }
+}
+
+// Qué colección consulta cada tipo de resultado de la búsqueda global y cómo
+// se proyecta
+struct SearchTarget {
+    kind: SearchHitKind,
+    collection: &'static str,
+    regex_fields: &'static [&'static str],
+    sort: Document,   // orden en modo prefijo / desempate por relevancia
+}
+
+impl SearchTarget {
+    fn of(kind: SearchHitKind) -> Self {
+        match kind {
+            SearchHitKind::Book => Self {
+                kind,
+                collection: "books",
+                regex_fields: &["title", "summary"],
+                sort: doc! { "title": 1 },
+            },
+            SearchHitKind::Author => Self {
+                kind,
+                collection: "authors",
+                regex_fields: &["name", "country", "description"],
+                sort: doc! { "name": 1 },
+            },
+            SearchHitKind::Review => Self {
+                kind,
+                collection: "reviews",
+                regex_fields: &["text"],
+                sort: doc! { "up_votes": -1, "_id": 1 },
+            },
+        }
+    }
+
+    fn sort_by_score(&self) -> Document {
+        let mut sort = doc! { "relevance": -1 };
+        sort.extend(self.sort.clone());
+        sort
+    }
+
+    // $regex sobre los campos de texto: encuentra coincidencias parciales
+    // (prefijos, palabras a medio escribir) que $text no ve.
+    fn regex_filter(&self, clauses: &RegexClauses) -> Document {
+        let field_match = |pattern: &String| {
+            let any_field: Vec<Document> = self
+                .regex_fields
+                .iter()
+                .map(|f| doc! { *f: { "$regex": pattern, "$options": "i" } })
+                .collect();
+            doc! { "$or": any_field }
+        };
+
+        let mut and: Vec<Document> = clauses.required.iter().map(field_match).collect();
+        if !clauses.excluded.is_empty() {
+            let excluded: Vec<Document> = clauses.excluded.iter().map(field_match).collect();
+            and.push(doc! { "$nor": excluded });
+        }
+        doc! { "$and": and }
+    }
+
+    // Etapas tras paginar: joins + proyección al struct de resultado.
+    // La relevancia se calcula como "relevance" (las reseñas ya tienen "score").
+    fn result_stages(&self) -> Vec<Document> {
+        match self.kind {
+            // el autor ya viene de facets::enrich_stages
+            SearchHitKind::Book => vec![doc! {
+                "$project": {
+                    "book_id": "$_id",
+                    "title": 1,
+                    "author_name": "$author.name",
+                    "summary": 1,
+                    "publication_date": bson_date::if_date("publication_date", doc! { "$dateToString": { "date": "$publication_date", "format": "%Y-%m-%d" } }),
+                    "score": "$relevance"
+                }
+            }],
+            SearchHitKind::Author => vec![doc! {
+                "$project": {
+                    "author_id": "$_id",
+                    "name": 1,
+                    "country": 1,
+                    "description": 1,
+                    "score": "$relevance"
+                }
+            }],
+            SearchHitKind::Review => vec![
+                doc! {
+                    "$lookup": {
+                        "from": "books",
+                        "localField": "book_id",
+                        "foreignField": "_id",
+                        "as": "book"
+                    }
+                },
+                doc! { "$unwind": "$book" },
+                doc! {
+                    "$project": {
+                        "review_id": "$_id",
+                        "book_id": 1,
+                        "book_title": "$book.title",
+                        "snippet": "$text",
+                        "review_score": "$score",
+                        "score": "$relevance"
+                    }
+                },
+            ],
+        }
+    }
+
+    fn to_items(&self, documents: Vec<Document>) -> Vec<SearchItem> {
+        documents
+            .into_iter()
+            .filter_map(|doc| {
+                Some(match self.kind {
+                    SearchHitKind::Book => SearchItem::Book(mongodb::bson::from_document(doc).ok()?),
+                    SearchHitKind::Author => SearchItem::Author(mongodb::bson::from_document(doc).ok()?),
+                    SearchHitKind::Review => SearchItem::Review(mongodb::bson::from_document(doc).ok()?),
+                })
+            })
+            .collect()
+    }
+}
+
+// Solo un mongod standalone: o el driver ya sabe que la topología no
+// admite transacciones, o (se anuncia con soporte de sesiones) es el servidor
+// el que rechaza la primera operación con IllegalOperation. Cualquier otro
+// error de transacción es un fallo de verdad.
+const NO_TXN_DEPLOYMENT: &str = "Transactions are not supported by this deployment";
+const NO_TXN_STANDALONE: &str = "Transaction numbers are only allowed on a replica set member or mongos";
+
+fn transactions_unsupported(e: &mongodb::error::Error) -> bool {
+    match &*e.kind {
+        ErrorKind::Transaction { message, .. } => message == NO_TXN_DEPLOYMENT,
+        ErrorKind::Command(c) => c.code == 20 && c.message.contains(NO_TXN_STANDALONE),
+        _ => false,
+    }
+}
+
+// MaxTimeMSExpired: la consulta agotó su max_time
+fn max_time_expired(e: &mongodb::error::Error) -> bool {
+    matches!(&*e.kind, ErrorKind::Command(c) if c.code == 50)
+}
+
+async fn commit(session: &mut ClientSession) -> mongodb::error::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match session.commit_transaction().await {
+            Err(e) if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) && attempt < TXN_ATTEMPTS => attempt += 1,
+            result => return result,
+        }
+    }
+}
+
+// Un string que no es fecha se lee como None (bson_date); antes de que el update
+// escriba encima lo movemos a `<field>_raw` para no perder el valor original
+async fn keep_raw_date<T: Send + Sync>(col: &Collection<T>, id: &ObjectId, field: &str) -> mongodb::error::Result<()> {
+    col.update_one(doc! { "_id": id, field: { "$type": "string" } }, doc! { "$rename": { field: format!("{field}_raw") } }).await?;
+    Ok(())
+}
+
+// Más vendidos: ventas del libro y del autor, y si fue top 5 en su año
+fn top_selling_pipeline() -> Vec<Document> {
+    vec![
+        // Start with books
+        doc! {
+            "$lookup": {
+                "from": "sales",
+                "localField": "_id",
+                "foreignField": "book_id",
+                "as": "sales"
+            }
+        },
+        // Calculate total sales for each book
+        doc! {
+            "$addFields": {
+                "book_total_sales": { "$sum": "$sales.units" },
+                // null si el libro no tiene fecha (o no es una fecha)
+                "publication_year": bson_date::if_date("publication_date", doc! { "$year": "$publication_date" })
+            }
+        },
+        // Only include books with sales
+        doc! {
+            "$match": {
+                "book_total_sales": { "$gt": 0 }
+            }
+        },
+        // Sort by total sales (highest first) and limit to top 50
+        doc! {
+            "$sort": {
+                "book_total_sales": -1
+            }
+        },
+        doc! {
+            "$limit": 50
+        },
+        // Lookup author information
+        doc! {
+            "$lookup": {
+                "from": "authors",
+                "localField": "author_id",
+                "foreignField": "_id",
+                "as": "author"
+            }
+        },
+        doc! {
+            "$unwind": "$author"
+        },
+        // Calculate author total sales (all their books)
+        doc! {
+            "$lookup": {
+                "from": "books",
+                "localField": "author._id",
+                "foreignField": "author_id",
+                "as": "author_books"
+            }
+        },
+        doc! {
+            "$lookup": {
+                "from": "sales",
+                "localField": "author_books._id",
+                "foreignField": "book_id",
+                "as": "author_sales"
+            }
+        },
+        doc! {
+            "$addFields": {
+                "author_total_sales": { "$sum": "$author_sales.units" }
+            }
+        },
+        doc! {
+            "$lookup": {
+                "from": "books",
+                // [1 de enero, 1 de enero siguiente): rango sobre el índice de publication_date
+                "let": {
+                    "year_start": { "$dateFromParts": { "year": "$publication_year" } },
+                    "year_end": { "$dateFromParts": { "year": { "$add": ["$publication_year", 1] } } }
+                },
+                "pipeline": [
+                    {
+                        "$match": {
+                            "$expr": {
+                                "$and": [
+                                    { "$gte": ["$publication_date", "$$year_start"] },
+                                    { "$lt": ["$publication_date", "$$year_end"] }
+                                ]
+                            }
+                        }
+                    },
+                    {
+                        "$lookup": {
+                            "from": "sales",
+                            "localField": "_id",
+                            "foreignField": "book_id",
+                            "as": "book_sales"
+                        }
+                    },
+                    {
+                        "$addFields": {
+                            "total_sales": { "$sum": "$book_sales.units" }
+                        }
+                    },
+                    {
+                        "$sort": { "total_sales": -1 }
+                    },
+                    {
+                        "$limit": 5
+                    },
+                    {
+                        "$project": { "_id": 1 }
+                    }
+                ],
+                "as": "top_5_same_year"
+            }
+        },
+        doc! {
+            "$addFields": {
+                "was_top_5_in_publication_year": {
+                    "$cond": [
+                        { "$ne": ["$publication_year", null] },
+                        {
+                            "$in": [
+                                "$_id",
+                                { "$map": {
+                                    "input": "$top_5_same_year",
+                                    "as": "book",
+                                    "in": "$$book._id"
+                                }}
+                            ]
+                        },
+                        false
+                    ]
+                }
+            }
+        },
+        doc! {
+            "$project": {
+                "book_id": "$_id",
+                "title": 1,
+                "author_name": "$author.name",
+                "publication_date": bson_date::if_date("publication_date", doc! { "$dateToString": { "date": "$publication_date", "format": "%Y-%m-%d" } }),
+                "book_total_sales": 1,
+                "author_total_sales": 1,
+                "was_top_5_in_publication_year": 1
+            }
+        }
+    ]
+}
+
+// sum(units) de las ventas de un libro
+fn units_pipeline(book_id: &ObjectId) -> Vec<Document> {
+    vec![
+        doc! { "$match": { "book_id": book_id } },
+        doc! { "$group": { "_id": Bson::Null, "total": { "$sum": "$units" } } },
+    ]
+}
+
+fn units_total(docs: &[Document]) -> i64 {
+    match docs.first().and_then(|d| d.get("total")) {
+        Some(Bson::Int64(v)) => *v,
+        Some(Bson::Int32(v)) => *v as i64,
+        _ => 0,
+    }
+}
+
+// Recalcula books.total_sales dentro de la transacción que cambió sus ventas
+async fn store_book_total(
+    sales: &Collection<Sale>,
+    books: &Collection<Book>,
+    book_id: &ObjectId,
+    session: &mut ClientSession,
+) -> mongodb::error::Result<()> {
+    let docs: Vec<Document> = sales
+        .clone_with_type::<Document>()
+        .aggregate(units_pipeline(book_id))
+        .session(&mut *session)
+        .await?
+        .stream(&mut *session)
+        .try_collect()
+        .await?;
+    books
+        .update_one(doc! {"_id": book_id}, doc! {"$set": {"total_sales": units_total(&docs)}})
+        .session(&mut *session)
+        .await?;
+    Ok(())
+}
+
+// Ids de las reseñas de esos libros, dentro de la transacción de un borrado
+// en cascada (para sacarlas luego del índice de búsqueda).
+async fn book_review_ids(
+    reviews: &Collection<Review>,
+    book_ids: &[ObjectId],
+    session: &mut ClientSession,
+) -> mongodb::error::Result<Vec<ObjectId>> {
+    let found: Vec<Review> = reviews
+        .find(doc! {"book_id": {"$in": book_ids}})
+        .session(&mut *session)
+        .await?
+        .stream(session)
+        .try_collect()
+        .await?;
+    Ok(found.into_iter().filter_map(|r| r.id).collect())
+}
+
+// Campo de texto: $regex sin distinguir mayúsculas
+fn insert_pattern(filter: &mut Document, field: &str, pattern: &Option<String>) {
+    if let Some(p) = pattern {
+        filter.insert(field, doc! { "$regex": p, "$options": "i" });
+    }
+}
+
+fn inserted_id(id: Bson) -> anyhow::Result<ObjectId> {
+    id.as_object_id().ok_or_else(|| anyhow::anyhow!("inserted _id is not an ObjectId: {id}"))
+}
+
+#[async_trait]
+impl AuthorRepo for MongoRepo {
+    async fn list(&self, filter: &AuthorFilter) -> anyhow::Result<Vec<Author>> {
+        let mut f = Document::new();
+        insert_pattern(&mut f, "name", &filter.name);
+        Ok(self.authors().find(f).await?.try_collect().await?)
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Author>> {
+        Ok(self.authors().find_one(doc! {"_id": id}).await?)
+    }
+
+    async fn insert(&self, author: &Author) -> anyhow::Result<ObjectId> {
+        inserted_id(self.authors().insert_one(author).await?.inserted_id)
+    }
+
+    async fn update(&self, id: &ObjectId, author: &Author) -> anyhow::Result<Option<Author>> {
+        let set_doc = doc! {
+            "name": &author.name,
+            "date_of_birth": author.date_of_birth.map(bson_date::to_bson),
+            "country": &author.country,
+            "description": &author.description,
+        };
+        keep_raw_date(&self.authors(), id, "date_of_birth").await?;
+        Ok(self
+            .authors()
+            .find_one_and_update(doc! {"_id": id}, doc! {"$set": set_doc})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade> {
+        let (authors, books, reviews, sales) = (self.authors(), self.books(), self.reviews(), self.sales());
+        let author_id = *id;
+
+        let cascade = self.transaction("delete author", |s| {
+            let (authors, books, reviews, sales) = (authors.clone(), books.clone(), reviews.clone(), sales.clone());
+            Box::pin(async move {
+                // libros del autor
+                let found: Vec<Book> = books
+                    .find(doc! { "author_id": author_id })
+                    .session(&mut *s)
+                    .await?
+                    .stream(&mut *s)
+                    .try_collect()
+                    .await?;
+                let book_ids: Vec<ObjectId> = found.into_iter().filter_map(|b| b.id).collect();
+                let review_ids = book_review_ids(&reviews, &book_ids, s).await?;
+
+                // reseñas y ventas de esos libros, los libros y el autor
+                reviews.delete_many(doc! { "book_id": { "$in": &book_ids } }).session(&mut *s).await?;
+                sales.delete_many(doc! { "book_id": { "$in": &book_ids } }).session(&mut *s).await?;
+                books.delete_many(doc! { "author_id": author_id }).session(&mut *s).await?;
+                let deleted = authors.delete_one(doc! { "_id": author_id }).session(&mut *s).await?.deleted_count > 0;
+                Ok(Cascade { deleted, book_ids, review_ids })
+            })
+        }).await?;
+        Ok(cascade)
+    }
+
+    async fn summary(&self) -> anyhow::Result<Vec<AuthorSummary>> {
+        let pipeline = vec![
+            // Lookup books for each author
+            doc! {
+                "$lookup": {
+                    "from": "books",
+                    "localField": "_id",
+                    "foreignField": "author_id",
+                    "as": "books"
+                }
+            },
+            // Unwind books to get individual book documents
+            doc! {
+                "$unwind": {
+                    "path": "$books",
+                    "preserveNullAndEmptyArrays": true
+                }
+            },
+            // Lookup reviews for each book
+            doc! {
+                "$lookup": {
+                    "from": "reviews",
+                    "localField": "books._id",
+                    "foreignField": "book_id",
+                    "as": "reviews"
+                }
+            },
+            // Lookup sales for each book
+            doc! {
+                "$lookup": {
+                    "from": "sales",
+                    "localField": "books._id",
+                    "foreignField": "book_id",
+                    "as": "sales"
+                }
+            },
+            // Group by author to calculate aggregated values
+            doc! {
+                "$group": {
+                    "_id": "$_id",
+                    "name": { "$first": "$name" },
+                    "published_books": {
+                        "$sum": {
+                            "$cond": [
+                                { "$ne": ["$books", null] },
+                                1,
+                                0
+                            ]
+                        }
+                    },
+                    "total_reviews": { "$push": "$reviews" },
+                    "total_sales_data": { "$push": "$sales" }
+                }
+            },
+            // Calculate average score and total sales
+            doc! {
+                "$addFields": {
+                    "all_scores": {
+                        "$reduce": {
+                            "input": "$total_reviews",
+                            "initialValue": [],
+                            "in": { "$concatArrays": ["$$value", "$$this"] }
+                        }
+                    },
+                    "all_sales": {
+                        "$reduce": {
+                            "input": "$total_sales_data",
+                            "initialValue": [],
+                            "in": { "$concatArrays": ["$$value", "$$this"] }
+                        }
+                    }
+                }
+            },
+            doc! {
+                "$addFields": {
+                    "average_score": {
+                        "$cond": [
+                            { "$gt": [{ "$size": "$all_scores" }, 0] },
+                            { "$avg": "$all_scores.score" },
+                            0.0
+                        ]
+                    },
+                    "total_sales": {
+                        "$sum": "$all_sales.units"
+                    }
+                }
+            },
+            doc! {
+                "$project": {
+                    "author_id": "$_id",
+                    "name": 1,
+                    "published_books": 1,
+                    "average_score": { "$round": ["$average_score", 2] },
+                    "total_sales": 1
+                }
+            },
+            // Sort by name
+            doc! {
+                "$sort": {
+                    "name": 1
+                }
+            }
+        ];
+
+        self.aggregate("authors", pipeline).await
+    }
+
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>> {
+        self.suggest_field(SearchHitKind::Author, "authors", "name", prefix, limit).await
+    }
+
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        self.search_run(&SearchTarget::of(SearchHitKind::Author), base, None, skip, limit).await
+    }
+}
+
+#[async_trait]
+impl BookRepo for MongoRepo {
+    async fn list(&self, filter: &BookFilter) -> anyhow::Result<Vec<Book>> {
+        let mut f = Document::new();
+        insert_pattern(&mut f, "title", &filter.title);
+        if let Some(aid) = &filter.author_id {
+            f.insert("author_id", aid);
+        }
+        Ok(self.books().find(f).await?.try_collect().await?)
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Book>> {
+        Ok(self.books().find_one(doc! {"_id": id}).await?)
+    }
+
+    async fn insert(&self, book: &Book) -> anyhow::Result<ObjectId> {
+        inserted_id(self.books().insert_one(book).await?.inserted_id)
+    }
+
+    async fn update(&self, id: &ObjectId, book: &Book) -> anyhow::Result<Option<Book>> {
+        let set_doc = doc! {
+            "title": &book.title,
+            "author_id": book.author_id,
+            "summary": &book.summary,
+            "publication_date": book.publication_date.map(bson_date::to_bson),
+        };
+        keep_raw_date(&self.books(), id, "publication_date").await?;
+        Ok(self
+            .books()
+            .find_one_and_update(doc! {"_id": id}, doc! {"$set": set_doc})
+            .return_document(ReturnDocument::After)
+            .await?)
+    }
+
+    async fn set_total_sales(&self, id: &ObjectId, total: i64) -> anyhow::Result<()> {
+        self.books().update_one(doc! {"_id": id}, doc! {"$set": {"total_sales": total}}).await?;
+        Ok(())
+    }
+
+    async fn delete_cascade(&self, id: &ObjectId) -> anyhow::Result<Cascade> {
+        let (books, reviews, sales) = (self.books(), self.reviews(), self.sales());
+        let book_id = *id;
+
+        let cascade = self.transaction("delete book", |s| {
+            let (books, reviews, sales) = (books.clone(), reviews.clone(), sales.clone());
+            Box::pin(async move {
+                let review_ids = book_review_ids(&reviews, &[book_id], s).await?;
+                reviews.delete_many(doc! {"book_id": book_id}).session(&mut *s).await?;
+                sales.delete_many(doc! {"book_id": book_id}).session(&mut *s).await?;
+                let deleted = books.delete_one(doc! {"_id": book_id}).session(&mut *s).await?.deleted_count > 0;
+                Ok(Cascade { deleted, book_ids: vec![book_id], review_ids })
+            })
+        }).await?;
+        Ok(cascade)
+    }
+
+    async fn list_with_authors(&self) -> anyhow::Result<Vec<BookWithAuthor>> {
+        let pipeline = vec![
+            doc! {
+                "$lookup": {
+                    "from": "authors",
+                    "localField": "author_id",
+                    "foreignField": "_id",
+                    "as": "author"
+                }
+            },
+            doc! {
+                "$unwind": "$author"
+            },
+            doc! {
+                "$project": {
+                    "_id": 1,
+                    "title": 1,
+                    "author_name": "$author.name"
+                }
+            },
+            doc! {
+                "$sort": {
+                    "title": 1
+                }
+            }
+        ];
+
+        let docs: Vec<Document> = self.aggregate("books", pipeline).await?;
+        let books: Vec<BookWithAuthor> = docs.iter()
+            .filter_map(|doc| {
+                Some(BookWithAuthor {
+                    id: doc.get_object_id("_id").ok()?.to_string(),
+                    title: doc.get_str("title").ok()?.to_string(),
+                    author_name: doc.get_str("author_name").ok()?.to_string(),
+                })
+            })
+            .collect();
+
+        Ok(books)
+    }
+
+    async fn top_rated(&self) -> anyhow::Result<Vec<TopRatedBook>> {
+        let pipeline = vec![
+            // Start with books
+            doc! {
+                "$lookup": {
+                    "from": "reviews",
+                    "localField": "_id",
+                    "foreignField": "book_id",
+                    "as": "reviews"
+                }
+            },
+            // Only include books that have reviews
+            doc! {
+                "$match": {
+                    "reviews": { "$ne": [] }
+                }
+            },
+            // Calculate average score for each book
+            doc! {
+                "$addFields": {
+                    "average_score": { "$avg": "$reviews.score" },
+                    "total_reviews": { "$size": "$reviews" }
+                }
+            },
+            // Sort by average score (highest first)
+            doc! {
+                "$sort": {
+                    "average_score": -1,
+                    "total_reviews": -1  // Secondary sort by number of reviews
+                }
+            },
+            // Take top 10
+            doc! {
+                "$limit": 10
+            },
+            // Lookup author information
+            doc! {
+                "$lookup": {
+                    "from": "authors",
+                    "localField": "author_id",
+                    "foreignField": "_id",
+                    "as": "author"
+                }
+            },
+            // Unwind author
+            doc! {
+                "$unwind": "$author"
+            },
+            // Add highest and lowest rated reviews
+            doc! {
+                "$addFields": {
+                    "highest_rated_review": {
+                        "$arrayElemAt": [
+                            {
+                                "$filter": {
+                                    "input": "$reviews",
+                                    "as": "review",
+                                    "cond": {
+                                        "$eq": [
+                                            "$$review.score",
+                                            { "$max": "$reviews.score" }
+                                        ]
+                                    }
+                                }
+                            },
+                            0
+                        ]
+                    },
+                    "lowest_rated_review": {
+                        "$arrayElemAt": [
+                            {
+                                "$filter": {
+                                    "input": "$reviews",
+                                    "as": "review",
+                                    "cond": {
+                                        "$eq": [
+                                            "$$review.score",
+                                            { "$min": "$reviews.score" }
+                                        ]
+                                    }
+                                }
+                            },
+                            0
+                        ]
+                    }
+                }
+            },
+            doc! {
+                "$project": {
+                    "book_id": "$_id",
+                    "title": 1,
+                    "author_name": "$author.name",
+                    "average_score": { "$round": ["$average_score", 2] },
+                    "total_reviews": 1,
+                    "highest_rated_review": {
+                        "text": "$highest_rated_review.text",
+                        "score": "$highest_rated_review.score",
+                        "up_votes": "$highest_rated_review.up_votes"
+                    },
+                    "lowest_rated_review": {
+                        "text": "$lowest_rated_review.text",
+                        "score": "$lowest_rated_review.score",
+                        "up_votes": "$lowest_rated_review.up_votes"
+                    }
+                }
+            }
+        ];
+
+        self.aggregate("books", pipeline).await
+    }
+
+    async fn top_selling(&self) -> anyhow::Result<Vec<TopSellingBook>> {
+        self.aggregate("books", top_selling_pipeline()).await
+    }
+
+    async fn suggest(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<Suggestion>> {
+        self.suggest_field(SearchHitKind::Book, "books", "title", prefix, limit).await
+    }
+
+    async fn search(&self, base: &SearchBase, facets: &FacetSelection, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        self.search_run(&SearchTarget::of(SearchHitKind::Book), base, Some(facets), skip, limit).await
+    }
+}
+
+#[async_trait]
+impl ReviewRepo for MongoRepo {
+    async fn list(&self, filter: &ReviewFilter) -> anyhow::Result<Vec<Review>> {
+        let mut f = Document::new();
+        insert_pattern(&mut f, "text", &filter.text);
+        if let Some(bid) = &filter.book_id {
+            f.insert("book_id", bid);
+        }
+        Ok(self.reviews().find(f).await?.try_collect().await?)
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Review>> {
+        Ok(self.reviews().find_one(doc! {"_id": id}).await?)
+    }
+
+    async fn insert(&self, review: &Review) -> anyhow::Result<ObjectId> {
+        inserted_id(self.reviews().insert_one(review).await?.inserted_id)
+    }
+
+    async fn update(&self, id: &ObjectId, review: &Review) -> anyhow::Result<Option<Review>> {
+        let set_doc = doc! {
+            "book_id": review.book_id,
+            "text": &review.text,
+            "score": review.score,
+            "up_votes": review.up_votes,
+        };
+        Ok(self
+            .reviews()
+            .find_one_and_update(doc! {"_id": id}, doc! {"$set": set_doc})
+            .return_document(ReturnDocument::Before)
+            .await?)
+    }
+
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Review>> {
+        Ok(self.reviews().find_one_and_delete(doc! {"_id": id}).await?)
+    }
+
+    async fn average_score(&self, book_id: &ObjectId) -> anyhow::Result<f64> {
+        #[derive(serde::Deserialize)]
+        struct AvgDoc { avg: Option<f64> }
+
+        let pipeline = vec![
+            doc! { "$match": { "book_id": book_id } },
+            doc! { "$group": { "_id": null, "avg": { "$avg": "$score" } } },
+        ];
+        let docs: Vec<AvgDoc> = self.aggregate("reviews", pipeline).await?;
+        Ok(docs.first().and_then(|d| d.avg).unwrap_or(0.0))
+    }
+
+    async fn search(&self, base: &SearchBase, skip: i64, limit: i64) -> anyhow::Result<SearchPage> {
+        self.search_run(&SearchTarget::of(SearchHitKind::Review), base, None, skip, limit).await
+    }
+}
+
+#[async_trait]
+impl SaleRepo for MongoRepo {
+    async fn list(&self, filter: &SaleFilter) -> anyhow::Result<Vec<Sale>> {
+        let mut f = Document::new();
+        if let Some(bid) = &filter.book_id {
+            f.insert("book_id", bid);
+        }
+        if let Some(y) = filter.year {
+            f.insert("year", y);
+        }
+        Ok(self.sales().find(f).await?.try_collect().await?)
+    }
+
+    async fn get(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>> {
+        Ok(self.sales().find_one(doc! {"_id": id}).await?)
+    }
+
+    async fn find_by_key(&self, book_id: &ObjectId, year: i32, except: Option<&ObjectId>) -> anyhow::Result<Option<Sale>> {
+        let mut filter = doc! { "book_id": book_id, "year": year };
+        if let Some(oid) = except {
+            filter.insert("_id", doc! { "$ne": oid });
+        }
+        Ok(self.sales().find_one(filter).await?)
+    }
+
+    async fn insert(&self, sale: &Sale) -> anyhow::Result<ObjectId> {
+        inserted_id(self.sales().insert_one(sale).await?.inserted_id)
+    }
+
+    async fn update(&self, id: &ObjectId, sale: &Sale) -> anyhow::Result<Option<Sale>> {
+        Ok(self
+            .sales()
+            .find_one_and_update(
+                doc! {"_id": id},
+                doc! {"$set": {"book_id": sale.book_id, "year": sale.year, "units": sale.units}},
+            )
+            .return_document(ReturnDocument::Before)
+            .await?)
+    }
+
+    async fn upsert(&self, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()> {
+        let (sales, books) = (self.sales(), self.books());
+        let book_id = *book_id;
+        self.transaction("upsert sale", |s| {
+            let (sales, books) = (sales.clone(), books.clone());
+            Box::pin(async move {
+                sales.update_one(doc! {"book_id": book_id, "year": year}, doc! {"$set": {"units": units}})
+                    .upsert(true)
+                    .session(&mut *s)
+                    .await?;
+                store_book_total(&sales, &books, &book_id, s).await
+            })
+        }).await?;
+        Ok(())
+    }
+
+    async fn merge_into(&self, id: &ObjectId, book_id: &ObjectId, year: i32, units: i64) -> anyhow::Result<()> {
+        let (sales, books) = (self.sales(), self.books());
+        let (oid, book_id) = (*id, *book_id);
+        self.transaction("merge sale", |s| {
+            let (sales, books) = (sales.clone(), books.clone());
+            Box::pin(async move {
+                sales.update_one(doc! {"book_id": book_id, "year": year}, doc! {"$set": {"units": units}})
+                    .upsert(true)
+                    .session(&mut *s)
+                    .await?;
+                // el libro de origen también pierde estas ventas
+                if let Some(prev) = sales.find_one_and_delete(doc! {"_id": oid}).session(&mut *s).await? {
+                    store_book_total(&sales, &books, &prev.book_id, s).await?;
+                }
+                store_book_total(&sales, &books, &book_id, s).await
+            })
+        }).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &ObjectId) -> anyhow::Result<Option<Sale>> {
+        Ok(self.sales().find_one_and_delete(doc! {"_id": id}).await?)
+    }
+
+    async fn total_units(&self, book_id: &ObjectId) -> anyhow::Result<i64> {
+        let docs: Vec<Document> = self.aggregate("sales", units_pipeline(book_id)).await?;
+        Ok(units_total(&docs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{self, doc, oid::ObjectId, Bson};
+    use mongodb::error::{CommandError, Error, ErrorKind};
+
+    use super::{max_time_expired, top_selling_pipeline, transactions_unsupported, SearchTarget, NO_TXN_STANDALONE};
+    use crate::models::{bson_date, SearchHitKind, SearchItem};
+    use crate::search::facets;
+
+    fn command_error(code: i32, name: &str) -> Error {
+        command_error_with(code, name, "x")
+    }
+
+    fn command_error_with(code: i32, name: &str, errmsg: &str) -> Error {
+        let command: CommandError = bson::from_document(doc! { "code": code, "codeName": name, "errmsg": errmsg }).unwrap();
+        Error::from(ErrorKind::Command(command))
+    }
+
+    #[test]
+    fn only_a_standalone_server_runs_without_transaction() {
+        assert!(transactions_unsupported(&command_error_with(20, "IllegalOperation", NO_TXN_STANDALONE)));
+
+        // otros IllegalOperation y errores de transacción fallan
+        assert!(!transactions_unsupported(&command_error_with(20, "IllegalOperation", "Cannot run 'count' in a multi-document transaction")));
+        assert!(!transactions_unsupported(&command_error(251, "NoSuchTransaction")));
+        assert!(!transactions_unsupported(&Error::custom("boom")));
+    }
+
+    #[test]
+    fn only_max_time_ms_expired_counts_as_a_timeout() {
+        assert!(max_time_expired(&command_error(50, "MaxTimeMSExpired")));
+        assert!(!max_time_expired(&command_error(11000, "DuplicateKey")));
+        assert!(!max_time_expired(&Error::custom("boom")));
+    }
+
+    #[test]
+    fn review_projection_keeps_the_review_score_apart_from_relevance() {
+        let stages = SearchTarget::of(SearchHitKind::Review).result_stages();
+        let project = stages.iter().find_map(|s| s.get_document("$project").ok()).unwrap();
+        assert_eq!(project.get("review_score"), Some(&Bson::String("$score".into())));
+        assert_eq!(project.get("score"), Some(&Bson::String("$relevance".into())));
+    }
+
+    #[test]
+    fn review_hits_deserialize_with_both_scores() {
+        let target = SearchTarget::of(SearchHitKind::Review);
+        let projected = doc! {
+            "review_id": ObjectId::new(),
+            "book_id": ObjectId::new(),
+            "book_title": "Rayuela",
+            "snippet": "una novela enorme",
+            "review_score": 4,
+            "score": 1.25,
+        };
+        let items = target.to_items(vec![projected]);
+        let [SearchItem::Review(r)] = items.as_slice() else { panic!("review hit dropped") };
+        assert_eq!(r.review_score, 4);
+        assert_eq!(r.score, Some(1.25));
+    }
+
+    // $year / $dateToString que no están dentro del $cond de bson_date::if_date
+    fn unguarded_date_ops(value: &Bson) -> usize {
+        let is_date = Bson::Document(doc! { "$eq": [{ "$type": "$publication_date" }, "date"] });
+        match value {
+            Bson::Document(d) if d.get_array("$cond").is_ok_and(|c| c.first() == Some(&is_date)) => 0,
+            Bson::Document(d) => d
+                .iter()
+                .map(|(k, v)| usize::from(k == "$year" || k == "$dateToString") + unguarded_date_ops(v))
+                .sum(),
+            Bson::Array(a) => a.iter().map(unguarded_date_ops).sum(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn date_expressions_skip_strings_that_are_not_dates() {
+        let guarded = bson_date::if_date("publication_date", doc! { "$year": "$publication_date" });
+        assert_eq!(unguarded_date_ops(&Bson::Document(guarded.clone())), 0);
+        assert_eq!(unguarded_date_ops(guarded.get_array("$cond").unwrap().get(1).unwrap()), 1);
+
+        // un string sin migrar en publication_date no llega a $year / $dateToString
+        let stages = top_selling_pipeline()
+            .into_iter()
+            .chain(facets::enrich_stages())
+            .chain(SearchTarget::of(SearchHitKind::Book).result_stages());
+        for stage in stages {
+            assert_eq!(unguarded_date_ops(&Bson::Document(stage.clone())), 0, "{stage}");
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+
+use crate::cache::metrics::{metrics, CacheFamily, FamilySnapshot};
+use crate::db::AppState;
+use crate::error::{AppError, AppResult};
+
+// Claves de ejemplo que se listan por familia
+const KEYS_PER_FAMILY: usize = 20;
+
+#[derive(Serialize)]
+struct FamilyView {
+    #[serde(flatten)]
+    stats: FamilySnapshot,
+    prefixes: &'static [&'static str],
+    keys: Vec<String>,
+    more_keys: bool,
+}
+
+#[derive(Serialize)]
+struct CacheCtx {
+    families: Vec<FamilyView>,
+    warmup_queries: Vec<String>,
+    message: String,
+    message_kind: String,
+}
+
+// GET /admin/cache
+// Contadores por familia de claves, algunas claves de cada una y botón para vaciarla.
+#[get("/cache")]
+pub async fn cache(state: &State<AppState>, flash: Option<FlashMessage<'_>>) -> Template {
+    let mut families = Vec::new();
+    for (family, stats) in CacheFamily::ALL.into_iter().zip(metrics().snapshot()) {
+        let mut keys = state.cache_family_keys(family, KEYS_PER_FAMILY + 1).await;
+        let more_keys = keys.len() > KEYS_PER_FAMILY;
+        keys.truncate(KEYS_PER_FAMILY);
+        families.push(FamilyView { stats, prefixes: family.prefixes(), keys, more_keys });
+    }
+
+    let (message_kind, message) = flash.map(|f| (f.kind().to_string(), f.message().to_string())).unwrap_or_default();
+    let warmup_queries = state.warmup_queries.clone();
+    Template::render("admin/cache", &CacheCtx { families, warmup_queries, message, message_kind })
+}
+
+// POST /admin/cache/flush/<family>
+#[post("/cache/flush/<family>")]
+pub async fn flush(state: &State<AppState>, family: &str) -> AppResult<Flash<Redirect>> {
+    match CacheFamily::parse(family) {
+        Some(f) if !f.prefixes().is_empty() => {
+            state.cache_flush_family(f).await;
+            Ok(Flash::success(Redirect::to("/admin/cache"), format!("Flushed {}", f.name())))
+        }
+        _ => Err(AppError::not_found("cache family")),
+    }
+}
+
+// POST /admin/cache/warmup
+// Precalienta la cache (p.ej. después de correr el seeder); espera a que termine.
+#[post("/cache/warmup")]
+pub async fn warmup(state: &State<AppState>) -> Flash<Redirect> {
+    let report = state.warm_up().await;
+    let to = Redirect::to("/admin/cache");
+    if report.failed > 0 {
+        Flash::error(to, format!("Warm-up: {report}"))
+    } else {
+        Flash::success(to, format!("Warm-up: {report}"))
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![cache, flush, warmup]
+}
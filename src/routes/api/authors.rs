@@ -0,0 +1,150 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use serde::{Deserialize, Serialize};
+
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+
+use crate::db::AppState;
+use crate::error::{AppError, AppResult, path_oid};
+use crate::models::Author;
+use crate::repo::AuthorFilter;
+use crate::routes::authors::delete_author_cascade;
+use crate::routes::forms::parse_date;
+
+// Representación JSON: ids como string hex en vez de {"$oid": ...}
+#[derive(Serialize)]
+pub struct AuthorDto {
+    pub id: String,
+    pub name: String,
+    pub date_of_birth: Option<NaiveDate>,
+    pub country: Option<String>,
+    pub description: Option<String>,
+    pub image_path: Option<String>,
+}
+
+impl From<Author> for AuthorDto {
+    fn from(a: Author) -> Self {
+        Self {
+            id: a.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: a.name,
+            date_of_birth: a.date_of_birth,
+            country: a.country,
+            description: a.description,
+            image_path: a.image_path,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthorInput {
+    pub name: String,
+    pub date_of_birth: Option<String>,
+    pub country: Option<String>,
+    pub description: Option<String>,
+}
+
+impl AuthorInput {
+    fn validate(&self) -> AppResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::validation("name must not be empty"));
+        }
+        if self.date_of_birth.as_deref().is_some_and(|d| parse_date(d).is_none()) {
+            return Err(AppError::validation("date_of_birth must be a date (YYYY-MM-DD)"));
+        }
+        Ok(())
+    }
+}
+
+async fn invalidate(state: &AppState, author_oid: &ObjectId) {
+    state.invalidate_tags(&[AppState::tag_author(author_oid), AppState::TAG_AUTHORS.into()]).await;
+}
+
+// GET /api/v1/authors
+#[get("/")]
+pub async fn list(state: &State<AppState>) -> AppResult<Json<Vec<AuthorDto>>> {
+    let authors = state.authors.list(&AuthorFilter::default()).await?;
+    Ok(Json(authors.into_iter().map(AuthorDto::from).collect()))
+}
+
+// GET /api/v1/authors/<id>
+#[get("/<id>")]
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Json<AuthorDto>> {
+    let oid = path_oid(id, "author")?;
+    match state.authors.get(&oid).await? {
+        Some(a) => Ok(Json(a.into())),
+        None => Err(AppError::not_found("author")),
+    }
+}
+
+// POST /api/v1/authors
+#[post("/", format = "json", data = "<input>")]
+pub async fn create(
+    state: &State<AppState>,
+    input: Json<AuthorInput>,
+) -> AppResult<status::Created<Json<AuthorDto>>> {
+    let input = input.into_inner();
+    input.validate()?;
+
+    let mut a = Author {
+        id: None,
+        name: input.name,
+        date_of_birth: input.date_of_birth.as_deref().and_then(parse_date),
+        country: input.country,
+        description: input.description,
+        image_path: None,
+    };
+    let oid = state.authors.insert(&a).await?;
+    a.id = Some(oid);
+    invalidate(state, &oid).await;
+
+    let dto = AuthorDto::from(a);
+    Ok(status::Created::new(format!("/api/v1/authors/{}", dto.id)).body(Json(dto)))
+}
+
+// PUT /api/v1/authors/<id>
+#[put("/<id>", format = "json", data = "<input>")]
+pub async fn update(
+    state: &State<AppState>,
+    id: &str,
+    input: Json<AuthorInput>,
+) -> AppResult<Json<AuthorDto>> {
+    let oid = path_oid(id, "author")?;
+    let input = input.into_inner();
+    input.validate()?;
+
+    let a = Author {
+        id: Some(oid),
+        name: input.name,
+        date_of_birth: input.date_of_birth.as_deref().and_then(parse_date),
+        country: input.country,
+        description: input.description,
+        image_path: None,
+    };
+    let updated = state.authors.update(&oid, &a).await?;
+
+    match updated {
+        Some(a) => {
+            invalidate(state, &oid).await;
+            Ok(Json(a.into()))
+        }
+        None => Err(AppError::not_found("author")),
+    }
+}
+
+// DELETE /api/v1/authors/<id>
+// Borra también sus libros, reseñas y ventas.
+#[delete("/<id>")]
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Status> {
+    let oid = path_oid(id, "author")?;
+    match delete_author_cascade(state, &oid).await? {
+        true => Ok(Status::NoContent),
+        false => Err(AppError::not_found("author")),
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list, read, create, update, delete]
+}
@@ -0,0 +1,170 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use serde::{Deserialize, Serialize};
+
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+
+use crate::db::AppState;
+use crate::error::{AppError, AppResult, field_oid, path_oid};
+use crate::models::Book;
+use crate::repo::BookFilter;
+use crate::routes::books::delete_book_cascade;
+use crate::routes::forms::parse_date;
+
+#[derive(Serialize)]
+pub struct BookDto {
+    pub id: String,
+    pub author_id: String,
+    pub title: String,
+    pub summary: Option<String>,
+    pub publication_date: Option<NaiveDate>,
+    pub total_sales: Option<i64>,
+    pub cover_image_path: Option<String>,
+}
+
+impl From<Book> for BookDto {
+    fn from(b: Book) -> Self {
+        Self {
+            id: b.id.map(|id| id.to_hex()).unwrap_or_default(),
+            author_id: b.author_id.to_hex(),
+            title: b.title,
+            summary: b.summary,
+            publication_date: b.publication_date,
+            total_sales: b.total_sales,
+            cover_image_path: b.cover_image_path,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BookInput {
+    pub title: String,
+    pub author_id: String,
+    pub summary: Option<String>,
+    pub publication_date: Option<String>,
+}
+
+impl BookInput {
+    // Devuelve el author_id ya validado (formato y existencia).
+    async fn validate(&self, state: &AppState) -> AppResult<ObjectId> {
+        if self.title.trim().is_empty() {
+            return Err(AppError::validation("title must not be empty"));
+        }
+        if self.publication_date.as_deref().is_some_and(|d| parse_date(d).is_none()) {
+            return Err(AppError::validation("publication_date must be a date (YYYY-MM-DD)"));
+        }
+        let author_oid = field_oid(&self.author_id, "author_id")?;
+        let exists = state.authors.get(&author_oid).await?.is_some();
+        if !exists {
+            return Err(AppError::validation("author_id does not reference an existing author"));
+        }
+        Ok(author_oid)
+    }
+}
+
+async fn invalidate(state: &AppState, book: &Book) {
+    let mut tags = vec![AppState::tag_author(&book.author_id), AppState::TAG_BOOKS.into()];
+    if let Some(id) = &book.id {
+        tags.push(AppState::tag_book(id));
+    }
+    state.invalidate_tags(&tags).await;
+}
+
+// GET /api/v1/books?author_id=
+#[get("/?<author_id>")]
+pub async fn list(state: &State<AppState>, author_id: Option<String>) -> AppResult<Json<Vec<BookDto>>> {
+    let filter = BookFilter {
+        author_id: author_id.as_deref().map(|aid| field_oid(aid, "author_id")).transpose()?,
+        ..Default::default()
+    };
+
+    let books = state.books.list(&filter).await?;
+    Ok(Json(books.into_iter().map(BookDto::from).collect()))
+}
+
+// GET /api/v1/books/<id>
+#[get("/<id>")]
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Json<BookDto>> {
+    let oid = path_oid(id, "book")?;
+    match state.books.get(&oid).await? {
+        Some(b) => Ok(Json(b.into())),
+        None => Err(AppError::not_found("book")),
+    }
+}
+
+// POST /api/v1/books
+#[post("/", format = "json", data = "<input>")]
+pub async fn create(
+    state: &State<AppState>,
+    input: Json<BookInput>,
+) -> AppResult<status::Created<Json<BookDto>>> {
+    let input = input.into_inner();
+    let author_oid = input.validate(state).await?;
+
+    let mut b = Book {
+        id: None,
+        author_id: author_oid,
+        title: input.title,
+        summary: input.summary,
+        publication_date: input.publication_date.as_deref().and_then(parse_date),
+        total_sales: None,
+        cover_image_path: None,
+    };
+    b.id = Some(state.books.insert(&b).await?);
+    state.search_index_book(&b).await;
+    invalidate(state, &b).await;
+
+    let dto = BookDto::from(b);
+    Ok(status::Created::new(format!("/api/v1/books/{}", dto.id)).body(Json(dto)))
+}
+
+// PUT /api/v1/books/<id>
+// total_sales no se toca: lo mantiene el recálculo de ventas.
+#[put("/<id>", format = "json", data = "<input>")]
+pub async fn update(
+    state: &State<AppState>,
+    id: &str,
+    input: Json<BookInput>,
+) -> AppResult<Json<BookDto>> {
+    let oid = path_oid(id, "book")?;
+    let input = input.into_inner();
+    let author_oid = input.validate(state).await?;
+
+    let b = Book {
+        id: Some(oid),
+        author_id: author_oid,
+        title: input.title,
+        summary: input.summary,
+        publication_date: input.publication_date.as_deref().and_then(parse_date),
+        total_sales: None,
+        cover_image_path: None,
+    };
+    let updated = state.books.update(&oid, &b).await?;
+
+    match updated {
+        Some(b) => {
+            state.search_index_book(&b).await;
+            invalidate(state, &b).await;
+            Ok(Json(b.into()))
+        }
+        None => Err(AppError::not_found("book")),
+    }
+}
+
+// DELETE /api/v1/books/<id>
+// Borra también sus reseñas y ventas.
+#[delete("/<id>")]
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Status> {
+    let oid = path_oid(id, "book")?;
+    match delete_book_cascade(state, &oid).await? {
+        true => Ok(Status::NoContent),
+        false => Err(AppError::not_found("book")),
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list, read, create, update, delete]
+}
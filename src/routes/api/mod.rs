@@ -0,0 +1,46 @@
+// API JSON versionada (/api/v1/...) que convive con la UI Tera.
+// Cada submódulo expone list/read/create/update/delete para un modelo;
+// `search` sirve el autocompletado de la home (/api/search).
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::{Json, Value};
+use rocket::{Catcher, Request};
+
+use crate::error::{problem, problem_content_type};
+
+pub mod authors;
+pub mod books;
+pub mod reviews;
+pub mod sales;
+pub mod search;
+
+/* ===== Catchers: problem documents también para errores de Rocket ===== */
+// Los handlers responden con crate::error::AppError, que ya sabe hacerlo.
+
+fn problem_response(status: Status, detail: &str) -> (ContentType, Json<Value>) {
+    (problem_content_type(), Json(problem(status, detail)))
+}
+
+#[catch(400)]
+fn bad_request(_req: &Request) -> (ContentType, Json<Value>) {
+    problem_response(Status::BadRequest, "bad request")
+}
+
+#[catch(404)]
+fn route_not_found(_req: &Request) -> (ContentType, Json<Value>) {
+    problem_response(Status::NotFound, "not found")
+}
+
+// Rocket responde 422 cuando el cuerpo JSON no encaja con el struct de entrada.
+#[catch(422)]
+fn unprocessable_entity(_req: &Request) -> (ContentType, Json<Value>) {
+    problem_response(Status::UnprocessableEntity, "invalid request body")
+}
+
+#[catch(500)]
+fn internal_error(_req: &Request) -> (ContentType, Json<Value>) {
+    problem_response(Status::InternalServerError, "internal error")
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![bad_request, route_not_found, unprocessable_entity, internal_error]
+}
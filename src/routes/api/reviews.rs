@@ -0,0 +1,163 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use serde::{Deserialize, Serialize};
+
+use mongodb::bson::oid::ObjectId;
+
+use crate::db::AppState;
+use crate::error::{AppError, AppResult, field_oid, path_oid};
+use crate::models::Review;
+use crate::repo::ReviewFilter;
+
+#[derive(Serialize)]
+pub struct ReviewDto {
+    pub id: String,
+    pub book_id: String,
+    pub text: String,
+    pub score: i32,
+    pub up_votes: i64,
+}
+
+impl From<Review> for ReviewDto {
+    fn from(r: Review) -> Self {
+        Self {
+            id: r.id.map(|id| id.to_hex()).unwrap_or_default(),
+            book_id: r.book_id.to_hex(),
+            text: r.text,
+            score: r.score,
+            up_votes: r.up_votes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReviewInput {
+    pub book_id: String,
+    pub text: String,
+    pub score: i32,
+    pub up_votes: Option<i64>,
+}
+
+impl ReviewInput {
+    // Como el formulario de la UI, rechaza valores fuera de rango (422).
+    async fn validate(&self, state: &AppState) -> AppResult<ObjectId> {
+        if self.text.trim().is_empty() {
+            return Err(AppError::validation("text must not be empty"));
+        }
+        if !(1..=5).contains(&self.score) {
+            return Err(AppError::validation("score must be between 1 and 5"));
+        }
+        if self.up_votes.unwrap_or(0) < 0 {
+            return Err(AppError::validation("up_votes must not be negative"));
+        }
+        let book_oid = field_oid(&self.book_id, "book_id")?;
+        let exists = state.books.get(&book_oid).await?.is_some();
+        if !exists {
+            return Err(AppError::validation("book_id does not reference an existing book"));
+        }
+        Ok(book_oid)
+    }
+}
+
+async fn invalidate(state: &AppState, book_oid: &ObjectId) {
+    state.invalidate_tags(&[AppState::tag_book(book_oid), AppState::TAG_REVIEWS.into()]).await;
+}
+
+// GET /api/v1/reviews?book_id=
+#[get("/?<book_id>")]
+pub async fn list(state: &State<AppState>, book_id: Option<String>) -> AppResult<Json<Vec<ReviewDto>>> {
+    let filter = ReviewFilter {
+        book_id: book_id.as_deref().map(|bid| field_oid(bid, "book_id")).transpose()?,
+        ..Default::default()
+    };
+
+    let reviews = state.reviews.list(&filter).await?;
+    Ok(Json(reviews.into_iter().map(ReviewDto::from).collect()))
+}
+
+// GET /api/v1/reviews/<id>
+#[get("/<id>")]
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Json<ReviewDto>> {
+    let oid = path_oid(id, "review")?;
+    match state.reviews.get(&oid).await? {
+        Some(r) => Ok(Json(r.into())),
+        None => Err(AppError::not_found("review")),
+    }
+}
+
+// POST /api/v1/reviews
+#[post("/", format = "json", data = "<input>")]
+pub async fn create(
+    state: &State<AppState>,
+    input: Json<ReviewInput>,
+) -> AppResult<status::Created<Json<ReviewDto>>> {
+    let input = input.into_inner();
+    let book_oid = input.validate(state).await?;
+
+    let mut r = Review {
+        id: None,
+        book_id: book_oid,
+        text: input.text,
+        score: input.score,
+        up_votes: input.up_votes.unwrap_or(0),
+    };
+    r.id = Some(state.reviews.insert(&r).await?);
+    state.search_index_review(&r).await;
+    invalidate(state, &book_oid).await;
+
+    let dto = ReviewDto::from(r);
+    Ok(status::Created::new(format!("/api/v1/reviews/{}", dto.id)).body(Json(dto)))
+}
+
+// PUT /api/v1/reviews/<id>
+#[put("/<id>", format = "json", data = "<input>")]
+pub async fn update(
+    state: &State<AppState>,
+    id: &str,
+    input: Json<ReviewInput>,
+) -> AppResult<Json<ReviewDto>> {
+    let oid = path_oid(id, "review")?;
+    let input = input.into_inner();
+    let book_oid = input.validate(state).await?;
+
+    let r = Review {
+        id: Some(oid),
+        book_id: book_oid,
+        text: input.text,
+        score: input.score,
+        up_votes: input.up_votes.unwrap_or(0),
+    };
+    // devolvemos el documento previo para invalidar también el libro anterior
+    let prev = state.reviews.update(&oid, &r).await?;
+
+    let Some(prev) = prev else { return Err(AppError::not_found("review")) };
+    if prev.book_id != book_oid {
+        invalidate(state, &prev.book_id).await;
+    }
+    invalidate(state, &book_oid).await;
+
+    state.search_index_review(&r).await;
+    Ok(Json(r.into()))
+}
+
+// DELETE /api/v1/reviews/<id>
+#[delete("/<id>")]
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Status> {
+    let oid = path_oid(id, "review")?;
+    let prev = state.reviews.delete(&oid).await?;
+
+    match prev {
+        Some(r) => {
+            state.search_delete_review(&oid).await;
+            invalidate(state, &r.book_id).await;
+            Ok(Status::NoContent)
+        }
+        None => Err(AppError::not_found("review")),
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list, read, create, update, delete]
+}
@@ -0,0 +1,152 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use serde::{Deserialize, Serialize};
+
+use mongodb::bson::oid::ObjectId;
+
+use crate::db::AppState;
+use crate::error::{AppError, AppResult, field_oid, path_oid};
+use crate::models::Sale;
+use crate::repo::SaleFilter;
+use crate::routes::sales::{recompute_book_total, SALE_YEARS};
+
+#[derive(Serialize)]
+pub struct SaleDto {
+    pub id: String,
+    pub book_id: String,
+    pub year: i32,
+    pub units: i64,
+}
+
+impl From<Sale> for SaleDto {
+    fn from(s: Sale) -> Self {
+        Self {
+            id: s.id.map(|id| id.to_hex()).unwrap_or_default(),
+            book_id: s.book_id.to_hex(),
+            year: s.year,
+            units: s.units,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SaleInput {
+    pub book_id: String,
+    pub year: i32,
+    pub units: i64,
+}
+
+impl SaleInput {
+    async fn validate(&self, state: &AppState) -> AppResult<ObjectId> {
+        if self.units < 0 {
+            return Err(AppError::validation("units must not be negative"));
+        }
+        if !SALE_YEARS.contains(&(self.year as isize)) {
+            return Err(AppError::validation(format!("year must be between {} and {}", SALE_YEARS.start(), SALE_YEARS.end())));
+        }
+        let book_oid = field_oid(&self.book_id, "book_id")?;
+        let exists = state.books.get(&book_oid).await?.is_some();
+        if !exists {
+            return Err(AppError::validation("book_id does not reference an existing book"));
+        }
+        Ok(book_oid)
+    }
+}
+
+// (book_id, year) es único: la UI fusiona, la API responde 409.
+async fn ensure_unique(state: &AppState, book_oid: &ObjectId, year: i32, except: Option<ObjectId>) -> AppResult<()> {
+    let taken = state.sales.find_by_key(book_oid, year, except.as_ref()).await?.is_some();
+    if taken {
+        return Err(AppError::conflict(format!("a sale for this book in {year} already exists")));
+    }
+    Ok(())
+}
+
+// GET /api/v1/sales?book_id=&year=
+#[get("/?<book_id>&<year>")]
+pub async fn list(
+    state: &State<AppState>,
+    book_id: Option<String>,
+    year: Option<i32>,
+) -> AppResult<Json<Vec<SaleDto>>> {
+    let filter = SaleFilter {
+        book_id: book_id.as_deref().map(|bid| field_oid(bid, "book_id")).transpose()?,
+        year,
+    };
+
+    let sales = state.sales.list(&filter).await?;
+    Ok(Json(sales.into_iter().map(SaleDto::from).collect()))
+}
+
+// GET /api/v1/sales/<id>
+#[get("/<id>")]
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Json<SaleDto>> {
+    let oid = path_oid(id, "sale")?;
+    match state.sales.get(&oid).await? {
+        Some(s) => Ok(Json(s.into())),
+        None => Err(AppError::not_found("sale")),
+    }
+}
+
+// POST /api/v1/sales
+#[post("/", format = "json", data = "<input>")]
+pub async fn create(
+    state: &State<AppState>,
+    input: Json<SaleInput>,
+) -> AppResult<status::Created<Json<SaleDto>>> {
+    let input = input.into_inner();
+    let book_oid = input.validate(state).await?;
+    ensure_unique(state, &book_oid, input.year, None).await?;
+
+    let mut s = Sale { id: None, book_id: book_oid, year: input.year, units: input.units };
+    s.id = Some(state.sales.insert(&s).await?);
+    recompute_book_total(state, &book_oid).await?;
+
+    let dto = SaleDto::from(s);
+    Ok(status::Created::new(format!("/api/v1/sales/{}", dto.id)).body(Json(dto)))
+}
+
+// PUT /api/v1/sales/<id>
+#[put("/<id>", format = "json", data = "<input>")]
+pub async fn update(
+    state: &State<AppState>,
+    id: &str,
+    input: Json<SaleInput>,
+) -> AppResult<Json<SaleDto>> {
+    let oid = path_oid(id, "sale")?;
+    let input = input.into_inner();
+    let book_oid = input.validate(state).await?;
+    ensure_unique(state, &book_oid, input.year, Some(oid)).await?;
+
+    let s = Sale { id: Some(oid), book_id: book_oid, year: input.year, units: input.units };
+    let prev = state.sales.update(&oid, &s).await?;
+
+    let Some(prev) = prev else { return Err(AppError::not_found("sale")) };
+    if prev.book_id != book_oid {
+        recompute_book_total(state, &prev.book_id).await?;
+    }
+    recompute_book_total(state, &book_oid).await?;
+
+    Ok(Json(s.into()))
+}
+
+// DELETE /api/v1/sales/<id>
+#[delete("/<id>")]
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Status> {
+    let oid = path_oid(id, "sale")?;
+    let prev = state.sales.delete(&oid).await?;
+
+    match prev {
+        Some(s) => {
+            recompute_book_total(state, &s.book_id).await?;
+            Ok(Status::NoContent)
+        }
+        None => Err(AppError::not_found("sale")),
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list, read, create, update, delete]
+}
@@ -0,0 +1,22 @@
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+
+use crate::db::AppState;
+use crate::error::AppResult;
+use crate::models::Suggestions;
+
+// Sugerencias por tipo si no se pide otra cosa
+const DEFAULT_LIMIT: i64 = 5;
+
+// GET /api/search/suggest?q=&limit=
+// Autocompletado: títulos y autores que empiezan por q (cacheado unos segundos).
+#[get("/suggest?<q>&<limit>")]
+pub async fn suggest(state: &State<AppState>, q: Option<&str>, limit: Option<i64>) -> AppResult<Json<Suggestions>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, AppState::SUGGEST_MAX_LIMIT);
+    let suggestions = state.suggest_cached(q.unwrap_or_default(), limit).await?;
+    Ok(Json(suggestions))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![suggest]
+}
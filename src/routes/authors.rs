@@ -1,37 +1,33 @@
 use rocket::{Route, State};
-use rocket::form::{Form, FromForm};           // Para manejar <form> (UI)
+use rocket::form::{Contextual, Form, FromForm}; // Para manejar <form> (UI)
 use rocket_dyn_templates::Template;            // Para renderizar Tera
 use rocket::response::Redirect;
 use std::collections::HashMap;
 use serde::Serialize;                          // Para serializar structs hacia la vista
+use serde_json::Map;
 
-use futures_util::TryStreamExt;                // Para iterar cursores async de Mongo
-use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Collection,                                // Tipo de colección tipada
-};
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
 
-use crate::db::AppState;                       // Estado global (contiene Database)
-//use crate::models::Author;                     // Modelo de dominio (serde + bson)
-
-// Helper: obtener un handle tipado a la colección "authors".
-// - No hace I/O aún; solo devuelve un objeto para luego invocar find/insert/update...
-// - El tipo genérico <Author> hace (de)serialización BSON<->Rust automática con serde.
-fn col(state: &State<AppState>) -> Collection<Author> {
-    state.db.collection::<Author>("authors")
-}
-
-use crate::models::{Author, Book, Review, Sale};   // importar todos los modelos
+use crate::db::AppState;                       // Estado global (contiene los repos)
+use crate::error::{path_oid, AppError, AppResult};
+use crate::models::Author;                     // Modelo de dominio (serde + bson)
+use crate::repo::AuthorFilter;
+use crate::routes::forms::{non_empty, not_blank, parse_date, past_date, Rejected, Submitted};
+use crate::search::query::list_pattern;
 
 /* ====== UI ===== */
 
 // Estructura del <form> de creación en la vista (solo campos que el usuario rellena).
 // FromForm habilita que Rocket parsee application/x-www-form-urlencoded.
+// Las reglas de cada campo van en #[field(validate)] (ver routes::forms).
 #[derive(FromForm)]
 pub struct AuthorForm {
+    #[field(validate = not_blank())]
     pub name: String,
     pub country: Option<String>,
     pub description: Option<String>,
+    #[field(validate = past_date())]
     pub date_of_birth: Option<String>,
 }
 
@@ -43,18 +39,18 @@ struct AuthorView {
     name: String,
     country: Option<String>,
     description: Option<String>,
-    date_of_birth: Option<String>,
+    date_of_birth: Option<NaiveDate>,
 }
 
 // Contexto que enviamos al template de índice.
 // - authors: la lista renderizable
 // - q: el query de búsqueda (para rellenar el input)
-// - message: mensajes flash/banners (si los usáramos)
+// - regex: si q se interpreta como regex (modo avanzado)
 #[derive(Serialize)]
 struct AuthorsCtx {
     authors: Vec<AuthorView>,
     q: Option<String>,
-    message: Option<String>,
+    regex: bool,
     editing: Option<AuthorView>,
 }
 
@@ -63,24 +59,21 @@ struct AuthorCtx {
     author: AuthorView,
 }
 
-// GET /authors?q=
+// GET /authors?q=&regex=
 // Renderiza la vista con listado + formulario de creación.
 // Notas:
 // - Convertimos Author -> AuthorView (id a hex para URLs).
 // - Template::render("authors/index", &ctx) busca templates/authors/index.html.tera
-#[get("/?<q>")]
-pub async fn index(state: &State<AppState>, q: Option<String>) -> Template {
-    let c = col(state);
-
-    let filter = if let Some(ref s) = q {
-        doc! { "name": { "$regex": s, "$options": "i" } }
-    } else {
-        doc! {}
-    };
+#[get("/?<q>&<regex>")]
+pub async fn index(state: &State<AppState>, q: Option<String>, regex: Option<bool>) -> AppResult<Template> {
+    let regex = regex.unwrap_or(false);
+
+    // q se escapa salvo en modo regex (y aun así se valida)
+    let filter = AuthorFilter { name: list_pattern(q.as_deref(), regex) };
 
-    let mut cur = c.find(filter).await.expect("find authors");
+    let found = state.authors.list(&filter).await?;
     let mut authors = Vec::<AuthorView>::new();
-    while let Some(a) = cur.try_next().await.expect("cursor") {
+    for a in found {
         if let Some(id) = a.id {
             authors.push(AuthorView {
                 id: id.to_hex(),          // ObjectId -> String amigable para URLs
@@ -92,123 +85,103 @@ pub async fn index(state: &State<AppState>, q: Option<String>) -> Template {
         }
     }
 
-    Template::render("authors/index", &AuthorsCtx { authors, q, message: None, editing: None })
+    Ok(Template::render("authors/index", &AuthorsCtx { authors, q, regex, editing: None }))
 }
 
 // POST /authors/create
 // Crea un autor desde el formulario de la vista y vuelve a renderizar el índice.
-// Notas:
-// - Aquí ignoramos el error de insert por simplicidad (en prod, manejarlo).
-// - Podríamos hacer Redirect::to("/authors/ui") si preferimos PRG pattern.
+// Si no valida, vuelve el formulario con los errores.
+// Nota: podríamos hacer Redirect::to("/authors") si preferimos PRG pattern.
 #[post("/create", data = "<form>")]
-pub async fn create(state: &State<AppState>, form: Form<AuthorForm>) -> Template {
-    let c = col(state);
-    let f = form.into_inner();
+pub async fn create(state: &State<AppState>, form: Form<Contextual<'_, AuthorForm>>) -> AppResult<Submitted<Template>> {
+    let form = form.into_inner();
+    let Some(f) = form.value else {
+        return Ok(Err(Rejected::new(&form.context).render("authors/create", "author", Map::new())));
+    };
 
     let a = Author {
         id: None,
-        name: f.name,
-        date_of_birth: f.date_of_birth,
-        country: f.country,
-        description: f.description,
+        name: f.name.trim().to_string(),
+        date_of_birth: f.date_of_birth.as_deref().and_then(parse_date),
+        country: f.country.and_then(non_empty),
+        description: f.description.and_then(non_empty),
         image_path: None,  // Default to None for new authors
     };
 
-    let _ = c.insert_one(&a).await;
+    let oid = state.authors.insert(&a).await?;
     // Invalidate caches affected by author creation
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-    state.cache_del_pref("search:books:").await;
+    state.invalidate_tags(&[AppState::TAG_AUTHORS.into(), AppState::tag_author(&oid)]).await;
     // Re-render directo del índice (simple y efectivo)
-    index(state, None).await
+    index(state, None, None).await.map(Ok)
 }
 
 // POST /authors/delete/<id>
-// Borra un autor desde la tabla de la vista y re-renderiza la lista.
-// Notas:
-// - Si el id no es válido o no existe, simplemente recargamos.
-// - Igual que arriba, podríamos usar Redirect::to("/authors/ui").
+// Borra un autor (con sus libros, reseñas y ventas) y vuelve al listado.
+// Si la cascada falla no se borra nada y se muestra la página de error.
 #[post("/delete/<id>")]
-pub async fn delete_author(state: &State<AppState>, id: &str) -> Template {
-    let db = &state.db;
-
-    if let Ok(author_id) = ObjectId::parse_str(id) {
-        let authors = db.collection::<Author>("authors");
-        let books = db.collection::<Book>("books");
-        let reviews = db.collection::<Review>("reviews");
-        let sales = db.collection::<Sale>("sales");
-
-        // buscar libros del autor
-        if let Ok(mut cursor) = books.find(doc! { "author_id": &author_id }).await {
-            while let Some(book) = cursor.try_next().await.unwrap_or(None) {
-                if let Some(book_id) = book.id {
-                    // borrar reseñas y ventas de cada libro
-                    let _ = reviews.delete_many(doc! { "book_id": &book_id }).await;
-                    let _ = sales.delete_many(doc! { "book_id": &book_id }).await;
-
-                    // Invalidate per-book cached average score
-                    let avg_key = format!("book:{}:avg_score", book_id.to_hex());
-                    state.cache_del_key(&avg_key).await;
-                }
-            }
-        }
-
-        // borrar libros del autor
-        let _ = books.delete_many(doc! { "author_id": &author_id }).await;
+pub async fn delete_author(state: &State<AppState>, id: &str) -> AppResult<Redirect> {
+    let author_id = path_oid(id, "author")?;
+    if !delete_author_cascade(state, &author_id).await? {
+        return Err(AppError::not_found("author"));
+    }
+    Ok(Redirect::to("/authors"))
+}
 
-        // borrar autor
-        let _ = authors.delete_one(doc! { "_id": &author_id }).await;
+// Borrado en cascada: reseñas y ventas de sus libros, los libros y el
+// autor; o todo o nada. Devuelve false si el autor no existía.
+// Compartido entre la UI y la API JSON.
+pub async fn delete_author_cascade(state: &AppState, author_id: &ObjectId) -> anyhow::Result<bool> {
+    let cascade = state.authors.delete_cascade(author_id).await?;
 
-        // Invalidate caches affected by author deletion
-        state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-        state.cache_del_pref("search:books:").await;
+    // ya confirmado: índice de búsqueda y cache
+    for id in &cascade.review_ids {
+        state.search_delete_review(id).await;
     }
-
-    index(state, None).await
+    // tags de lo borrado: el autor, sus libros y todas las colecciones
+    let mut tags = vec![
+        AppState::tag_author(author_id),
+        AppState::TAG_AUTHORS.into(),
+        AppState::TAG_BOOKS.into(),
+        AppState::TAG_REVIEWS.into(),
+        AppState::TAG_SALES.into(),
+    ];
+    for book_id in &cascade.book_ids {
+        state.search_delete_book(book_id).await;
+        tags.push(AppState::tag_book(book_id));
+    }
+    state.invalidate_tags(&tags).await;
+    Ok(cascade.deleted)
 }
 
 
 // GET /authors/edit/<id>
 // Carga el autor a editar y renderiza la vista dedicada de edición.
 #[get("/edit/<id>")]
-pub async fn edit(state: &State<AppState>, id: &str) -> Template {
-    let c = col(state);
-    if let Ok(oid) = ObjectId::parse_str(id) {
-        if let Ok(Some(a)) = c.find_one(doc! {"_id": oid}).await {
-            if let Some(oid) = a.id {
-                let view = AuthorView {
-                    id: oid.to_hex(),
-                    name: a.name,
-                    country: a.country,
-                    description: a.description,
-                    date_of_birth: a.date_of_birth,
-                };
-                return Template::render("authors/edit", &AuthorCtx { author: view });
-            }
-        }
-    }
-    // Si no se encuentra o hay error, volvemos al índice
-    index(state, None).await
+pub async fn edit(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let author = find_author(state, id).await?;
+    Ok(Template::render("authors/edit", &AuthorCtx { author }))
 }
 
 // POST /authors/update/<id>
 // Actualiza y redirige al listado (PRG pattern).
 #[post("/update/<id>", data = "<form>")]
-pub async fn update(state: &State<AppState>, id: &str, form: Form<AuthorForm>) -> Redirect {
-    let c = col(state);
-    if let Ok(oid) = ObjectId::parse_str(id) {
-        let f = form.into_inner();
-        let mut set_doc = doc! { "name": f.name };
-        if let Some(country) = f.country { set_doc.insert("country", country); }
-        if let Some(desc) = f.description { set_doc.insert("description", desc); }
-        if let Some(dob_str) = f.date_of_birth {
-            set_doc.insert("date_of_birth", dob_str);
-        }
-        let _ = c.find_one_and_update(doc! {"_id": oid}, doc! {"$set": set_doc}).await;
-        // Invalidate caches affected by author update
-        state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-        state.cache_del_pref("search:books:").await;
-    }
-    Redirect::to("/authors")
+pub async fn update(state: &State<AppState>, id: &str, form: Form<Contextual<'_, AuthorForm>>) -> AppResult<Submitted<Redirect>> {
+    let oid = path_oid(id, "author")?;
+    let form = form.into_inner();
+    let Some(f) = form.value else {
+        return Ok(Err(Rejected::new(&form.context).with_id(id).render("authors/edit", "author", Map::new())));
+    };
+
+    // los campos opcionales que no vienen en el form se conservan (vacíos se borran)
+    let mut a = state.authors.get(&oid).await?.ok_or_else(|| AppError::not_found("author"))?;
+    a.name = f.name.trim().to_string();
+    if let Some(country) = f.country { a.country = non_empty(country); }
+    if let Some(desc) = f.description { a.description = non_empty(desc); }
+    if let Some(dob) = f.date_of_birth { a.date_of_birth = parse_date(&dob); }
+    state.authors.update(&oid, &a).await?;
+    // Invalidate caches affected by author update
+    state.invalidate_tags(&[AppState::tag_author(&oid), AppState::TAG_AUTHORS.into()]).await;
+    Ok(Ok(Redirect::to("/authors")))
 }
 
 // GET /authors/create
@@ -222,24 +195,22 @@ pub async fn create_page() -> Template {
 // GET /authors/read/<id>
 // Renderiza la vista de solo lectura con los datos del autor.
 #[get("/read/<id>")]
-pub async fn read(state: &State<AppState>, id: &str) -> Template {
-    let c = col(state);
-    if let Ok(oid) = ObjectId::parse_str(id) {
-        if let Ok(Some(a)) = c.find_one(doc! {"_id": oid}).await {
-            if let Some(oid) = a.id {
-                let view = AuthorView {
-                    id: oid.to_hex(),
-                    name: a.name,
-                    country: a.country,
-                    description: a.description,
-                    date_of_birth: a.date_of_birth,
-                };
-                return Template::render("authors/read", &AuthorCtx { author: view });
-            }
-        }
-    }
-    // Si no se encuentra o hay error, volvemos al índice
-    index(state, None).await
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let author = find_author(state, id).await?;
+    Ok(Template::render("authors/read", &AuthorCtx { author }))
+}
+
+// El autor de la ruta ya como vista; 404 si el id no es válido o no existe
+async fn find_author(state: &AppState, id: &str) -> AppResult<AuthorView> {
+    let oid = path_oid(id, "author")?;
+    let a = state.authors.get(&oid).await?.ok_or_else(|| AppError::not_found("author"))?;
+    Ok(AuthorView {
+        id: oid.to_hex(),
+        name: a.name,
+        country: a.country,
+        description: a.description,
+        date_of_birth: a.date_of_birth,
+    })
 }
 
 // Registro de rutas SOLO UI para montar en main.rs
@@ -1,35 +1,30 @@
 use rocket::{Route, State};
-use rocket::form::{Form, FromForm};
+use rocket::form::{Contextual, Form, FromForm};
 use rocket_dyn_templates::Template;
 use rocket::response::Redirect;
 use serde::Serialize;
-use futures_util::TryStreamExt;
+use serde_json::{json, Map};
 use std::collections::HashMap;
 
-use crate::routes::reviews::reviews_col;          // helper público para obtener reviews
-use crate::routes::sales::sales_col;              // helper público para obtener sales
-
-use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Collection,
-};
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
 
 use crate::db::AppState;
-use crate::models::{Author, Book};
-
-pub fn books_col(state: &State<AppState>) -> Collection<Book> {
-    state.db.collection::<Book>("books")
-}
-pub fn authors_col(state: &State<AppState>) -> Collection<Author> {
-    state.db.collection::<Author>("authors")
-}
+use crate::error::{path_oid, AppError, AppResult};
+use crate::repo::{AuthorFilter, BookFilter};
+use crate::routes::forms::{iso_date, missing, non_empty, not_blank, object_id, parse_date, Invalid, Rejected, Submitted};
+use crate::search::query::list_pattern;
+use crate::models::Book;
 
 
 #[derive(FromForm)]
 pub struct BookForm {
+    #[field(validate = not_blank())]
     pub title: String,
+    #[field(validate = object_id())]
     pub author_id: String,               
     pub summary: Option<String>,
+    #[field(validate = iso_date())]
     pub publication_date: Option<String>,
     pub total_sales: Option<i64> 
 }
@@ -41,7 +36,7 @@ struct BookView {
     author_name: String,                 
     title: String,
     summary: Option<String>,
-    publication_date: Option<String>,
+    publication_date: Option<NaiveDate>,
     pub total_sales: Option<i64> 
 }
 
@@ -57,7 +52,7 @@ struct BooksCtx {
     books: Vec<BookView>,
     authors: Vec<AuthorOpt>,            // para el <select> en create/edit
     q: Option<String>,                  // búsqueda por título
-    message: Option<String>,
+    regex: bool,                        // q como regex (modo avanzado)
 }
 
 #[derive(Serialize)]
@@ -66,138 +61,129 @@ struct BookCtx {
     authors: Vec<AuthorOpt>,
 }
 
-/* ===== Handlers ===== */
+/* ===== Helpers ===== */
 
-// GET /books?q=
-#[get("/?<q>")]
-pub async fn index(state: &State<AppState>, q: Option<String>) -> Template {
-    let books_c = books_col(state);
-    let authors_c = authors_col(state);
+// Autores para el <select>
+async fn author_options(state: &AppState) -> AppResult<Vec<AuthorOpt>> {
+    let all_authors = state.authors.list(&AuthorFilter::default()).await?;
+    Ok(all_authors
+        .into_iter()
+        .filter_map(|a| Some(AuthorOpt { id: a.id?.to_hex(), name: a.name }))
+        .collect())
+}
 
-    // Traer autores para mapear nombres
-    let mut a_cur = authors_c.find(doc!{}).await.expect("authors find");
-    let mut authors = Vec::<AuthorOpt>::new();
-    let mut author_name_by_id = HashMap::<String, String>::new();
-    while let Some(a) = a_cur.try_next().await.expect("cursor authors") {
-        if let Some(id) = a.id {
-            let s = id.to_hex();
-            let name = a.name.clone();
-            author_name_by_id.insert(s.clone(), name.clone());
-            authors.push(AuthorOpt { id: s, name });
+// El form validado y con un autor que existe; si no, lo enviado con los errores
+async fn checked(state: &AppState, form: Contextual<'_, BookForm>) -> AppResult<Result<(BookForm, ObjectId), Rejected>> {
+    let Contextual { value, mut context } = form;
+    let Some(f) = value else { return Ok(Err(Rejected::new(&context))) };
+    let author_oid = ObjectId::parse_str(&f.author_id).ok();
+    match author_oid {
+        Some(oid) if state.authors.get(&oid).await?.is_some() => Ok(Ok((f, oid))),
+        _ => {
+            missing(&mut context, "author_id", "author");
+            Ok(Err(Rejected::new(&context)))
         }
     }
+}
+
+// El form otra vez (edit si hay id, si no create), con los autores del <select>
+async fn invalid(state: &AppState, rejected: Rejected, id: Option<&str>) -> AppResult<Invalid> {
+    let (template, rejected) = match id {
+        Some(id) => ("books/edit", rejected.with_id(id)),
+        None => ("books/create", rejected),
+    };
+    let mut ctx = Map::new();
+    ctx.insert("authors".into(), json!(author_options(state).await?));
+    Ok(rejected.render(template, "book", ctx))
+}
+
+async fn find_book(state: &AppState, id: &str) -> AppResult<Book> {
+    let oid = path_oid(id, "book")?;
+    state.books.get(&oid).await?.ok_or_else(|| AppError::not_found("book"))
+}
+
+/* ===== Handlers ===== */
+
+// GET /books?q=&regex=
+#[get("/?<q>&<regex>")]
+pub async fn index(state: &State<AppState>, q: Option<String>, regex: Option<bool>) -> AppResult<Template> {
+    // Traer autores para mapear nombres
+    let authors = author_options(state).await?;
+    let author_name_by_id: HashMap<&str, &str> = authors.iter().map(|a| (a.id.as_str(), a.name.as_str())).collect();
 
-    // filtro por búsqueda en título (regex simple; ya tienes índice text para luego)
-    let filter = if let Some(ref s) = q {
-        doc! { "title": { "$regex": s, "$options": "i" } }
-    } else { doc!{} };
+    // filtro por búsqueda en título (texto literal salvo en modo regex)
+    let regex = regex.unwrap_or(false);
+    let filter = BookFilter { title: list_pattern(q.as_deref(), regex), ..Default::default() };
 
-    let mut b_cur = books_c.find(filter).await.expect("books find");
+    let found = state.books.list(&filter).await?;
     let mut books = Vec::<BookView>::new();
-    loop {
-        match b_cur.try_next().await {
-            Ok(Some(b)) => {
-                if let Some(id) = b.id {
-                    let aid = b.author_id.to_hex();
-                    let author_name = author_name_by_id
-                        .get(&aid)
-                        .cloned()
-                        .unwrap_or_else(|| "(unknown)".into());
-                    books.push(BookView {
-                        id: id.to_hex(),
-                        author_id: aid,
-                        author_name,
-                        title: b.title,
-                        summary: b.summary,
-                        publication_date: b.publication_date,
-                        total_sales: b.total_sales,
-                    });
-                }
-            }
-            Ok(None) => break,
-            Err(e) => { eprintln!("books cursor: {e}"); break; }
+    for b in found {
+        if let Some(id) = b.id {
+            let aid = b.author_id.to_hex();
+            let author_name = author_name_by_id
+                .get(aid.as_str())
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "(unknown)".into());
+            books.push(BookView {
+                id: id.to_hex(),
+                author_id: aid,
+                author_name,
+                title: b.title,
+                summary: b.summary,
+                publication_date: b.publication_date,
+                total_sales: b.total_sales,
+            });
         }
     }
 
-    Template::render("books/index", &BooksCtx { books, authors, q, message: None })
+    Ok(Template::render("books/index", &BooksCtx { books, authors, q, regex }))
 }
 
 // GET /books/create
 #[get("/create")]
-pub async fn create_page(state: &State<AppState>) -> Template {
-    let authors_c = authors_col(state);
-    let mut a_cur = authors_c.find(doc!{}).await.expect("authors find");
-    let mut authors = Vec::<AuthorOpt>::new();
-    while let Some(a) = a_cur.try_next().await.expect("cursor authors") {
-        if let Some(id) = a.id {
-            authors.push(AuthorOpt { id: id.to_hex(), name: a.name });
-        }
-    }
-
-    Template::render("books/create", &serde_json::json!({ "authors": authors }))
+pub async fn create_page(state: &State<AppState>) -> AppResult<Template> {
+    let authors = author_options(state).await?;
+    Ok(Template::render("books/create", serde_json::json!({ "authors": authors })))
 }
 
 // POST /books/create
 #[post("/create", data = "<form>")]
-pub async fn create(state: &State<AppState>, form: Form<BookForm>) -> Redirect {
-    let books_c = books_col(state);
-    let authors_c = authors_col(state);
-    let f = form.into_inner();
-
-    // validar ObjectId y existencia del autor
-    let author_oid = match ObjectId::parse_str(&f.author_id) {
-        Ok(oid) => oid,
-        Err(_) => return Redirect::to("/books"),
+pub async fn create(state: &State<AppState>, form: Form<Contextual<'_, BookForm>>) -> AppResult<Submitted<Redirect>> {
+    // validar campos, ObjectId y existencia del autor
+    let (f, author_oid) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, None).await?)),
     };
-    let exists = authors_c.find_one(doc!{"_id": &author_oid}).await.ok().flatten().is_some();
-    if !exists {
-        return Redirect::to("/books");
-    }
 
-    let b = Book {
+    let mut b = Book {
         id: None,
         author_id: author_oid,
-        title: f.title,
-        summary: f.summary,
-        publication_date: f.publication_date,
+        title: f.title.trim().to_string(),
+        summary: f.summary.and_then(non_empty),
+        publication_date: f.publication_date.as_deref().and_then(parse_date),
         total_sales: None,
         cover_image_path: None,  // Default to None for new books
     };
-    let _ = books_c.insert_one(&b).await;
-    // invalidate caches: authors summary and search results
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-    state.cache_del_pref("search:books:").await;
-    Redirect::to("/books")
+    b.id = Some(state.books.insert(&b).await?);
+    state.search_index_book(&b).await;
+    // invalidate caches: the author's values and everything listing books
+    state.invalidate_tags(&[AppState::tag_author(&author_oid), AppState::TAG_BOOKS.into()]).await;
+    Ok(Ok(Redirect::to("/books")))
 }
 
 // GET /books/edit/<id>
 #[get("/edit/<id>")]
-pub async fn edit(state: &State<AppState>, id: &str) -> Template {
-    let books_c = books_col(state);
-    let authors_c = authors_col(state);
-
+pub async fn edit(state: &State<AppState>, id: &str) -> AppResult<Template> {
     // cargar libro
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_) => return index(state, None).await };
-    let book = match books_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_) => None };
-    if book.is_none() { return index(state, None).await; }
-    let book = book.unwrap();
+    let book = find_book(state, id).await?;
 
     // cargar autores
-    let mut a_cur = authors_c.find(doc!{}).await.expect("authors find");
-    let mut authors = Vec::<AuthorOpt>::new();
-    let mut author_name_by_id = HashMap::<String, String>::new();
-    while let Some(a) = a_cur.try_next().await.expect("cursor authors") {
-        if let Some(aid) = a.id {
-            let s = aid.to_hex();
-            author_name_by_id.insert(s.clone(), a.name.clone());
-            authors.push(AuthorOpt { id: s, name: a.name });
-        }
-    }
+    let authors = author_options(state).await?;
 
     // armar BookView
-    let id_s = book.id.unwrap().to_hex();
+    let id_s = book.id.map(|id| id.to_hex()).unwrap_or_default();
     let aid_s = book.author_id.to_hex();
-    let author_name = author_name_by_id.get(&aid_s).cloned().unwrap_or_else(|| "(unknown)".into());
+    let author_name = authors.iter().find(|a| a.id == aid_s).map(|a| a.name.clone()).unwrap_or_else(|| "(unknown)".into());
 
     let view = BookView {
         id: id_s,
@@ -209,82 +195,76 @@ pub async fn edit(state: &State<AppState>, id: &str) -> Template {
         total_sales: book.total_sales,
     };
 
-    Template::render("books/edit", &BookCtx { book: view, authors })
+    Ok(Template::render("books/edit", &BookCtx { book: view, authors }))
 }
 
 // POST /books/update/<id>
 #[post("/update/<id>", data = "<form>")]
-pub async fn update(state: &State<AppState>, id: &str, form: Form<BookForm>) -> Redirect {
-    let books_c = books_col(state);
-    let authors_c = authors_col(state);
-    let f = form.into_inner();
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_) => return Redirect::to("/books") };
-    let author_oid = match ObjectId::parse_str(&f.author_id) { Ok(x)=>x, Err(_) => return Redirect::to("/books") };
+pub async fn update(state: &State<AppState>, id: &str, form: Form<Contextual<'_, BookForm>>) -> AppResult<Submitted<Redirect>> {
+    let oid = path_oid(id, "book")?;
 
-    // validar existencia del autor
-    let exists = authors_c.find_one(doc!{"_id": &author_oid}).await.ok().flatten().is_some();
-    if !exists { return Redirect::to("/books"); }
-
-    let mut set_doc = doc! {
-        "title": f.title,
-        "author_id": author_oid
+    // validar campos, ObjectId y existencia del autor
+    let (f, author_oid) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, Some(id)).await?)),
     };
-    if let Some(s) = f.summary { set_doc.insert("summary", s); }
-    if let Some(p) = f.publication_date { set_doc.insert("publication_date", p); }
-
-    let _ = books_c.find_one_and_update(doc!{"_id": oid}, doc!{"$set": set_doc}).await;
-    // invalidate caches: authors summary and search results
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-    state.cache_del_pref("search:books:").await;
-    Redirect::to("/books")
+
+    // resumen y fecha que no vienen en el form se conservan (vacíos se borran)
+    let mut b = state.books.get(&oid).await?.ok_or_else(|| AppError::not_found("book"))?;
+    b.title = f.title.trim().to_string();
+    b.author_id = author_oid;
+    if let Some(s) = f.summary { b.summary = non_empty(s); }
+    if let Some(p) = f.publication_date { b.publication_date = parse_date(&p); }
+
+    let b = state.books.update(&oid, &b).await?.ok_or_else(|| AppError::not_found("book"))?;
+    state.search_index_book(&b).await;
+    // invalidate caches: this book (and its author) and everything listing books
+    state.invalidate_tags(&[AppState::tag_book(&oid), AppState::TAG_BOOKS.into(), AppState::tag_author(&b.author_id)]).await;
+    Ok(Ok(Redirect::to("/books")))
 }
 
 // POST /books/delete/<id>
-// #[post("/delete/<id>")]
+// Si la cascada falla no se borra nada y se muestra la página de error.
 #[post("/delete/<id>")]
-pub async fn delete(state: &State<AppState>, id: &str) -> Redirect {
-    let books_c = books_col(state);
-    let reviews_c = reviews_col(state);
-    let sales_c = sales_col(state);
-
-    if let Ok(book_id) = ObjectId::parse_str(id) {
-        // eliminar reseñas asociadas
-        let _ = reviews_c.delete_many(doc! {"book_id": &book_id}).await;
-
-        // eliminar ventas asociadas
-        let _ = sales_c.delete_many(doc! {"book_id": &book_id}).await;
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Redirect> {
+    let book_id = path_oid(id, "book")?;
+    if !delete_book_cascade(state, &book_id).await? {
+        return Err(AppError::not_found("book"));
+    }
+    Ok(Redirect::to("/books"))
+}
 
-        // eliminar el libro
-        let _ = books_c.delete_one(doc! {"_id": &book_id}).await;
+// Borra el libro junto con sus reseñas y ventas, o nada (UI y API JSON).
+// Devuelve false si el libro no existía.
+pub async fn delete_book_cascade(state: &AppState, book_id: &ObjectId) -> anyhow::Result<bool> {
+    let cascade = state.books.delete_cascade(book_id).await?;
 
-        // invalidate caches related to this book
-        state.cache_del_key(&AppState::key_book_avg(&book_id.to_hex())).await;
-        state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-        state.cache_del_pref("search:books:").await;
+    // ya confirmado: índice de búsqueda y cache
+    for id in &cascade.review_ids {
+        state.search_delete_review(id).await;
     }
-
-    Redirect::to("/books")
+    state.search_delete_book(book_id).await;
+    state.invalidate_tags(&[
+        AppState::tag_book(book_id),
+        AppState::TAG_BOOKS.into(),
+        AppState::TAG_REVIEWS.into(),
+        AppState::TAG_SALES.into(),
+    ]).await;
+    Ok(cascade.deleted)
 }
 
 // GET /books/read/<id>
 #[get("/read/<id>")]
-pub async fn read(state: &State<AppState>, id: &str) -> Template {
-    let books_c = books_col(state);
-    let authors_c = authors_col(state);
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_) => return index(state, None).await };
-    let book = match books_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_) => None };
-    if book.is_none() { return index(state, None).await; }
-    let b = book.unwrap();
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let b = find_book(state, id).await?;
 
     // nombre del autor
     let aid_s = b.author_id.to_hex();
-    let author_name = authors_c
-        .find_one(doc!{"_id": &b.author_id}).await.ok().flatten().map(|a| a.name).unwrap_or_else(|| "(unknown)".into());
+    let author_name = state.authors
+        .get(&b.author_id).await?.map(|a| a.name).unwrap_or_else(|| "(unknown)".into());
 
     let view = BookView {
-        id: b.id.unwrap().to_hex(),
+        id: b.id.map(|id| id.to_hex()).unwrap_or_default(),
         author_id: aid_s,
         author_name,
         title: b.title,
@@ -293,7 +273,7 @@ pub async fn read(state: &State<AppState>, id: &str) -> Template {
         total_sales: b.total_sales,
     };
 
-    Template::render("books/read", &BookCtx { book: view, authors: vec![] })
+    Ok(Template::render("books/read", &BookCtx { book: view, authors: vec![] }))
 }
 
 pub fn routes() -> Vec<Route> {
@@ -0,0 +1,110 @@
+// Validación de los formularios de la UI. Las reglas se declaran en cada
+// *Form con #[field(validate = ...)]; los handlers reciben Form<Contextual<..>>
+// y si algo no valida vuelven a pintar el formulario (422) con lo que escribió
+// el usuario y un mensaje por campo (`errors.<campo>` en los parciales _form).
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+use rocket::form::{self, Context};
+use rocket_dyn_templates::Template;
+use serde_json::{Map, Value};
+
+/* ===== Validadores ===== */
+
+pub fn not_blank<'v>(value: &str) -> form::Result<'v, ()> {
+    if value.trim().is_empty() {
+        Err(form::Error::validation("must not be empty"))?;
+    }
+    Ok(())
+}
+
+// Los <select> de libro/autor
+pub fn object_id<'v>(value: &str) -> form::Result<'v, ()> {
+    if ObjectId::parse_str(value).is_err() {
+        Err(form::Error::validation("is not a valid id"))?;
+    }
+    Ok(())
+}
+
+// "YYYY-MM-DD" (lo que manda <input type="date">); vacío es "sin fecha"
+pub fn iso_date<'v>(value: &Option<String>) -> form::Result<'v, ()> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(()),
+        Some(s) if parse_date(s).is_some() => Ok(()),
+        Some(_) => Err(form::Error::validation("must be a date (YYYY-MM-DD)"))?,
+    }
+}
+
+// Como iso_date, y además no puede ser posterior a hoy
+pub fn past_date<'v>(value: &Option<String>) -> form::Result<'v, ()> {
+    iso_date(value)?;
+    let today = chrono::Local::now().date_naive();
+    if value.as_deref().and_then(parse_date).is_some_and(|d| d > today) {
+        Err(form::Error::validation("must not be in the future"))?;
+    }
+    Ok(())
+}
+
+// También la usa la API JSON para las mismas fechas
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()
+}
+
+// Un campo opcional que llega vacío se guarda como ausente
+pub fn non_empty(value: String) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/* ===== Re-render ===== */
+
+// El formulario otra vez, con los errores
+#[derive(Responder)]
+#[response(status = 422)]
+pub struct Invalid(Template);
+
+// Respuesta de un POST de formulario: R si fue bien, el form (422) si no
+pub type Submitted<R> = Result<R, Invalid>;
+
+// Lo que escribió el usuario y el primer error de cada campo
+pub struct Rejected {
+    values: Map<String, Value>,
+    errors: HashMap<String, String>,
+}
+
+impl Rejected {
+    pub fn new(context: &Context<'_>) -> Self {
+        let mut values = Map::new();
+        for name in context.fields() {
+            let value = context.field_value(name).unwrap_or_default();
+            values.insert(name.to_string(), Value::String(value.to_string()));
+        }
+        let mut errors = HashMap::new();
+        for e in context.errors() {
+            if let Some(name) = &e.name {
+                errors.entry(name.to_string()).or_insert_with(|| e.kind.to_string());
+            }
+        }
+        Self { values, errors }
+    }
+
+    // El form de edición necesita el id para el action
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.values.insert("id".into(), Value::String(id.to_string()));
+        self
+    }
+
+    // Pinta `template` con lo enviado bajo `entity` (el nombre con el que lo
+    // lee el parcial: author, book...), `errors` y el resto de `ctx`
+    pub fn render(self, template: &'static str, entity: &str, mut ctx: Map<String, Value>) -> Invalid {
+        ctx.insert(entity.to_string(), Value::Object(self.values));
+        ctx.insert("errors".into(), serde_json::json!(self.errors));
+        Invalid(Template::render(template, Value::Object(ctx)))
+    }
+}
+
+// Un <select> (libro, autor) que apunta a algo que ya no existe
+pub fn missing(context: &mut Context<'_>, field: &'static str, what: &str) {
+    context.push_error(form::Error::validation(format!("no such {what}")).with_name(field));
+}
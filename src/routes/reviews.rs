@@ -1,36 +1,86 @@
 use rocket::{Route, State};
-use rocket::form::{Form, FromForm};
+use rocket::form::{Contextual, Form, FromForm};
 use rocket::response::Redirect;
 use rocket_dyn_templates::Template;
 use serde::Serialize;
-use futures_util::TryStreamExt;
+use serde_json::{json, Map};
 use std::collections::HashMap;
 
-use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Collection,
-};
+use mongodb::bson::oid::ObjectId;
 
 use crate::db::AppState;
-use crate::models::{Book, Review};
+use crate::error::{path_oid, AppError, AppResult};
+use crate::models::Review;
+use crate::repo::{BookFilter, ReviewFilter};
+use crate::routes::forms::{missing, not_blank, object_id, Invalid, Rejected, Submitted};
+use crate::search::query::list_pattern;
+
+/* ===== Helpers ===== */
+
+// Opciones del <select> de libros
+async fn book_options(state: &AppState) -> AppResult<Vec<BookOpt>> {
+    let books = state.books.list(&BookFilter::default()).await?;
+    Ok(books
+        .into_iter()
+        .filter_map(|b| Some(BookOpt { id: b.id?.to_hex(), title: b.title }))
+        .collect())
+}
 
-/* ========= Helpers de colecciones ========= */
+async fn book_title(state: &AppState, book_id: &ObjectId) -> AppResult<String> {
+    Ok(state.books.get(book_id).await?.map(|b| b.title).unwrap_or_else(|| "(unknown)".into()))
+}
 
-pub fn reviews_col(state: &State<AppState>) -> Collection<Review> {
-    state.db.collection::<Review>("reviews")
+// El form validado y con un libro que existe; si no, lo enviado con los errores
+async fn checked(state: &AppState, form: Contextual<'_, ReviewForm>) -> AppResult<Result<(ReviewForm, ObjectId), Rejected>> {
+    let Contextual { value, mut context } = form;
+    let Some(f) = value else { return Ok(Err(Rejected::new(&context))) };
+    let book_oid = ObjectId::parse_str(&f.book_id).ok();
+    match book_oid {
+        Some(oid) if state.books.get(&oid).await?.is_some() => Ok(Ok((f, oid))),
+        _ => {
+            missing(&mut context, "book_id", "book");
+            Ok(Err(Rejected::new(&context)))
+        }
+    }
 }
-pub fn books_col(state: &State<AppState>) -> Collection<Book> {
-    state.db.collection::<Book>("books")
+
+// El form otra vez (edit si hay id, si no create), con los libros del <select>
+async fn invalid(state: &AppState, rejected: Rejected, id: Option<&str>) -> AppResult<Invalid> {
+    let (template, rejected) = match id {
+        Some(id) => ("reviews/edit", rejected.with_id(id)),
+        None => ("reviews/create", rejected),
+    };
+    let mut ctx = Map::new();
+    ctx.insert("books".into(), json!(book_options(state).await?));
+    Ok(rejected.render(template, "review", ctx))
+}
+
+// La reseña de la ruta ya como vista; 404 si el id no es válido o no existe
+async fn find_review(state: &AppState, id: &str) -> AppResult<ReviewView> {
+    let oid = path_oid(id, "review")?;
+    let r = state.reviews.get(&oid).await?.ok_or_else(|| AppError::not_found("review"))?;
+    Ok(ReviewView {
+        id: oid.to_hex(),
+        book_id: r.book_id.to_hex(),
+        book_title: book_title(state, &r.book_id).await?,
+        text: r.text,
+        score: r.score,
+        up_votes: r.up_votes,
+    })
 }
 
 /* ===== Formularios y vistas ===== */
 
 #[derive(FromForm)]
 pub struct ReviewForm {
+    #[field(validate = object_id())]
     pub book_id: String,        // viene del <select>
+    #[field(validate = not_blank())]
     pub text: String,
-    pub score: i32,             // 1..5
-    pub up_votes: Option<i64>,  // default 0
+    #[field(validate = range(1..=5))]
+    pub score: i32,
+    #[field(default = 0, validate = range(0..))]
+    pub up_votes: i64,
 }
 
 #[derive(Serialize)]
@@ -54,6 +104,7 @@ struct ReviewsCtx {
     reviews: Vec<ReviewView>,
     books: Vec<BookOpt>,        // para el <select> en create/edit
     q: Option<String>,          // búsqueda por texto
+    regex: bool,                // q como regex (modo avanzado)
     message: Option<String>,
 }
 
@@ -63,225 +114,119 @@ struct ReviewCtx {
     books: Vec<BookOpt>,
 }
 
-// GET /reviews?q=
-#[get("/?<q>")]
-pub async fn index(state: &State<AppState>, q: Option<String>) -> Template {
-    let r_c = reviews_col(state);
-    let b_c = books_col(state);
-
+// GET /reviews?q=&regex=
+#[get("/?<q>&<regex>")]
+pub async fn index(state: &State<AppState>, q: Option<String>, regex: Option<bool>) -> AppResult<Template> {
     // Map de book_id -> title
-    let mut b_cur = b_c.find(doc!{}).await.expect("books find");
-    let mut books = Vec::<BookOpt>::new();
-    let mut book_title_by_id = HashMap::<String, String>::new();
-    while let Ok(Some(b)) = b_cur.try_next().await {
-        if let Some(id) = b.id {
-            let s = id.to_hex();
-            let title = b.title.clone();
-            book_title_by_id.insert(s.clone(), title.clone());
-            books.push(BookOpt { id: s, title });
-        }
-    }
+    let books = book_options(state).await?;
+    let book_title_by_id: HashMap<&str, &str> = books.iter().map(|b| (b.id.as_str(), b.title.as_str())).collect();
 
-    let filter = if let Some(ref s) = q {
-        doc! { "text": { "$regex": s, "$options": "i" } }
-    } else { doc!{} };
+    let regex = regex.unwrap_or(false);
+    let filter = ReviewFilter { text: list_pattern(q.as_deref(), regex), ..Default::default() };
 
     let mut rv = Vec::<ReviewView>::new();
-    let mut cur = r_c.find(filter).await.expect("reviews find");
-    loop {
-        match cur.try_next().await {
-            Ok(Some(r)) => {
-                if let Some(id) = r.id {
-                    let bid = r.book_id.to_hex();
-                    let book_title = book_title_by_id.get(&bid).cloned().unwrap_or_else(|| "(unknown)".into());
-                    rv.push(ReviewView {
-                        id: id.to_hex(),
-                        book_id: bid,
-                        book_title,
-                        text: r.text,
-                        score: r.score,
-                        up_votes: r.up_votes,
-                    });
-                }
-            }
-            Ok(None) => break,
-            Err(e) => { eprintln!("reviews cursor: {e}"); break; }
+    let found = state.reviews.list(&filter).await?;
+    for r in found {
+        if let Some(id) = r.id {
+            let bid = r.book_id.to_hex();
+            let book_title = book_title_by_id.get(bid.as_str()).map(|t| t.to_string()).unwrap_or_else(|| "(unknown)".into());
+            rv.push(ReviewView {
+                id: id.to_hex(),
+                book_id: bid,
+                book_title,
+                text: r.text,
+                score: r.score,
+                up_votes: r.up_votes,
+            });
         }
     }
 
-    Template::render("reviews/index", &ReviewsCtx { reviews: rv, books, q, message: None })
+    Ok(Template::render("reviews/index", &ReviewsCtx { reviews: rv, books, q, regex, message: None }))
 }
 
 // GET /reviews/create
 #[get("/create")]
-pub async fn create_page(state: &State<AppState>) -> Template {
-    let b_c = books_col(state);
-    let mut books = Vec::<BookOpt>::new();
-    let mut cur = b_c.find(doc!{}).await.expect("books find");
-    while let Ok(Some(b)) = cur.try_next().await {
-        if let Some(id) = b.id {
-            books.push(BookOpt { id: id.to_hex(), title: b.title });
-        }
-    }
-    Template::render("reviews/create", &serde_json::json!({ "books": books }))
+pub async fn create_page(state: &State<AppState>) -> AppResult<Template> {
+    let books = book_options(state).await?;
+    Ok(Template::render("reviews/create", serde_json::json!({ "books": books })))
 }
 
 // POST /reviews/create
 #[post("/create", data = "<form>")]
-pub async fn create(state: &State<AppState>, form: Form<ReviewForm>) -> Redirect {
-    let r_c = reviews_col(state);
-    let b_c = books_col(state);
-    let f = form.into_inner();
-
-    // validar book_id y existencia
-    let book_oid = match ObjectId::parse_str(&f.book_id) { Ok(x)=>x, Err(_)=> return Redirect::to("/reviews") };
-    let exists = b_c.find_one(doc!{"_id": &book_oid}).await.ok().flatten().is_some();
-    if !exists { return Redirect::to("/reviews"); }
-
-    // validar score
-    let score = f.score.clamp(1, 5);
-    let up_votes = f.up_votes.unwrap_or(0).max(0);
+pub async fn create(state: &State<AppState>, form: Form<Contextual<'_, ReviewForm>>) -> AppResult<Submitted<Redirect>> {
+    // validar campos, book_id y existencia
+    let (f, book_oid) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, None).await?)),
+    };
 
-    let r = Review {
+    let mut r = Review {
         id: None,
         book_id: book_oid,
-        text: f.text,
-        score,
-        up_votes,
+        text: f.text.trim().to_string(),
+        score: f.score,
+        up_votes: f.up_votes,
     };
-    let _ = r_c.insert_one(&r).await;
+    r.id = Some(state.reviews.insert(&r).await?);
+    state.search_index_review(&r).await;
 
     // invalidate caches affected by this review
-    state.cache_del_key(&AppState::key_book_avg(&book_oid.to_hex())).await;
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
+    state.invalidate_tags(&[AppState::tag_book(&book_oid), AppState::TAG_REVIEWS.into()]).await;
 
-    state.cache_del_pref("search:books:").await;
-
-    Redirect::to("/reviews")
+    Ok(Ok(Redirect::to("/reviews")))
 }
 
 // GET /reviews/edit/<id>
 #[get("/edit/<id>")]
-pub async fn edit(state: &State<AppState>, id: &str) -> Template {
-    let r_c = reviews_col(state);
-    let b_c = books_col(state);
-
+pub async fn edit(state: &State<AppState>, id: &str) -> AppResult<Template> {
     // cargar review
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return index(state, None).await };
-    let rev = match r_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_)=> None };
-    if rev.is_none() { return index(state, None).await; }
-    let r = rev.unwrap();
+    let rv = find_review(state, id).await?;
 
     // cargar libros para select
-    let mut books = Vec::<BookOpt>::new();
-    let mut cur = b_c.find(doc!{}).await.expect("books find");
-    while let Ok(Some(b)) = cur.try_next().await {
-        if let Some(id) = b.id {
-            books.push(BookOpt { id: id.to_hex(), title: b.title });
-        }
-    }
+    let books = book_options(state).await?;
 
-    // armar view
-    let rv = ReviewView {
-        id: r.id.unwrap().to_hex(),
-        book_id: r.book_id.to_hex(),
-        book_title: b_c
-            .find_one(doc!{"_id": &r.book_id}).await.ok().flatten().map(|b| b.title).unwrap_or_else(|| "(unknown)".into()),
-        text: r.text,
-        score: r.score,
-        up_votes: r.up_votes,
-    };
-
-    Template::render("reviews/edit", &ReviewCtx { review: rv, books })
+    Ok(Template::render("reviews/edit", &ReviewCtx { review: rv, books }))
 }
 
 // POST /reviews/update/<id>
 #[post("/update/<id>", data = "<form>")]
-pub async fn update(state: &State<AppState>, id: &str, form: Form<ReviewForm>) -> Redirect {
-    let r_c = reviews_col(state);
-    let b_c = books_col(state);
-    let f = form.into_inner();
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return Redirect::to("/reviews") };
-    let book_oid = match ObjectId::parse_str(&f.book_id) { Ok(x)=>x, Err(_)=> return Redirect::to("/reviews") };
-
-    // validar libro
-    let exists = b_c.find_one(doc!{"_id": &book_oid}).await.ok().flatten().is_some();
-    if !exists { return Redirect::to("/reviews"); }
-
-    let score = f.score.clamp(1, 5);
-    let up_votes = f.up_votes.unwrap_or(0).max(0);
-
-    // load previous review to know previous book_id for cache invalidation
-    let prev_review = r_c.find_one(doc!{"_id": oid}).await.ok().flatten();
-
-    let _ = r_c
-        .find_one_and_update(
-            doc!{"_id": oid},
-            doc!{"$set": {
-                "book_id": book_oid,
-                "text": f.text,
-                "score": score,
-                "up_votes": up_votes
-            }}
-        )
-        .await;
-
-    // invalidate caches: previous and new book, plus authors summary
-    if let Some(prev) = prev_review {
-        state.cache_del_key(&AppState::key_book_avg(&prev.book_id.to_hex())).await;
-    }
-    state.cache_del_key(&AppState::key_book_avg(&book_oid.to_hex())).await;
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
+pub async fn update(state: &State<AppState>, id: &str, form: Form<Contextual<'_, ReviewForm>>) -> AppResult<Submitted<Redirect>> {
+    let oid = path_oid(id, "review")?;
+    // validar campos y libro
+    let (f, book_oid) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, Some(id)).await?)),
+    };
+
+    // update devuelve la reseña previa: su book_id también se invalida
+    let r = Review { id: Some(oid), book_id: book_oid, text: f.text.trim().to_string(), score: f.score, up_votes: f.up_votes };
+    let prev = state.reviews.update(&oid, &r).await?.ok_or_else(|| AppError::not_found("review"))?;
+    state.search_index_review(&r).await;
 
-    state.cache_del_pref("search:books:").await;
+    // invalidate caches: previous and new book, plus everything aggregating reviews
+    state.invalidate_tags(&[AppState::tag_book(&book_oid), AppState::TAG_REVIEWS.into(), AppState::tag_book(&prev.book_id)]).await;
 
-    Redirect::to("/reviews")
+    Ok(Ok(Redirect::to("/reviews")))
 }
 
 // POST /reviews/delete/<id>
 #[post("/delete/<id>")]
-pub async fn delete(state: &State<AppState>, id: &str) -> Redirect {
-    let r_c = reviews_col(state);
-    if let Ok(oid) = ObjectId::parse_str(id) {
-        // read review to get affected book_id before deleting
-        let prev = r_c.find_one(doc!{"_id": oid}).await.ok().flatten();
-        let _ = r_c.delete_one(doc!{"_id": oid}).await;
-
-        // invalidate caches
-        if let Some(r) = prev {
-            state.cache_del_key(&AppState::key_book_avg(&r.book_id.to_hex())).await;
-        }
-        state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-        state.cache_del_pref("search:books:").await;
-    }
-    Redirect::to("/reviews")
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Redirect> {
+    let oid = path_oid(id, "review")?;
+    // delete devuelve la reseña borrada (su book_id se invalida)
+    let prev = state.reviews.delete(&oid).await?.ok_or_else(|| AppError::not_found("review"))?;
+    state.search_delete_review(&oid).await;
+
+    // invalidate caches
+    state.invalidate_tags(&[AppState::TAG_REVIEWS.into(), AppState::tag_book(&prev.book_id)]).await;
+    Ok(Redirect::to("/reviews"))
 }
 
 // GET /reviews/read/<id>
 #[get("/read/<id>")]
-pub async fn read(state: &State<AppState>, id: &str) -> Template {
-    let r_c = reviews_col(state);
-    let b_c = books_col(state);
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return index(state, None).await };
-    let rev = match r_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_)=> None };
-    if rev.is_none() { return index(state, None).await; }
-    let r = rev.unwrap();
-
-    let book_title = b_c
-        .find_one(doc!{"_id": &r.book_id}).await.ok().flatten().map(|b| b.title).unwrap_or_else(|| "(unknown)".into());
-
-    let rv = ReviewView {
-        id: r.id.unwrap().to_hex(),
-        book_id: r.book_id.to_hex(),
-        book_title,
-        text: r.text,
-        score: r.score,
-        up_votes: r.up_votes,
-    };
-
-    Template::render("reviews/read", &ReviewCtx { review: rv, books: vec![] })
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let rv = find_review(state, id).await?;
+    Ok(Template::render("reviews/read", &ReviewCtx { review: rv, books: vec![] }))
 }
 
 pub fn routes() -> Vec<Route> {
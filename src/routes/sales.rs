@@ -1,34 +1,84 @@
 use rocket::{Route, State};
-use rocket::form::{Form, FromForm};
+use rocket::form::{Contextual, Form, FromForm};
 use rocket::response::Redirect;
 use rocket_dyn_templates::Template;
 use serde::Serialize;
-use futures_util::TryStreamExt;
+use serde_json::{json, Map};
 use std::collections::HashMap;
 
-use mongodb::{
-    bson::{doc, oid::ObjectId, Bson, Document},
-    Collection,
-};
+use mongodb::bson::oid::ObjectId;
 
 use crate::db::AppState;
-use crate::models::{Book, Sale};
+use crate::error::{path_oid, AppError, AppResult};
+use crate::models::Sale;
+use crate::repo::{BookFilter, SaleFilter};
+use crate::routes::forms::{missing, object_id, Invalid, Rejected, Submitted};
+
+/* ========= Helpers ========= */
+
+// Opciones del <select> de libros
+async fn book_options(state: &AppState) -> AppResult<Vec<BookOpt>> {
+    let books = state.books.list(&BookFilter::default()).await?;
+    Ok(books
+        .into_iter()
+        .filter_map(|b| Some(BookOpt { id: b.id?.to_hex(), title: b.title }))
+        .collect())
+}
 
-/* ========= Helpers de colecciones ========= */
+async fn book_title(state: &AppState, book_id: &ObjectId) -> AppResult<String> {
+    Ok(state.books.get(book_id).await?.map(|b| b.title).unwrap_or_else(|| "(unknown)".into()))
+}
 
-pub fn sales_col(state: &State<AppState>) -> Collection<Sale> {
-    state.db.collection::<Sale>("sales")
+// El form validado y con un libro que existe; si no, lo enviado con los errores
+async fn checked(state: &AppState, form: Contextual<'_, SaleForm>) -> AppResult<Result<(SaleForm, ObjectId), Rejected>> {
+    let Contextual { value, mut context } = form;
+    let Some(f) = value else { return Ok(Err(Rejected::new(&context))) };
+    let book_oid = ObjectId::parse_str(&f.book_id).ok();
+    match book_oid {
+        Some(oid) if state.books.get(&oid).await?.is_some() => Ok(Ok((f, oid))),
+        _ => {
+            missing(&mut context, "book_id", "book");
+            Ok(Err(Rejected::new(&context)))
+        }
+    }
+}
+
+// El form otra vez (edit si hay id, si no create), con los libros del <select>
+async fn invalid(state: &AppState, rejected: Rejected, id: Option<&str>) -> AppResult<Invalid> {
+    let (template, rejected) = match id {
+        Some(id) => ("sales/edit", rejected.with_id(id)),
+        None => ("sales/create", rejected),
+    };
+    let mut ctx = Map::new();
+    ctx.insert("books".into(), json!(book_options(state).await?));
+    Ok(rejected.render(template, "sale", ctx))
 }
-pub fn books_col(state: &State<AppState>) -> Collection<Book> {
-    state.db.collection::<Book>("books")
+
+// La venta de la ruta ya como vista; 404 si el id no es válido o no existe
+async fn find_sale(state: &AppState, id: &str) -> AppResult<SaleView> {
+    let oid = path_oid(id, "sale")?;
+    let s = state.sales.get(&oid).await?.ok_or_else(|| AppError::not_found("sale"))?;
+    Ok(SaleView {
+        id: oid.to_hex(),
+        book_id: s.book_id.to_hex(),
+        book_title: book_title(state, &s.book_id).await?,
+        year: s.year,
+        units: s.units,
+    })
 }
 
 /* ========= Formularios y vistas ========= */
 
+// Años de venta admitidos (formulario y API)
+pub const SALE_YEARS: std::ops::RangeInclusive<isize> = 1900..=2100;
+
 #[derive(FromForm)]
 pub struct SaleForm {
+    #[field(validate = object_id())]
     pub book_id: String,    // del <select>
+    #[field(validate = range(SALE_YEARS))]
     pub year: i32,
+    #[field(validate = range(0..))]
     pub units: i64,
 }
 
@@ -53,7 +103,6 @@ struct SalesCtx {
     books: Vec<BookOpt>,
     q_book: Option<String>, // filtro por book_id
     q_year: Option<i32>,    // filtro por year
-    message: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -64,39 +113,20 @@ struct SaleCtx {
 
 /* ========= Recalcular total_sales del Book ========= */
 
-async fn recompute_book_total(state: &State<AppState>, book_oid: &ObjectId) {
-    let sales = sales_col(state);
-    let books = books_col(state);
-
-    // pipeline: sum(units) por book_id
-    let pipeline = vec![
-        doc! { "$match": { "book_id": book_oid } },
-        doc! { "$group": { "_id": Bson::Null, "total": { "$sum": "$units" } } },
-    ];
+pub async fn recompute_book_total(state: &AppState, book_oid: &ObjectId) -> AppResult<()> {
+    let total = state.sales.total_units(book_oid).await?;
 
-    let mut cur = match sales.aggregate(pipeline).await {
-        Ok(c) => c,
-        Err(e) => { eprintln!("aggregate sales error: {e}"); return; }
-    };
+    // actualizar books.total_sales
+    state.books.set_total_sales(book_oid, total).await?;
 
-    let mut total: i64 = 0;
-    if let Ok(Some(d)) = cur.try_next().await {
-        if let Ok(t) = d.get_i64("total") { total = t; }
-        else if let Some(Bson::Int64(v)) = d.get("total") { total = *v; }
-        else if let Some(Bson::Int32(v)) = d.get("total") { total = *v as i64; }
-    }
+    invalidate_book_sales(state, book_oid).await;
+    Ok(())
+}
 
-    // actualizar books.total_sales
-    let _ = books
-        .find_one_and_update(
-            doc! { "_id": book_oid },
-            doc! { "$set": { "total_sales": total } },
-        )
-        .await;
-
-    // invalidate caches: author summaries (they aggregate sales) and search results
-    state.cache_del_key(AppState::AUTHORS_SUMMARY_CACHE_KEY).await;
-    state.cache_del_pref("search:books:").await;
+// invalidate caches: this book and everything aggregating sales
+// (upsert y merge_into ya dejan total_sales al día en su transacción)
+pub async fn invalidate_book_sales(state: &AppState, book_oid: &ObjectId) {
+    state.invalidate_tags(&[AppState::tag_book(book_oid), AppState::TAG_SALES.into()]).await;
 }
 
 
@@ -106,234 +136,128 @@ pub async fn index(
     state: &State<AppState>,
     q_book: Option<String>,
     q_year: Option<i32>,
-) -> Template {
-    let s_c = sales_col(state);
-    let b_c = books_col(state);
-
+) -> AppResult<Template> {
     // map de book_id -> title y opciones para select
-    let mut b_cur = b_c.find(doc!{}).await.expect("books find");
-    let mut books = Vec::<BookOpt>::new();
-    let mut title_by_id = HashMap::<String, String>::new();
-    while let Ok(Some(b)) = b_cur.try_next().await {
-        if let Some(id) = b.id {
-            let s = id.to_hex();
-            title_by_id.insert(s.clone(), b.title.clone());
-            books.push(BookOpt { id: s, title: b.title });
-        }
-    }
+    let books = book_options(state).await?;
+    let title_by_id: HashMap<&str, &str> = books.iter().map(|b| (b.id.as_str(), b.title.as_str())).collect();
 
     // filtros
-    let mut filter = Document::new();
-    if let Some(ref bid) = q_book {
-        if let Ok(oid) = ObjectId::parse_str(bid) {
-            filter.insert("book_id", oid);
-        }
-    }
-    if let Some(y) = q_year {
-        filter.insert("year", y);
-    }
+    let filter = SaleFilter {
+        book_id: q_book.as_deref().and_then(|bid| ObjectId::parse_str(bid).ok()),
+        year: q_year,
+    };
 
     // query
     let mut sales = Vec::<SaleView>::new();
-    let mut cur = s_c.find(filter).await.expect("sales find");
-    loop {
-        match cur.try_next().await {
-            Ok(Some(s)) => {
-                if let Some(id) = s.id {
-                    let bid = s.book_id.to_hex();
-                    let book_title = title_by_id.get(&bid).cloned().unwrap_or_else(|| "(unknown)".into());
-                    sales.push(SaleView {
-                        id: id.to_hex(),
-                        book_id: bid,
-                        book_title,
-                        year: s.year,
-                        units: s.units,
-                    });
-                }
-            }
-            Ok(None) => break,
-            Err(e) => { eprintln!("sales cursor: {e}"); break; }
+    let found = state.sales.list(&filter).await?;
+    for s in found {
+        if let Some(id) = s.id {
+            let bid = s.book_id.to_hex();
+            let book_title = title_by_id.get(bid.as_str()).map(|t| t.to_string()).unwrap_or_else(|| "(unknown)".into());
+            sales.push(SaleView {
+                id: id.to_hex(),
+                book_id: bid,
+                book_title,
+                year: s.year,
+                units: s.units,
+            });
         }
     }
 
-    Template::render(
+    Ok(Template::render(
         "sales/index",
-        &SalesCtx { sales, books, q_book, q_year, message: None }
-    )
+        &SalesCtx { sales, books, q_book, q_year }
+    ))
 }
 
 // GET /sales/create
 #[get("/create")]
-pub async fn create_page(state: &State<AppState>) -> Template {
-    let b_c = books_col(state);
-    let mut books = Vec::<BookOpt>::new();
-    let mut cur = b_c.find(doc!{}).await.expect("books find");
-    while let Ok(Some(b)) = cur.try_next().await {
-        if let Some(id) = b.id {
-            books.push(BookOpt { id: id.to_hex(), title: b.title });
-        }
-    }
-    Template::render("sales/create", &serde_json::json!({ "books": books }))
+pub async fn create_page(state: &State<AppState>) -> AppResult<Template> {
+    let books = book_options(state).await?;
+    Ok(Template::render("sales/create", serde_json::json!({ "books": books })))
 }
 
 // POST /sales/create
 // si ya existe (book_id, year), actualizamos units (replace) en lugar de insertar duplicado.
 #[post("/create", data = "<form>")]
-pub async fn create(state: &State<AppState>, form: Form<SaleForm>) -> Redirect {
-    let s_c = sales_col(state);
-    let b_c = books_col(state);
-    let f = form.into_inner();
-
-    let book_oid = match ObjectId::parse_str(&f.book_id) { Ok(x)=>x, Err(_)=> return Redirect::to("/sales") };
-    // validar libro
-    let exists = b_c.find_one(doc!{"_id": &book_oid}).await.ok().flatten().is_some();
-    if !exists { return Redirect::to("/sales"); }
+pub async fn create(state: &State<AppState>, form: Form<Contextual<'_, SaleForm>>) -> AppResult<Submitted<Redirect>> {
+    // validar campos y libro
+    let (f, book_oid) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, None).await?)),
+    };
 
     // si existe la venta de ese año, actualizamos; si no, insertamos
-    let key = doc! { "book_id": &book_oid, "year": f.year };
-    let found = s_c.find_one(key.clone()).await.ok().flatten();
-    if found.is_some() {
-        let _ = s_c
-            .find_one_and_update(key, doc!{ "$set": { "units": f.units } })
-            .await;
-    } else {
-        let s = Sale { id: None, book_id: book_oid, year: f.year, units: f.units };
-        let _ = s_c.insert_one(&s).await;
-    }
+    // (el total del libro se recalcula en la misma transacción)
+    state.sales.upsert(&book_oid, f.year, f.units).await?;
+    invalidate_book_sales(state, &book_oid).await;
 
-    // recomputar total del libro
-    recompute_book_total(state, &book_oid).await;
-
-    Redirect::to("/sales")
+    Ok(Ok(Redirect::to("/sales")))
 }
 
 // GET /sales/edit/<id>
 #[get("/edit/<id>")]
-pub async fn edit(state: &State<AppState>, id: &str) -> Template {
-    let s_c = sales_col(state);
-    let b_c = books_col(state);
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return index(state, None, None).await };
-    let s = match s_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_)=> None };
-    if s.is_none() { return index(state, None, None).await; }
-    let s = s.unwrap();
+pub async fn edit(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let view = find_sale(state, id).await?;
 
     // cargar libros para select (permitimos cambiar libro; si no lo quieres, puedes hacerlo readonly)
-    let mut books = Vec::<BookOpt>::new();
-    let mut cur = b_c.find(doc!{}).await.expect("books find");
-    while let Ok(Some(b)) = cur.try_next().await {
-        if let Some(id) = b.id {
-            books.push(BookOpt { id: id.to_hex(), title: b.title });
-        }
-    }
-
-    let book_title = b_c
-        .find_one(doc!{"_id": &s.book_id}).await.ok().flatten().map(|b| b.title).unwrap_or_else(|| "(unknown)".into());
+    let books = book_options(state).await?;
 
-    let view = SaleView {
-        id: s.id.unwrap().to_hex(),
-        book_id: s.book_id.to_hex(),
-        book_title,
-        year: s.year,
-        units: s.units,
-    };
-
-    Template::render("sales/edit", &SaleCtx { sale: view, books })
+    Ok(Template::render("sales/edit", &SaleCtx { sale: view, books }))
 }
 
 // POST /sales/update/<id>
+// Si el cambio falla no se toca nada y se muestra la página de error.
 #[post("/update/<id>", data = "<form>")]
-pub async fn update(state: &State<AppState>, id: &str, form: Form<SaleForm>) -> Redirect {
-    let s_c = sales_col(state);
-    let b_c = books_col(state);
-    let f = form.into_inner();
+pub async fn update(state: &State<AppState>, id: &str, form: Form<Contextual<'_, SaleForm>>) -> AppResult<Submitted<Redirect>> {
+    let oid = path_oid(id, "sale")?;
+    // validar campos y libro
+    let (f, new_book) = match checked(state, form.into_inner()).await? {
+        Ok(checked) => checked,
+        Err(rejected) => return Ok(Err(invalid(state, rejected, Some(id)).await?)),
+    };
 
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return Redirect::to("/sales") };
-    let new_book = match ObjectId::parse_str(&f.book_id) { Ok(x)=>x, Err(_)=>return Redirect::to("/sales") };
+    // necesitamos saber el book_id anterior para recomputar si cambia
+    let prev = state.sales.get(&oid).await?.ok_or_else(|| AppError::not_found("sale"))?;
 
-    // validar libro
-    let exists = b_c.find_one(doc!{"_id": &new_book}).await.ok().flatten().is_some();
-    if !exists { return Redirect::to("/sales"); }
+    // si cambió (book_id, year) hay que respetar la unicidad (book_id, year):
+    // si ya existe el destino le ponemos estas units y borramos el original.
+    // Las dos escrituras y los totales de ambos libros (origen y destino) van
+    // en una transacción (o todo o nada).
+    if prev.book_id != new_book || prev.year != f.year {
+        state.sales.merge_into(&oid, &new_book, f.year, f.units).await?;
 
-    // necesitamos saber el book_id anterior para recomputar si cambia
-    let old = s_c.find_one(doc!{"_id": oid}).await.ok().flatten();
-
-    // si cambió (book_id, year) hay que respetar la unicidad (book_id, year)
-    // estrategia simple: si ya existe destino, actualizamos su units; y eliminamos el doc original.
-    if let Some(prev) = old {
-        if prev.book_id != new_book || prev.year != f.year {
-            // fusionar (upsert manual)
-            let key = doc!{"book_id": &new_book, "year": f.year};
-            let found = s_c.find_one(key.clone()).await.ok().flatten();
-            if found.is_some() {
-                let _ = s_c.find_one_and_update(key, doc!{"$set": {"units": f.units}}).await;
-            } else {
-                let _ = s_c.insert_one(&Sale { id: None, book_id: new_book, year: f.year, units: f.units }).await;
-            }
-            let _ = s_c.delete_one(doc!{"_id": oid}).await;
-
-            // recomputar ambos libros (origen y destino)
-            recompute_book_total(state, &prev.book_id).await;
-            recompute_book_total(state, &new_book).await;
-
-            return Redirect::to("/sales");
-        }
+        invalidate_book_sales(state, &prev.book_id).await;
+        invalidate_book_sales(state, &new_book).await;
+
+        return Ok(Ok(Redirect::to("/sales")));
     }
 
     // misma clave, solo actualizamos units
-    let _ = s_c
-        .find_one_and_update(
-            doc!{"_id": oid},
-            doc!{"$set": {"book_id": &new_book, "year": f.year, "units": f.units}}
-        )
-        .await;
+    let sale = Sale { id: Some(oid), book_id: new_book, year: f.year, units: f.units };
+    state.sales.update(&oid, &sale).await?;
 
     // recomputar libro
-    recompute_book_total(state, &new_book).await;
+    recompute_book_total(state, &new_book).await?;
 
-    Redirect::to("/sales")
+    Ok(Ok(Redirect::to("/sales")))
 }
 
 // POST /sales/delete/<id>
 #[post("/delete/<id>")]
-pub async fn delete(state: &State<AppState>, id: &str) -> Redirect {
-    let s_c = sales_col(state);
-
-    // obtener book_id para recomputar luego
-    if let Ok(oid) = ObjectId::parse_str(id) {
-        if let Ok(Some(prev)) = s_c.find_one(doc!{"_id": oid}).await {
-            let book = prev.book_id.clone();
-            let _ = s_c.delete_one(doc!{"_id": oid}).await;
-            recompute_book_total(state, &book).await;
-            return Redirect::to("/sales");
-        }
-    }
-    Redirect::to("/sales")
+pub async fn delete(state: &State<AppState>, id: &str) -> AppResult<Redirect> {
+    let oid = path_oid(id, "sale")?;
+    // delete devuelve la venta borrada: su book_id se recomputa
+    let prev = state.sales.delete(&oid).await?.ok_or_else(|| AppError::not_found("sale"))?;
+    recompute_book_total(state, &prev.book_id).await?;
+    Ok(Redirect::to("/sales"))
 }
 
 // GET /sales/read/<id>
 #[get("/read/<id>")]
-pub async fn read(state: &State<AppState>, id: &str) -> Template {
-    let s_c = sales_col(state);
-    let b_c = books_col(state);
-
-    let oid = match ObjectId::parse_str(id) { Ok(x)=>x, Err(_)=> return index(state, None, None).await };
-    let s = match s_c.find_one(doc!{"_id": oid}).await { Ok(x)=>x, Err(_)=> None };
-    if s.is_none() { return index(state, None, None).await; }
-    let s = s.unwrap();
-
-    let book_title = b_c
-        .find_one(doc!{"_id": &s.book_id}).await.ok().flatten().map(|b| b.title).unwrap_or_else(|| "(unknown)".into());
-
-    let view = SaleView {
-        id: s.id.unwrap().to_hex(),
-        book_id: s.book_id.to_hex(),
-        book_title,
-        year: s.year,
-        units: s.units,
-    };
-
-    Template::render("sales/read", &SaleCtx { sale: view, books: vec![] })
+pub async fn read(state: &State<AppState>, id: &str) -> AppResult<Template> {
+    let view = find_sale(state, id).await?;
+    Ok(Template::render("sales/read", &SaleCtx { sale: view, books: vec![] }))
 }
 
 pub fn routes() -> Vec<Route> {
@@ -0,0 +1,505 @@
+// Facetas de la búsqueda de libros: año de publicación, autor, país del autor,
+// nota media y tramo de ventas. Los conteos salen de un $facet sobre el mismo
+// $match que la búsqueda; cada faceta cuenta con el resto de filtros aplicados
+// (pero no el suyo, para poder cambiar de opción). MemoryRepo hace lo mismo
+// sobre BookFacets (`matches` y `count_books`).
+use std::collections::{BTreeMap, HashMap};
+
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use rocket::form::FromForm;
+use rocket::http::RawStr;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{bson_date, FacetBucket, FacetCounts};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facet {
+    Year,
+    Author,
+    Country,
+    Score,
+    Sales,
+}
+
+// Tramos de nota media: [n, n+1), el último incluye el 5
+const SCORE_BOUNDARIES: [f64; 6] = [0.0, 1.0, 2.0, 3.0, 4.0, 5.01];
+
+// Tramos de ventas totales: (valor en la URL, etiqueta, desde, hasta)
+const SALES_TIERS: [(&str, &str, i64, i64); 4] = [
+    ("low", "< 10K", 0, 10_000),
+    ("mid", "10K – 100K", 10_000, 100_000),
+    ("high", "100K – 1M", 100_000, 1_000_000),
+    ("top", "≥ 1M", 1_000_000, i64::MAX),
+];
+
+// Opciones mostradas para autor / país (las más frecuentes)
+const MAX_TERMS_FACET: i64 = 15;
+
+// Selección actual, tal como llega en el query string de /search
+#[derive(Debug, Clone, Default, FromForm, Serialize, Deserialize)]
+pub struct FacetSelection {
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub author: Option<String>,
+    pub country: Option<String>,
+    pub score: Option<String>,
+    pub sales: Option<String>,
+}
+
+// Los campos que añade `enrich_stages` a un libro encontrado
+#[derive(Debug, Clone)]
+pub struct BookFacets {
+    pub year: Option<i32>,
+    pub author_id: ObjectId,
+    pub author_name: String,
+    pub author_country: Option<String>,
+    pub avg_score: Option<f64>,
+    pub total_sales: i64,
+}
+
+// Query string de la selección sin una faceta: base de los enlaces de esa faceta.
+// Mismos nombres que en FacetCounts, para recorrerlos juntos en la vista.
+#[derive(Debug, Serialize)]
+pub struct FacetLinks {
+    pub years: String,
+    pub authors: String,
+    pub countries: String,
+    pub scores: String,
+    pub sales: String,
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+impl FacetSelection {
+    // Los formularios mandan "" para los campos vacíos
+    pub fn normalized(self) -> Self {
+        Self {
+            year_from: self.year_from,
+            year_to: self.year_to,
+            author: non_empty(self.author).filter(|a| ObjectId::parse_str(a).is_ok()),
+            country: non_empty(self.country),
+            score: non_empty(self.score),
+            sales: non_empty(self.sales),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.year_from.is_none()
+            && self.year_to.is_none()
+            && self.author.is_none()
+            && self.country.is_none()
+            && self.score.is_none()
+            && self.sales.is_none()
+    }
+
+    // Parte de la clave de cache (vacía si no hay filtros)
+    pub fn cache_key(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default().to_lowercase().replace(char::is_whitespace, "+");
+        format!(
+            ":f:y:{}-{}:a:{}:c:{}:s:{}:t:{}",
+            self.year_from.map(|y| y.to_string()).unwrap_or_default(),
+            self.year_to.map(|y| y.to_string()).unwrap_or_default(),
+            opt(&self.author),
+            opt(&self.country),
+            opt(&self.score),
+            opt(&self.sales),
+        )
+    }
+
+    // "&year_from=..&author=.." para preservar la selección en los enlaces
+    pub fn query_string(&self, except: Option<Facet>) -> String {
+        let mut qs = String::new();
+        let mut push = |k: &str, v: &str| {
+            qs.push_str(&format!("&{k}={}", RawStr::new(v).percent_encode()));
+        };
+        if except != Some(Facet::Year) {
+            if let Some(y) = self.year_from {
+                push("year_from", &y.to_string());
+            }
+            if let Some(y) = self.year_to {
+                push("year_to", &y.to_string());
+            }
+        }
+        if let (true, Some(v)) = (except != Some(Facet::Author), &self.author) {
+            push("author", v);
+        }
+        if let (true, Some(v)) = (except != Some(Facet::Country), &self.country) {
+            push("country", v);
+        }
+        if let (true, Some(v)) = (except != Some(Facet::Score), &self.score) {
+            push("score", v);
+        }
+        if let (true, Some(v)) = (except != Some(Facet::Sales), &self.sales) {
+            push("sales", v);
+        }
+        qs
+    }
+
+    pub fn links(&self) -> FacetLinks {
+        FacetLinks {
+            years: self.query_string(Some(Facet::Year)),
+            authors: self.query_string(Some(Facet::Author)),
+            countries: self.query_string(Some(Facet::Country)),
+            scores: self.query_string(Some(Facet::Score)),
+            sales: self.query_string(Some(Facet::Sales)),
+        }
+    }
+
+    // Condiciones sobre los campos que añade `enrich_stages`
+    pub fn match_doc(&self, except: Option<Facet>) -> Document {
+        let mut m = Document::new();
+
+        if except != Some(Facet::Year) {
+            let mut range = Document::new();
+            if let Some(y) = self.year_from {
+                range.insert("$gte", y);
+            }
+            if let Some(y) = self.year_to {
+                range.insert("$lte", y);
+            }
+            if !range.is_empty() {
+                m.insert("year", range);
+            }
+        }
+
+        if except != Some(Facet::Author) {
+            if let Some(oid) = self.author.as_deref().and_then(|a| ObjectId::parse_str(a).ok()) {
+                m.insert("author_id", oid);
+            }
+        }
+
+        if except != Some(Facet::Country) {
+            if let Some(c) = &self.country {
+                m.insert("author_country", c);
+            }
+        }
+
+        if except != Some(Facet::Score) {
+            match self.score.as_deref() {
+                Some("none") => {
+                    m.insert("avg_score", Bson::Null);
+                }
+                Some(s) => {
+                    if let Some(i) = s.parse::<usize>().ok().filter(|i| *i + 1 < SCORE_BOUNDARIES.len()) {
+                        m.insert("avg_score", doc! { "$gte": SCORE_BOUNDARIES[i], "$lt": SCORE_BOUNDARIES[i + 1] });
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if except != Some(Facet::Sales) {
+            if let Some((_, _, lo, hi)) = SALES_TIERS.iter().find(|t| Some(t.0) == self.sales.as_deref()) {
+                m.insert("total_sales", doc! { "$gte": lo, "$lt": hi });
+            }
+        }
+
+        m
+    }
+
+    // `match_doc` evaluado sobre un libro (sin Mongo)
+    pub fn matches(&self, b: &BookFacets, except: Option<Facet>) -> bool {
+        if except != Some(Facet::Year) {
+            // un rango de años no incluye libros sin fecha
+            if self.year_from.is_some_and(|y| b.year.is_none_or(|by| by < y)) || self.year_to.is_some_and(|y| b.year.is_none_or(|by| by > y)) {
+                return false;
+            }
+        }
+
+        if except != Some(Facet::Author) {
+            if let Some(oid) = self.author.as_deref().and_then(|a| ObjectId::parse_str(a).ok()) {
+                if b.author_id != oid {
+                    return false;
+                }
+            }
+        }
+
+        if except != Some(Facet::Country) {
+            if let Some(c) = &self.country {
+                if b.author_country.as_deref() != Some(c.as_str()) {
+                    return false;
+                }
+            }
+        }
+
+        if except != Some(Facet::Score) {
+            match self.score.as_deref() {
+                Some("none") if b.avg_score.is_some() => return false,
+                Some("none") | None => {}
+                Some(s) => {
+                    if let Some(i) = s.parse::<usize>().ok().filter(|i| *i + 1 < SCORE_BOUNDARIES.len()) {
+                        if !b.avg_score.is_some_and(|a| a >= SCORE_BOUNDARIES[i] && a < SCORE_BOUNDARIES[i + 1]) {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+
+        if except != Some(Facet::Sales) {
+            if let Some((_, _, lo, hi)) = SALES_TIERS.iter().find(|t| Some(t.0) == self.sales.as_deref()) {
+                if b.total_sales < *lo || b.total_sales >= *hi {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+// Campos calculados sobre cada libro encontrado, antes del $facet:
+// author (join), author_country, year, avg_score y total_sales.
+pub fn enrich_stages() -> Vec<Document> {
+    vec![
+        doc! {
+            "$lookup": {
+                "from": "authors",
+                "localField": "author_id",
+                "foreignField": "_id",
+                "as": "author"
+            }
+        },
+        doc! { "$unwind": "$author" },
+        doc! {
+            "$lookup": {
+                "from": "reviews",
+                "localField": "_id",
+                "foreignField": "book_id",
+                "as": "ratings"
+            }
+        },
+        doc! {
+            "$addFields": {
+                "author_country": "$author.country",
+                // null si no hay publication_date (o no es una fecha)
+                "year": bson_date::if_date("publication_date", doc! { "$year": "$publication_date" }),
+                // $avg de un array vacío => null (sin reseñas)
+                "avg_score": { "$avg": "$ratings.score" },
+                "total_sales": { "$ifNull": ["$total_sales", 0] }
+            }
+        },
+        doc! { "$unset": "ratings" },
+    ]
+}
+
+// Sub-pipelines de conteo para el $facet
+pub fn count_pipelines(sel: &FacetSelection) -> Document {
+    doc! {
+        "years": [
+            { "$match": sel.match_doc(Some(Facet::Year)) },
+            { "$match": { "year": { "$ne": null } } },
+            // por décadas
+            { "$group": { "_id": { "$subtract": ["$year", { "$mod": ["$year", 10] }] }, "count": { "$sum": 1 } } },
+            { "$sort": { "_id": -1 } }
+        ],
+        "authors": [
+            { "$match": sel.match_doc(Some(Facet::Author)) },
+            { "$group": { "_id": "$author_id", "name": { "$first": "$author.name" }, "count": { "$sum": 1 } } },
+            { "$sort": { "count": -1, "name": 1 } },
+            { "$limit": MAX_TERMS_FACET }
+        ],
+        "countries": [
+            { "$match": sel.match_doc(Some(Facet::Country)) },
+            { "$match": { "author_country": { "$nin": [null, ""] } } },
+            { "$group": { "_id": "$author_country", "count": { "$sum": 1 } } },
+            { "$sort": { "count": -1, "_id": 1 } },
+            { "$limit": MAX_TERMS_FACET }
+        ],
+        "scores": [
+            { "$match": sel.match_doc(Some(Facet::Score)) },
+            {
+                "$bucket": {
+                    "groupBy": "$avg_score",
+                    "boundaries": SCORE_BOUNDARIES.to_vec(),
+                    "default": "none",
+                    "output": { "count": { "$sum": 1 } }
+                }
+            }
+        ],
+        "sales": [
+            { "$match": sel.match_doc(Some(Facet::Sales)) },
+            {
+                "$bucket": {
+                    "groupBy": "$total_sales",
+                    "boundaries": SALES_TIERS.iter().map(|t| t.2).chain([i64::MAX]).collect::<Vec<_>>(),
+                    "default": "other",
+                    "output": { "count": { "$sum": 1 } }
+                }
+            }
+        ]
+    }
+}
+
+// Los conteos de `count_pipelines` sobre libros ya enriquecidos: arma la misma
+// salida que el $facet y la traduce con `counts_from`
+pub fn count_books(books: &[&BookFacets], sel: &FacetSelection) -> FacetCounts {
+    let counted = |except: Facet| books.iter().filter(move |b| sel.matches(b, Some(except)));
+    let bucket = |id: Bson, count: i64| doc! { "_id": id, "count": count };
+
+    // por décadas, la más reciente primero
+    let mut decades = BTreeMap::<i32, i64>::new();
+    for year in counted(Facet::Year).filter_map(|b| b.year) {
+        *decades.entry(year - year % 10).or_default() += 1;
+    }
+    let years: Vec<Document> = decades.into_iter().rev().map(|(d, n)| bucket(d.into(), n)).collect();
+
+    let mut by_author = HashMap::<ObjectId, (&str, i64)>::new();
+    for b in counted(Facet::Author) {
+        by_author.entry(b.author_id).or_insert((&b.author_name, 0)).1 += 1;
+    }
+    let mut by_author: Vec<_> = by_author.into_iter().collect();
+    by_author.sort_by(|(_, (a, n)), (_, (b, m))| m.cmp(n).then_with(|| a.cmp(b)));
+    let authors: Vec<Document> = by_author
+        .into_iter()
+        .take(MAX_TERMS_FACET as usize)
+        .map(|(id, (name, n))| doc! { "_id": id, "name": name, "count": n })
+        .collect();
+
+    let mut by_country = HashMap::<&str, i64>::new();
+    for c in counted(Facet::Country).filter_map(|b| b.author_country.as_deref()).filter(|c| !c.is_empty()) {
+        *by_country.entry(c).or_default() += 1;
+    }
+    let mut by_country: Vec<_> = by_country.into_iter().collect();
+    by_country.sort_by(|(a, n), (b, m)| m.cmp(n).then_with(|| a.cmp(b)));
+    let countries: Vec<Document> =
+        by_country.into_iter().take(MAX_TERMS_FACET as usize).map(|(c, n)| bucket(c.into(), n)).collect();
+
+    // $bucket: los tramos en orden y al final el "default" (sin nota)
+    let mut score_tiers = BTreeMap::<usize, i64>::new();
+    let mut no_score = 0;
+    for b in counted(Facet::Score) {
+        match b.avg_score.and_then(|a| SCORE_BOUNDARIES.windows(2).position(|w| a >= w[0] && a < w[1])) {
+            Some(i) => *score_tiers.entry(i).or_default() += 1,
+            None => no_score += 1,
+        }
+    }
+    let mut scores: Vec<Document> = score_tiers.into_iter().map(|(i, n)| bucket(SCORE_BOUNDARIES[i].into(), n)).collect();
+    if no_score > 0 {
+        scores.push(bucket("none".into(), no_score));
+    }
+
+    let mut sales_tiers = BTreeMap::<i64, i64>::new();
+    for b in counted(Facet::Sales) {
+        if let Some((_, _, lo, _)) = SALES_TIERS.iter().find(|t| b.total_sales >= t.2 && b.total_sales < t.3) {
+            *sales_tiers.entry(*lo).or_default() += 1;
+        }
+    }
+    let sales: Vec<Document> = sales_tiers.into_iter().map(|(lo, n)| bucket(lo.into(), n)).collect();
+
+    let facet = doc! { "years": years, "authors": authors, "countries": countries, "scores": scores, "sales": sales };
+    counts_from(&facet, sel)
+}
+
+fn count_of(d: &Document) -> i64 {
+    match d.get("count") {
+        Some(Bson::Int32(n)) => *n as i64,
+        Some(Bson::Int64(n)) => *n,
+        _ => 0,
+    }
+}
+
+fn number(b: Option<&Bson>) -> Option<f64> {
+    match b? {
+        Bson::Int32(n) => Some(*n as f64),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    }
+}
+
+// Traduce la salida del $facet a buckets para la vista
+pub fn counts_from(facet: &Document, sel: &FacetSelection) -> FacetCounts {
+    let docs = |name: &str| -> Vec<Document> {
+        facet
+            .get_array(name)
+            .map(|a| a.iter().filter_map(|b| b.as_document().cloned()).collect())
+            .unwrap_or_default()
+    };
+    let enc = |v: &str| RawStr::new(v).percent_encode().to_string();
+
+    let years = docs("years")
+        .iter()
+        .filter_map(|d| {
+            let decade = number(d.get("_id"))? as i32;
+            Some(FacetBucket {
+                value: decade.to_string(),
+                label: format!("{decade}s"),
+                count: count_of(d),
+                selected: sel.year_from == Some(decade) && sel.year_to == Some(decade + 9),
+                query: format!("&year_from={decade}&year_to={}", decade + 9),
+            })
+        })
+        .collect();
+
+    let authors = docs("authors")
+        .iter()
+        .filter_map(|d| {
+            let id = d.get_object_id("_id").ok()?.to_hex();
+            Some(FacetBucket {
+                label: d.get_str("name").unwrap_or("(unknown)").to_string(),
+                count: count_of(d),
+                selected: sel.author.as_deref() == Some(id.as_str()),
+                query: format!("&author={id}"),
+                value: id,
+            })
+        })
+        .collect();
+
+    let countries = docs("countries")
+        .iter()
+        .filter_map(|d| {
+            let c = d.get_str("_id").ok()?.to_string();
+            Some(FacetBucket {
+                label: c.clone(),
+                count: count_of(d),
+                selected: sel.country.as_deref() == Some(c.as_str()),
+                query: format!("&country={}", enc(&c)),
+                value: c,
+            })
+        })
+        .collect();
+
+    let scores = docs("scores")
+        .iter()
+        .filter_map(|d| {
+            let (value, label) = match d.get("_id") {
+                Some(Bson::String(s)) if s == "none" => ("none".to_string(), "Sin reseñas".to_string()),
+                other => {
+                    let lo = number(other)?;
+                    let i = SCORE_BOUNDARIES.iter().position(|b| *b == lo)?;
+                    (i.to_string(), format!("{lo:.0} – {:.0} ★", SCORE_BOUNDARIES[i + 1].floor()))
+                }
+            };
+            Some(FacetBucket {
+                selected: sel.score.as_deref() == Some(value.as_str()),
+                query: format!("&score={value}"),
+                count: count_of(d),
+                value,
+                label,
+            })
+        })
+        .collect();
+
+    let sales = docs("sales")
+        .iter()
+        .filter_map(|d| {
+            let lo = number(d.get("_id"))? as i64;
+            let (value, label, _, _) = SALES_TIERS.iter().find(|t| t.2 == lo)?;
+            Some(FacetBucket {
+                value: value.to_string(),
+                label: label.to_string(),
+                count: count_of(d),
+                selected: sel.sales.as_deref() == Some(*value),
+                query: format!("&sales={value}"),
+            })
+        })
+        .collect();
+
+    FacetCounts { years, authors, countries, scores, sales }
+}
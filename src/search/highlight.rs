@@ -0,0 +1,195 @@
+// Fragmentos resaltados para los resultados: qué palabras del título o del
+// resumen coinciden con la búsqueda. Se devuelven como partes (texto + si es
+// coincidencia) y la vista pone el <mark>, así no generamos HTML aquí.
+use crate::models::{Highlight, HighlightPart};
+
+use super::query::{edit_distance, fold, max_edits, parse};
+
+// Largo aproximado de un fragmento del resumen
+const FRAGMENT_CHARS: usize = 180;
+// Fragmentos de resumen como mucho
+const MAX_FRAGMENTS: usize = 2;
+
+// Términos a resaltar: palabras sueltas y de frases (no las excluidas)
+fn query_terms(q: &str) -> Vec<String> {
+    let parsed = parse(q);
+    let mut terms: Vec<String> = parsed
+        .terms
+        .iter()
+        .chain(parsed.phrases.iter())
+        .flat_map(|t| fold(t).split(|c: char| !c.is_alphanumeric()).map(str::to_string).collect::<Vec<_>>())
+        .filter(|t| !t.is_empty())
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+// Coincidencia como la vería alguno de los modos: exacta, por prefijo
+// (búsqueda parcial), por raíz común (stemming de $text) o con typos.
+fn word_matches(word: &str, term: &str) -> bool {
+    if word == term || (term.chars().count() >= 2 && word.starts_with(term)) {
+        return true;
+    }
+    let common = word.chars().zip(term.chars()).take_while(|(a, b)| a == b).count();
+    let term_len = term.chars().count();
+    if common >= 4 && common + 3 >= term_len {
+        return true;
+    }
+    edit_distance(word, term, max_edits(term)).is_some()
+}
+
+// Palabras del texto: (inicio, fin) en bytes y si coinciden
+fn words(text: &str, terms: &[String]) -> Vec<(usize, usize, bool)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                let w = fold(&text[s..i]);
+                out.push((s, i, terms.iter().any(|t| word_matches(&w, t))));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// Partes de text[from..to], con las palabras coincidentes marcadas
+fn parts(text: &str, words: &[(usize, usize, bool)], from: usize, to: usize) -> Vec<HighlightPart> {
+    let mut out: Vec<HighlightPart> = Vec::new();
+    let mut push = |s: &str, hit: bool| {
+        if s.is_empty() {
+            return;
+        }
+        match out.last_mut() {
+            Some(last) if !last.hit && !hit => last.text.push_str(s),
+            _ => out.push(HighlightPart { text: s.to_string(), hit }),
+        }
+    };
+
+    let mut pos = from;
+    for &(s, e, hit) in words.iter().filter(|(s, e, _)| *s >= from && *e <= to) {
+        push(&text[pos..s], false);
+        push(&text[s..e], hit);
+        pos = e;
+    }
+    push(&text[pos..to], false);
+    out
+}
+
+// Ventana de unas FRAGMENT_CHARS letras que empieza un poco antes de la
+// palabra `hit`, cortada en límites de palabra.
+fn window(text: &str, words: &[(usize, usize, bool)], hit: usize) -> (usize, usize) {
+    let mut first = hit;
+    while first > 0 && text[words[first - 1].0..words[hit].0].chars().count() < FRAGMENT_CHARS / 4 {
+        first -= 1;
+    }
+    let mut last = hit;
+    while last + 1 < words.len() && text[words[first].0..words[last + 1].1].chars().count() <= FRAGMENT_CHARS {
+        last += 1;
+    }
+    let from = if first == 0 { 0 } else { words[first].0 };
+    let to = if last + 1 == words.len() { text.len() } else { words[last].1 };
+    (from, to)
+}
+
+fn highlight_field(field: &str, text: &str, terms: &[String]) -> Vec<Highlight> {
+    let ws = words(text, terms);
+    let hits: Vec<usize> = ws.iter().enumerate().filter(|(_, w)| w.2).map(|(i, _)| i).collect();
+    if hits.is_empty() {
+        return vec![];
+    }
+
+    // Texto corto: entero
+    if text.chars().count() <= FRAGMENT_CHARS {
+        return vec![Highlight {
+            field: field.to_string(),
+            parts: parts(text, &ws, 0, text.len()),
+            truncated_start: false,
+            truncated_end: false,
+        }];
+    }
+
+    let mut out = Vec::new();
+    let mut covered_to = 0;
+    for &h in &hits {
+        if out.len() == MAX_FRAGMENTS {
+            break;
+        }
+        // los fragmentos no se solapan: esta coincidencia ya se ve (o casi)
+        let (from, to) = window(text, &ws, h);
+        if !out.is_empty() && from <= covered_to {
+            continue;
+        }
+        out.push(Highlight {
+            field: field.to_string(),
+            parts: parts(text, &ws, from, to),
+            truncated_start: from > 0,
+            truncated_end: to < text.len(),
+        });
+        covered_to = to;
+    }
+    out
+}
+
+// Fragmentos de título y resumen de un libro para la búsqueda `q`
+pub fn book_highlights(title: &str, summary: Option<&str>, q: &str) -> Vec<Highlight> {
+    let terms = query_terms(q);
+    if terms.is_empty() {
+        return vec![];
+    }
+    let mut out = highlight_field("title", title, &terms);
+    if let Some(s) = summary {
+        out.extend(highlight_field("summary", s, &terms));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Las partes marcadas de todos los fragmentos
+    fn hits(highlights: &[Highlight]) -> Vec<String> {
+        highlights.iter().flat_map(|h| &h.parts).filter(|p| p.hit).map(|p| p.text.clone()).collect()
+    }
+
+    #[test]
+    fn marks_exact_prefix_accented_and_misspelled_words() {
+        let h = book_highlights("Cien años de soledad", Some("La novela de Macondo"), "AÑOS soledades novla macon");
+        assert_eq!(hits(&h), ["años", "soledad", "novela", "Macondo"]);
+        // el texto se conserva tal cual, partido en partes
+        assert_eq!(h[0].parts.iter().map(|p| p.text.as_str()).collect::<String>(), "Cien años de soledad");
+        assert_eq!(h[1].field, "summary");
+    }
+
+    #[test]
+    fn excluded_terms_and_short_typos_are_not_marked() {
+        let h = book_highlights("El sol y la sal", None, "sil -sal");
+        assert!(h.is_empty(), "{:?}", hits(&h));
+    }
+
+    #[test]
+    fn no_terms_or_no_hits_give_no_fragments() {
+        assert!(book_highlights("Rayuela", Some("Una novela"), "  ").is_empty());
+        assert!(book_highlights("Rayuela", Some("Una novela"), "-rayuela").is_empty());
+        assert!(book_highlights("Rayuela", None, "ficciones").is_empty());
+    }
+
+    #[test]
+    fn long_summaries_are_cut_into_separate_fragments_around_hits() {
+        let filler = "palabra ".repeat(60);
+        let summary = format!("Empieza {filler}aparece Macondo {filler}y otra vez Macondo {filler}fin");
+        let h = book_highlights("Título", Some(&summary), "macondo");
+        assert_eq!(h.len(), MAX_FRAGMENTS);
+        for f in &h {
+            assert!(f.truncated_start && f.truncated_end);
+            let text: String = f.parts.iter().map(|p| p.text.as_str()).collect();
+            assert!(text.chars().count() <= FRAGMENT_CHARS + 10, "{text}");
+            assert_eq!(hits(std::slice::from_ref(f)), ["Macondo"]);
+        }
+    }
+}
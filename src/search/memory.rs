@@ -0,0 +1,289 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use super::query::{edit_distance, fold, max_edits};
+use super::{SearchEngine, SearchHit};
+
+// Parámetros estándar de BM25
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+// El título pesa más que el resumen: sus términos cuentan doble en el tf.
+const TITLE_WEIGHT: u32 = 2;
+
+// Un término corregido (typo) puntúa menos que uno exacto
+const FUZZY_WEIGHT: f32 = 0.5;
+
+/// Normaliza y separa un texto en términos: minúsculas, sin acentos
+/// ("Éxodo" -> "exodo") y cortando en todo lo que no sea alfanumérico.
+pub fn tokenize(text: &str) -> Vec<String> {
+    fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Índice invertido de una colección (libros o reseñas).
+#[derive(Default)]
+struct Corpus {
+    // término -> (doc id -> frecuencia ponderada)
+    postings: HashMap<String, HashMap<String, u32>>,
+    // doc id -> (longitud del documento, términos distintos para poder borrarlo)
+    docs: HashMap<String, (u32, Vec<String>)>,
+    total_len: u64,
+}
+
+impl Corpus {
+    fn upsert(&mut self, id: &str, fields: &[(&str, u32)]) {
+        self.remove(id);
+
+        let mut tf = HashMap::<String, u32>::new();
+        let mut len = 0u32;
+        for (text, weight) in fields {
+            for term in tokenize(text) {
+                *tf.entry(term).or_default() += weight;
+                len += weight;
+            }
+        }
+
+        for (term, freq) in &tf {
+            self.postings.entry(term.clone()).or_default().insert(id.to_string(), *freq);
+        }
+        self.docs.insert(id.to_string(), (len, tf.into_keys().collect()));
+        self.total_len += len as u64;
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some((len, terms)) = self.docs.remove(id) else { return };
+        self.total_len -= len as u64;
+        for term in terms {
+            if let Some(list) = self.postings.get_mut(&term) {
+                list.remove(id);
+                if list.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    // Postings de un término que no está en el vocabulario: la unión de los
+    // términos a distancia de edición tolerable (tf = el mayor de ellos).
+    fn fuzzy_postings(&self, term: &str) -> Option<HashMap<String, u32>> {
+        let max = max_edits(term);
+        if max == 0 {
+            return None;
+        }
+        let mut merged = HashMap::<String, u32>::new();
+        for (candidate, list) in &self.postings {
+            if edit_distance(term, candidate, max).is_none() {
+                continue;
+            }
+            for (id, tf) in list {
+                let e = merged.entry(id.clone()).or_default();
+                *e = (*e).max(*tf);
+            }
+        }
+        Some(merged).filter(|m| !m.is_empty())
+    }
+
+    /// Búsqueda conjuntiva (todos los términos deben aparecer), ordenada por BM25.
+    /// Un término que no existe en el índice se corrige por distancia de edición.
+    fn search(&self, q: &str, limit: usize) -> Vec<SearchHit> {
+        let mut terms = tokenize(q);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() || self.docs.is_empty() {
+            return vec![];
+        }
+
+        // (postings, peso): prestadas si el término existe, calculadas si es un typo
+        let mut lists: Vec<(Cow<HashMap<String, u32>>, f32)> = Vec::with_capacity(terms.len());
+        for term in &terms {
+            match self.postings.get(term) {
+                Some(list) => lists.push((Cow::Borrowed(list), 1.0)),
+                None => match self.fuzzy_postings(term) {
+                    Some(list) => lists.push((Cow::Owned(list), FUZZY_WEIGHT)),
+                    None => return vec![],
+                },
+            }
+        }
+        // Recorremos la lista más corta y comprobamos el resto
+        lists.sort_by_key(|(l, _)| l.len());
+
+        let n = self.docs.len() as f32;
+        let avg_len = self.total_len as f32 / n;
+
+        let mut hits: Vec<SearchHit> = lists[0]
+            .0
+            .keys()
+            .filter(|id| lists[1..].iter().all(|(l, _)| l.contains_key(*id)))
+            .map(|id| {
+                let doc_len = self.docs.get(id).map(|d| d.0).unwrap_or(0) as f32;
+                let score = lists
+                    .iter()
+                    .map(|(l, weight)| {
+                        let df = l.len() as f32;
+                        let tf = l[id] as f32;
+                        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        weight * idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * doc_len / avg_len))
+                    })
+                    .sum();
+                SearchHit { id: id.clone(), score }
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Motor de búsqueda en memoria: índice invertido + BM25.
+/// Se llena desde Mongo al arrancar y lo mantienen los handlers de libros y reseñas.
+#[derive(Default)]
+pub struct MemorySearch {
+    books: RwLock<Corpus>,
+    reviews: RwLock<Corpus>,
+}
+
+impl MemorySearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// Un lock envenenado (panic a mitad de una escritura) se reporta como error
+fn poisoned<T>(_: T) -> anyhow::Error {
+    anyhow!("search index lock poisoned")
+}
+
+#[async_trait]
+impl SearchEngine for MemorySearch {
+    fn enabled(&self) -> bool { true }
+
+    async fn index_book(&self, book_id: &str, title: &str, summary: &str) -> anyhow::Result<()> {
+        self.books
+            .write()
+            .map_err(poisoned)?
+            .upsert(book_id, &[(title, TITLE_WEIGHT), (summary, 1)]);
+        Ok(())
+    }
+
+    async fn delete_book(&self, book_id: &str) -> anyhow::Result<()> {
+        self.books.write().map_err(poisoned)?.remove(book_id);
+        Ok(())
+    }
+
+    async fn index_review(&self, review_id: &str, _book_id: &str, content: &str, _score: i32) -> anyhow::Result<()> {
+        self.reviews.write().map_err(poisoned)?.upsert(review_id, &[(content, 1)]);
+        Ok(())
+    }
+
+    async fn delete_review(&self, review_id: &str) -> anyhow::Result<()> {
+        self.reviews.write().map_err(poisoned)?.remove(review_id);
+        Ok(())
+    }
+
+    async fn search_books(&self, q: &str, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(self.books.read().map_err(poisoned)?.search(q, limit))
+    }
+
+    async fn search_reviews(&self, q: &str, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        Ok(self.reviews.read().map_err(poisoned)?.search(q, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.id.as_str()).collect()
+    }
+
+    fn books() -> Corpus {
+        let mut c = Corpus::default();
+        c.upsert("rayuela", &[("Rayuela", TITLE_WEIGHT), ("Novela experimental en París", 1)]);
+        c.upsert("cien", &[("Cien años de soledad", TITLE_WEIGHT), ("La novela de Macondo", 1)]);
+        c.upsert("ficciones", &[("Ficciones", TITLE_WEIGHT), ("Cuentos de Borges", 1)]);
+        c
+    }
+
+    #[test]
+    fn tokenize_folds_case_and_accents() {
+        assert_eq!(tokenize("Éxodo, AÑOS y París!"), ["exodo", "anos", "y", "paris"]);
+    }
+
+    #[test]
+    fn ranks_by_bm25_and_requires_every_term() {
+        let mut c = books();
+        c.upsert("macondo", &[("Macondo", TITLE_WEIGHT), ("Novela sobre Macondo", 1)]);
+
+        // el término en el título (y repetido) pesa más que en el resumen
+        assert_eq!(ids(&c.search("macondo", 10)), ["macondo", "cien"]);
+        // conjuntiva: "novela paris" solo en Rayuela
+        assert_eq!(ids(&c.search("novela paris", 10)), ["rayuela"]);
+        assert_eq!(ids(&c.search("novela", 1)).len(), 1);
+        assert!(c.search("borges macondo", 10).is_empty());
+    }
+
+    #[test]
+    fn matches_without_accents_either_way() {
+        let c = books();
+        assert_eq!(ids(&c.search("paris", 10)), ["rayuela"]);
+        assert_eq!(ids(&c.search("AÑOS", 10)), ["cien"]);
+        assert_eq!(ids(&c.search("anos", 10)), ["cien"]);
+    }
+
+    #[test]
+    fn removed_and_replaced_documents_leave_the_index() {
+        let mut c = books();
+        c.remove("cien");
+        assert!(c.search("macondo", 10).is_empty());
+        assert!(!c.postings.contains_key("macondo"));
+
+        // reindexar reemplaza los términos anteriores
+        c.upsert("rayuela", &[("Rayuela", TITLE_WEIGHT), ("Horacio Oliveira", 1)]);
+        assert!(c.search("paris", 10).is_empty());
+        assert_eq!(ids(&c.search("oliveira", 10)), ["rayuela"]);
+
+        c.remove("rayuela");
+        c.remove("ficciones");
+        assert_eq!(c.total_len, 0);
+        assert!(c.postings.is_empty());
+    }
+
+    #[test]
+    fn unknown_terms_fall_back_to_typo_matches_with_a_lower_score() {
+        let c = books();
+        let exact = c.search("ficciones", 10);
+        let typo = c.search("ficcoines", 10);
+        assert_eq!(ids(&typo), ["ficciones"]);
+        assert!(typo[0].score < exact[0].score);
+
+        // dos ediciones solo a partir de 8 letras; las palabras cortas no se corrigen
+        assert_eq!(ids(&c.search("fcicoines", 10)), ["ficciones"]);
+        assert_eq!(ids(&c.search("ryuela", 10)), ["rayuela"]);
+        assert!(c.search("sol", 10).is_empty());
+        assert!(c.search("zzzzzz", 10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_engine_indexes_and_deletes_books_and_reviews() {
+        let engine = MemorySearch::new();
+        engine.index_book("b1", "Rayuela", "Novela experimental").await.unwrap();
+        engine.index_review("r1", "b1", "Una novela inolvidable", 5).await.unwrap();
+        assert_eq!(ids(&engine.search_books("novela", 10).await.unwrap()), ["b1"]);
+        assert_eq!(ids(&engine.search_reviews("novela", 10).await.unwrap()), ["r1"]);
+
+        engine.delete_book("b1").await.unwrap();
+        engine.delete_review("r1").await.unwrap();
+        assert!(engine.search_books("novela", 10).await.unwrap().is_empty());
+        assert!(engine.search_reviews("novela", 10).await.unwrap().is_empty());
+    }
+}
@@ -1,20 +1,74 @@
+use async_trait::async_trait;
+
 pub struct SearchHit {
     pub id: String,     // ej: book_id o review_id
     pub score: f32,
 }
 
+// Documentos para la indexación en lote (carga inicial)
+pub struct BookDoc {
+    pub id: String,
+    pub title: String,
+    pub summary: String,
+}
+
+pub struct ReviewDoc {
+    pub id: String,
+    pub book_id: String,
+    pub content: String,
+    pub score: i32,
+}
+
+// Todas las operaciones son async y devuelven Result: un motor remoto puede
+// fallar y quien llama decide (loguear, degradar a Mongo, etc.).
+#[async_trait]
 pub trait SearchEngine: Send + Sync {
-    // Indexación mínima que luego conectaremos a ES/OpenSearch
-    fn index_book(&self, _book_id: &str, _title: &str, _summary: &str) {}
-    fn delete_book(&self, _book_id: &str) {}
+    // false => AppState usa el fallback con $regex sobre Mongo
+    fn enabled(&self) -> bool { false }
+
+    async fn index_book(&self, _book_id: &str, _title: &str, _summary: &str) -> anyhow::Result<()> { Ok(()) }
+    async fn delete_book(&self, _book_id: &str) -> anyhow::Result<()> { Ok(()) }
+
+    async fn index_review(&self, _review_id: &str, _book_id: &str, _content: &str, _score: i32) -> anyhow::Result<()> { Ok(()) }
+    async fn delete_review(&self, _review_id: &str) -> anyhow::Result<()> { Ok(()) }
 
-    fn index_review(&self, _review_id: &str, _book_id: &str, _content: &str, _score: i32) {}
-    fn delete_review(&self, _review_id: &str) {}
+    // En lote: por defecto uno a uno; los motores remotos lo sobrescriben (_bulk)
+    async fn index_books(&self, books: &[BookDoc]) -> anyhow::Result<()> {
+        for b in books {
+            self.index_book(&b.id, &b.title, &b.summary).await?;
+        }
+        Ok(())
+    }
+    async fn index_reviews(&self, reviews: &[ReviewDoc]) -> anyhow::Result<()> {
+        for r in reviews {
+            self.index_review(&r.id, &r.book_id, &r.content, r.score).await?;
+        }
+        Ok(())
+    }
 
-    // Búsqueda por texto (libros por ahora)
-    fn search_books(&self, _q: &str, _limit: usize) -> Vec<SearchHit> { vec![] }
+    // Búsqueda por texto; los autores se buscan siempre en Mongo
+    async fn search_books(&self, _q: &str, _limit: usize) -> anyhow::Result<Vec<SearchHit>> { Ok(vec![]) }
+    async fn search_reviews(&self, _q: &str, _limit: usize) -> anyhow::Result<Vec<SearchHit>> { Ok(vec![]) }
 }
 
 // No-op: no indexa ni devuelve resultados
 pub struct NoopSearch;
-impl SearchEngine for NoopSearch {}
\ No newline at end of file
+
+#[async_trait]
+impl SearchEngine for NoopSearch {}
+
+// Sintaxis de la caja de búsqueda y modos (texto / prefijo)
+pub mod query;
+
+// Filtros y conteos por facetas de la búsqueda de libros
+pub mod facets;
+
+// Fragmentos con las coincidencias marcadas
+pub mod highlight;
+
+// Índice invertido en memoria (BM25)
+pub mod memory;
+
+// Adaptador HTTP para OpenSearch / Elasticsearch
+#[cfg(feature = "search-opensearch")]
+pub mod opensearch;
@@ -0,0 +1,387 @@
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::{BookDoc, ReviewDoc, SearchEngine, SearchHit};
+
+// Analizador común: minúsculas + plegado de acentos (igual que el índice en memoria)
+fn index_settings() -> Value {
+    json!({
+        "analysis": {
+            "analyzer": {
+                "folding": {
+                    "type": "custom",
+                    "tokenizer": "standard",
+                    "filter": ["lowercase", "asciifolding"]
+                }
+            }
+        }
+    })
+}
+
+fn books_mapping() -> Value {
+    json!({
+        "settings": index_settings(),
+        "mappings": {
+            "properties": {
+                "title":   { "type": "text", "analyzer": "folding" },
+                "summary": { "type": "text", "analyzer": "folding" }
+            }
+        }
+    })
+}
+
+fn reviews_mapping() -> Value {
+    json!({
+        "settings": index_settings(),
+        "mappings": {
+            "properties": {
+                "book_id": { "type": "keyword" },
+                "content": { "type": "text", "analyzer": "folding" },
+                "score":   { "type": "integer" }
+            }
+        }
+    })
+}
+
+/// Motor de búsqueda sobre la API REST de OpenSearch/Elasticsearch.
+/// `base_url` es la raíz del cluster (p.ej. http://opensearch:9200), así que
+/// también puede apuntar a un servidor HTTP de prueba.
+pub struct OpenSearch {
+    client: Client,
+    base_url: String,
+    books_index: String,
+    reviews_index: String,
+}
+
+impl OpenSearch {
+    pub async fn new(base_url: &str, index_prefix: &str) -> anyhow::Result<Self> {
+        let engine = Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            books_index: format!("{index_prefix}_books"),
+            reviews_index: format!("{index_prefix}_reviews"),
+        };
+        engine.ensure_index(&engine.books_index, books_mapping()).await?;
+        engine.ensure_index(&engine.reviews_index, reviews_mapping()).await?;
+        Ok(engine)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    // Crea el índice con su mapping solo si no existe
+    async fn ensure_index(&self, index: &str, mapping: Value) -> anyhow::Result<()> {
+        let res = self.client.head(self.url(index)).send().await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            let res = self.client.put(self.url(index)).json(&mapping).send().await?;
+            if !res.status().is_success() {
+                anyhow::bail!("creating index {index}: {} {}", res.status(), res.text().await.unwrap_or_default());
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> anyhow::Result<Value> {
+        let mut req = self.client.request(method, self.url(path));
+        if let Some(b) = body {
+            req = req.json(&b);
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        // un 404 aquí es un índice que falta o mal nombrado: error, no "sin resultados"
+        if !status.is_success() {
+            anyhow::bail!("{path}: {status} {}", res.text().await.unwrap_or_default());
+        }
+        Ok(res.json().await?)
+    }
+
+    // Borrar un documento que ya no existe no es un error para nosotros
+    async fn delete_doc(&self, index: &str, id: &str) -> anyhow::Result<()> {
+        let path = format!("{index}/_doc/{id}");
+        let res = self.client.delete(self.url(&path)).send().await?;
+        let status = res.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            anyhow::bail!("{path}: {status} {}", res.text().await.unwrap_or_default());
+        }
+        Ok(())
+    }
+
+    // Solo ids y score: los documentos se hidratan desde Mongo
+    async fn search(&self, index: &str, query: Value, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        let body = json!({ "size": limit, "_source": false, "query": query });
+        let path = format!("{index}/_search");
+        let res = self.send(Method::POST, &path, Some(body)).await?;
+
+        let hits = res["hits"]["hits"]
+            .as_array()
+            .map(|hits| {
+                hits.iter()
+                    .filter_map(|h| {
+                        Some(SearchHit {
+                            id: h["_id"].as_str()?.to_string(),
+                            score: h["_score"].as_f64().unwrap_or(0.0) as f32,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(hits)
+    }
+
+    // _bulk usa NDJSON: una línea de acción + una de documento por item
+    async fn bulk(&self, lines: Vec<Value>) -> anyhow::Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut body = String::new();
+        for l in lines {
+            body.push_str(&l.to_string());
+            body.push('\n');
+        }
+        let res = self
+            .client
+            .post(self.url("_bulk"))
+            .header("Content-Type", "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            anyhow::bail!("_bulk: {}", res.status());
+        }
+        let out: Value = res.json().await?;
+        if out["errors"].as_bool().unwrap_or(false) {
+            anyhow::bail!("_bulk reported item errors");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for OpenSearch {
+    fn enabled(&self) -> bool { true }
+
+    async fn index_book(&self, book_id: &str, title: &str, summary: &str) -> anyhow::Result<()> {
+        let path = format!("{}/_doc/{}", self.books_index, book_id);
+        let body = json!({ "title": title, "summary": summary });
+        self.send(Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    async fn delete_book(&self, book_id: &str) -> anyhow::Result<()> {
+        self.delete_doc(&self.books_index, book_id).await
+    }
+
+    async fn index_review(&self, review_id: &str, book_id: &str, content: &str, score: i32) -> anyhow::Result<()> {
+        let path = format!("{}/_doc/{}", self.reviews_index, review_id);
+        let body = json!({ "book_id": book_id, "content": content, "score": score });
+        self.send(Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    async fn delete_review(&self, review_id: &str) -> anyhow::Result<()> {
+        self.delete_doc(&self.reviews_index, review_id).await
+    }
+
+    async fn index_books(&self, books: &[BookDoc]) -> anyhow::Result<()> {
+        let mut lines = Vec::with_capacity(books.len() * 2);
+        for b in books {
+            lines.push(json!({ "index": { "_index": self.books_index, "_id": b.id } }));
+            lines.push(json!({ "title": b.title, "summary": b.summary }));
+        }
+        self.bulk(lines).await
+    }
+
+    async fn index_reviews(&self, reviews: &[ReviewDoc]) -> anyhow::Result<()> {
+        let mut lines = Vec::with_capacity(reviews.len() * 2);
+        for r in reviews {
+            lines.push(json!({ "index": { "_index": self.reviews_index, "_id": r.id } }));
+            lines.push(json!({ "book_id": r.book_id, "content": r.content, "score": r.score }));
+        }
+        self.bulk(lines).await
+    }
+
+    async fn search_books(&self, q: &str, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        let query = json!({
+            "multi_match": {
+                "query": q,
+                "fields": ["title^2", "summary"],
+                "operator": "and",
+                "fuzziness": "AUTO"
+            }
+        });
+        self.search(&self.books_index, query, limit).await
+    }
+
+    async fn search_reviews(&self, q: &str, limit: usize) -> anyhow::Result<Vec<SearchHit>> {
+        let query = json!({
+            "match": {
+                "content": { "query": q, "operator": "and", "fuzziness": "AUTO" }
+            }
+        });
+        self.search(&self.reviews_index, query, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::OpenSearch;
+    use crate::search::{BookDoc, SearchEngine};
+
+    // Lo que recibió el servidor de prueba: (método, ruta, cuerpo)
+    type Seen = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    // Servidor HTTP mínimo en lugar del cluster: responde (status, cuerpo) según
+    // método y ruta (404 para lo demás) y guarda cada petición.
+    async fn stub(routes: Vec<(&'static str, &'static str, u16, Value)>) -> (String, Seen) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let seen: Seen = Arc::default();
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let head_end = loop {
+                    let n = conn.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+                let length = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                while buf.len() < head_end + length {
+                    let n = conn.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                let mut first = head.split_whitespace();
+                let (method, path) = (first.next().unwrap().to_string(), first.next().unwrap().to_string());
+                let body = String::from_utf8_lossy(&buf[head_end..head_end + length]).to_string();
+
+                let (status, reply) = routes
+                    .iter()
+                    .find(|(m, p, _, _)| *m == method && *p == path)
+                    .map(|(_, _, s, b)| (*s, b.to_string()))
+                    .unwrap_or((404, "{}".into()));
+                let reply = if method == "HEAD" { String::new() } else { reply };
+                log.lock().unwrap().push((method, path, body));
+                let res = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                conn.write_all(res.as_bytes()).await.unwrap();
+            }
+        });
+        (base, seen)
+    }
+
+    fn requests(seen: &Seen, method: &str, path: &str) -> Vec<String> {
+        seen.lock().unwrap().iter().filter(|(m, p, _)| m == method && p == path).map(|(_, _, b)| b.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn creates_missing_indices_with_their_mapping() {
+        let (base, seen) = stub(vec![
+            ("PUT", "/t_books", 200, json!({"acknowledged": true})),
+            ("PUT", "/t_reviews", 200, json!({"acknowledged": true})),
+        ])
+        .await;
+        OpenSearch::new(&base, "t").await.unwrap();
+
+        let books: Value = serde_json::from_str(&requests(&seen, "PUT", "/t_books")[0]).unwrap();
+        assert_eq!(books["mappings"]["properties"]["title"]["analyzer"], "folding");
+        assert_eq!(books["settings"]["analysis"]["analyzer"]["folding"]["filter"], json!(["lowercase", "asciifolding"]));
+        let reviews: Value = serde_json::from_str(&requests(&seen, "PUT", "/t_reviews")[0]).unwrap();
+        assert_eq!(reviews["mappings"]["properties"]["book_id"]["type"], "keyword");
+    }
+
+    #[tokio::test]
+    async fn leaves_existing_indices_alone() {
+        let (base, seen) = stub(vec![("HEAD", "/t_books", 200, json!({})), ("HEAD", "/t_reviews", 200, json!({}))]).await;
+        OpenSearch::new(&base, "t").await.unwrap();
+        assert!(seen.lock().unwrap().iter().all(|(m, _, _)| m == "HEAD"));
+    }
+
+    #[tokio::test]
+    async fn bulk_sends_an_action_and_a_document_per_book() {
+        let (base, seen) = stub(vec![
+            ("HEAD", "/t_books", 200, json!({})),
+            ("HEAD", "/t_reviews", 200, json!({})),
+            ("POST", "/_bulk", 200, json!({"errors": false, "items": []})),
+        ])
+        .await;
+        let engine = OpenSearch::new(&base, "t").await.unwrap();
+        let books = vec![
+            BookDoc { id: "b1".into(), title: "Uno".into(), summary: "primero".into() },
+            BookDoc { id: "b2".into(), title: "Dos".into(), summary: "".into() },
+        ];
+        engine.index_books(&books).await.unwrap();
+
+        let body = &requests(&seen, "POST", "/_bulk")[0];
+        assert!(body.ends_with('\n'));
+        let lines: Vec<Value> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], json!({"index": {"_index": "t_books", "_id": "b1"}}));
+        assert_eq!(lines[1], json!({"title": "Uno", "summary": "primero"}));
+        assert_eq!(lines[2]["index"]["_id"], "b2");
+    }
+
+    #[tokio::test]
+    async fn bulk_item_errors_are_errors() {
+        let (base, _) = stub(vec![
+            ("HEAD", "/t_books", 200, json!({})),
+            ("HEAD", "/t_reviews", 200, json!({})),
+            ("POST", "/_bulk", 200, json!({"errors": true, "items": []})),
+        ])
+        .await;
+        let engine = OpenSearch::new(&base, "t").await.unwrap();
+        let books = vec![BookDoc { id: "b1".into(), title: "Uno".into(), summary: "".into() }];
+        assert!(engine.index_books(&books).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn search_sends_multi_match_and_parses_hits() {
+        let (base, seen) = stub(vec![
+            ("HEAD", "/t_books", 200, json!({})),
+            ("HEAD", "/t_reviews", 200, json!({})),
+            ("POST", "/t_books/_search", 200, json!({
+                "hits": { "hits": [
+                    { "_id": "b1", "_score": 2.5 },
+                    { "_id": "b2", "_score": 1.0 },
+                    { "_score": 0.5 }
+                ]}
+            })),
+        ])
+        .await;
+        let engine = OpenSearch::new(&base, "t").await.unwrap();
+        let hits = engine.search_books("gabo", 5).await.unwrap();
+        assert_eq!(hits.iter().map(|h| (h.id.as_str(), h.score)).collect::<Vec<_>>(), vec![("b1", 2.5), ("b2", 1.0)]);
+
+        let sent: Value = serde_json::from_str(&requests(&seen, "POST", "/t_books/_search")[0]).unwrap();
+        assert_eq!(sent["size"], 5);
+        assert_eq!(sent["query"]["multi_match"]["query"], "gabo");
+        assert_eq!(sent["query"]["multi_match"]["fields"], json!(["title^2", "summary"]));
+    }
+
+    #[tokio::test]
+    async fn a_missing_index_is_an_error_but_a_missing_document_is_not() {
+        let (base, _) = stub(vec![("HEAD", "/t_books", 200, json!({})), ("HEAD", "/t_reviews", 200, json!({}))]).await;
+        let engine = OpenSearch::new(&base, "t").await.unwrap();
+        // el stub contesta 404 a todo lo demás
+        assert!(engine.search_books("gabo", 5).await.is_err());
+        assert!(engine.index_book("b1", "Uno", "").await.is_err());
+        engine.delete_book("b1").await.unwrap();
+        engine.delete_review("r1").await.unwrap();
+    }
+}
@@ -0,0 +1,625 @@
+// Parseo de la caja de búsqueda, compartido por los distintos modos.
+// Sintaxis (la misma que entiende $text de Mongo):
+//   palabra         término suelto
+//   "dos palabras"  frase exacta
+//   -palabra        excluir
+// Además construye patrones $regex seguros a partir de texto del usuario.
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    // $text con ranking; si no hay resultados, cae a prefijo/regex
+    Auto,
+    // solo $text (stemming según idioma)
+    Text,
+    // búsqueda antigua por $regex (coincidencias parciales / prefijos)
+    Prefix,
+}
+
+impl SearchMode {
+    pub fn parse(s: Option<&str>) -> Self {
+        match s.map(str::trim) {
+            Some("text") => Self::Text,
+            Some("prefix") => Self::Prefix,
+            _ => Self::Auto,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Text => "text",
+            Self::Prefix => "prefix",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+impl ParsedQuery {
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+}
+
+pub fn parse(q: &str) -> ParsedQuery {
+    let mut out = ParsedQuery::default();
+    let mut rest = q.trim();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('"') {
+            // frase hasta la próxima comilla (o hasta el final si no se cerró)
+            let (phrase, tail) = after.split_once('"').unwrap_or((after, ""));
+            let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !phrase.is_empty() {
+                out.phrases.push(phrase);
+            }
+            rest = tail.trim_start();
+            continue;
+        }
+
+        let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match word.strip_prefix('-') {
+            Some(neg) if !neg.is_empty() => out.excluded.push(neg.to_string()),
+            Some(_) => {}
+            None => out.terms.push(word.to_string()),
+        }
+        rest = tail.trim_start();
+    }
+
+    out
+}
+
+/* ===== $regex seguro a partir de texto del usuario ===== */
+
+// Más que suficiente para una caja de búsqueda; acota el coste en Mongo.
+pub const MAX_PATTERN_LEN: usize = 100;
+// Tope de términos por búsqueda: cada uno es un $regex más por documento.
+pub const MAX_TERMS: usize = 8;
+// Cuantificadores sin tope (*, +, {n,}) en una regex avanzada: cada uno multiplica
+// el backtracking posible (.*.*.*x es polinómico de grado alto).
+pub const MAX_UNBOUNDED_QUANTIFIERS: usize = 2;
+// Cota más alta de un {n,m}: a{1,99999} repite el trabajo del motor otras tantas veces.
+pub const MAX_REPEAT: u32 = 100;
+// Término más largo con patrón de typos (~1.300 caracteres de alternativas)
+pub const MAX_FUZZY_TERM_LEN: usize = 24;
+
+// Escapa los metacaracteres de PCRE: la entrada se busca tal cual.
+pub fn escape_regex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '^' | '$' | '.' | '|' | '?' | '*' | '+' | '(' | ')' | '[' | ']' | '{' | '}' | '-' | '/') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Patrón listo para $regex, de como mucho MAX_PATTERN_LEN caracteres.
+// En modo avanzado se respeta la regex del usuario solo si pasa `is_safe_regex`;
+// si no, se busca como texto literal: el tope se aplica ya escapado (cada
+// metacarácter ocupa dos) y sin partir un escape. None si no queda nada que buscar.
+pub fn regex_pattern(input: &str, advanced: bool) -> Option<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    if advanced {
+        let capped: String = input.chars().take(MAX_PATTERN_LEN).collect();
+        if is_safe_regex(&capped) {
+            return Some(capped);
+        }
+    }
+
+    let mut out = String::new();
+    let mut len = 0;
+    for c in input.chars() {
+        let escaped = escape_regex(c.encode_utf8(&mut [0; 4]));
+        len += escaped.chars().count();
+        if len > MAX_PATTERN_LEN {
+            break;
+        }
+        out.push_str(&escaped);
+    }
+    Some(out)
+}
+
+// Patrón del filtro de un listado (?q=&regex=), o None (todo) si no hay query.
+// Los repos lo aplican sin distinguir mayúsculas.
+pub fn list_pattern(input: Option<&str>, advanced: bool) -> Option<String> {
+    input.and_then(|q| regex_pattern(q, advanced))
+}
+
+// Validación conservadora para el modo avanzado. Rechaza:
+// - sintaxis rota (paréntesis/corchetes sin cerrar, cuantificador sin átomo)
+// - grupos cuantificados que a su vez contienen cuantificadores o alternancias,
+//   p.ej. (a+)+ o (a|aa)*: la fuente típica de backtracking exponencial
+// - backreferences (\1...)
+// - grupos (?...) que no sean (?:...) o el modificador (?i): lookarounds,
+//   recursión (?R), grupos con nombre, condicionales...
+// - más de MAX_UNBOUNDED_QUANTIFIERS cuantificadores sin tope, o un {n,m}
+//   con alguna cota mayor que MAX_REPEAT
+pub fn is_safe_regex(p: &str) -> bool {
+    let mut chars = p.chars().peekable();
+    // por grupo abierto: ¿contiene cuantificador o alternancia?
+    let mut groups: Vec<bool> = Vec::new();
+    // ¿el átomo anterior fue un grupo "arriesgado"?
+    let mut risky_atom = false;
+    let mut has_atom = false;
+    let mut after_quantifier = false;
+    let mut unbounded = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '+' | '?' | '{' => {
+                if after_quantifier {
+                    // a+? (lazy) y a++ (posesivo) son válidos; nada más se encadena
+                    if c == '*' || c == '{' {
+                        return false;
+                    }
+                    after_quantifier = false;
+                    continue;
+                }
+                if !has_atom || (risky_atom && c != '?') {
+                    return false;
+                }
+                let open_ended = match c {
+                    '{' => match skip_braces(&mut chars) {
+                        Some(open_ended) => open_ended,
+                        None => return false,
+                    },
+                    c => c != '?',
+                };
+                if open_ended {
+                    unbounded += 1;
+                    if unbounded > MAX_UNBOUNDED_QUANTIFIERS {
+                        return false;
+                    }
+                }
+                if let Some(g) = groups.last_mut() {
+                    *g = true;
+                }
+                has_atom = false;
+                risky_atom = false;
+                after_quantifier = true;
+                continue;
+            }
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => return false,
+                Some(_) => {}
+                None => return false,
+            },
+            '[' => {
+                chars.next_if_eq(&'^');
+                chars.next_if_eq(&']');
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            chars.next();
+                        }
+                        Some(']') => break,
+                        Some(_) => {}
+                        None => return false,
+                    }
+                }
+            }
+            '(' => {
+                if chars.next_if_eq(&'?').is_some() {
+                    match chars.next() {
+                        Some(':') => {}
+                        // (?i) no es un átomo: no se puede cuantificar
+                        Some('i') if chars.next_if_eq(&')').is_some() => {
+                            has_atom = false;
+                            risky_atom = false;
+                            after_quantifier = false;
+                            continue;
+                        }
+                        _ => return false,
+                    }
+                }
+                groups.push(false);
+                has_atom = false;
+                risky_atom = false;
+                after_quantifier = false;
+                continue;
+            }
+            ')' => {
+                let Some(risky) = groups.pop() else { return false };
+                if risky {
+                    if let Some(parent) = groups.last_mut() {
+                        *parent = true;
+                    }
+                }
+                has_atom = true;
+                risky_atom = risky;
+                after_quantifier = false;
+                continue;
+            }
+            '|' => {
+                if let Some(g) = groups.last_mut() {
+                    *g = true;
+                }
+                has_atom = false;
+                risky_atom = false;
+                after_quantifier = false;
+                continue;
+            }
+            _ => {}
+        }
+        has_atom = true;
+        risky_atom = false;
+        after_quantifier = false;
+    }
+
+    groups.is_empty()
+}
+
+// Consume `n}`, `n,}` o `n,m}` tras un `{`. Some(true) si no tiene tope (`n,}`),
+// None si está mal formado o alguna cota pasa de MAX_REPEAT.
+fn skip_braces(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<bool> {
+    let mut lower: Option<u32> = None;
+    let mut upper: Option<u32> = None;
+    let mut comma = false;
+    for c in chars.by_ref() {
+        match c {
+            '0'..='9' => {
+                let bound = if comma { &mut upper } else { &mut lower };
+                let n = bound.unwrap_or(0) * 10 + c.to_digit(10)?;
+                if n > MAX_REPEAT {
+                    return None;
+                }
+                *bound = Some(n);
+            }
+            ',' if !comma && lower.is_some() => comma = true,
+            '}' if upper.is_some_and(|m| Some(m) < lower) => return None,
+            '}' if lower.is_some() => return Some(comma && upper.is_none()),
+            _ => return None,
+        }
+    }
+    None
+}
+
+// Fragmento de `text` (como mucho `max_chars`) alrededor de la primera
+// coincidencia de algún término o frase; si no la hay (p.ej. coincidió por
+// stemming), el principio del texto.
+pub fn snippet(text: &str, q: &str, max_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        return text.to_string();
+    }
+
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let parsed = parse(q);
+    let hit = parsed
+        .phrases
+        .iter()
+        .chain(parsed.terms.iter())
+        .filter_map(|needle| {
+            let needle: Vec<char> = needle.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+            if needle.is_empty() {
+                return None;
+            }
+            lower.windows(needle.len()).position(|w| w == needle.as_slice())
+        })
+        .min()
+        .unwrap_or(0);
+
+    // un poco de contexto antes de la coincidencia
+    let start = hit.saturating_sub(max_chars / 4).min(chars.len() - max_chars);
+    let end = start + max_chars;
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    out.extend(&chars[start..end]);
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+/* ===== Tolerancia a errores de tipeo ===== */
+
+// Minúsculas y sin acentos ("Éxodo" -> "exodo"): la forma en que se comparan términos
+pub fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Ediciones toleradas según el largo del término: las palabras cortas
+// tienen demasiados vecinos a distancia 1 como para corregirlas.
+pub fn max_edits(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Distancia de edición (Levenshtein + transposición de vecinos) acotada:
+// None si supera `max`.
+pub fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // tres filas: la anterior a la anterior hace falta para la transposición
+    let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        if cur.iter().min().copied().unwrap_or(0) > max {
+            return None;
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    Some(prev[b.len()]).filter(|d| *d <= max)
+}
+
+// $regex que encuentra el término con una edición (letra de más, de menos,
+// cambiada o dos vecinas intercambiadas). Siempre una, aunque `max_edits`
+// admita dos en palabras largas: con dos el patrón crece demasiado.
+// Por cada posición i, `antes.?c?después` cubre la letra c cambiada, borrada o
+// con otra delante; además, una alternativa por par de vecinas intercambiadas.
+// Solo literales y `?`, así que no aplica el tope de MAX_PATTERN_LEN (pensado
+// para la regex del usuario): el patrón crece con el cuadrado del término y lo
+// acota MAX_FUZZY_TERM_LEN. None si el término es corto o pasa de ese largo
+// (se busca tal cual).
+pub fn fuzzy_pattern(term: &str) -> Option<String> {
+    if max_edits(term) == 0 || term.chars().count() > MAX_FUZZY_TERM_LEN {
+        return None;
+    }
+    let chars: Vec<char> = term.chars().collect();
+    let lit = |cs: &[char]| escape_regex(&cs.iter().collect::<String>());
+
+    let mut alternatives = Vec::new();
+    for i in 0..chars.len() {
+        alternatives.push(format!("{}.?{}?{}", lit(&chars[..i]), lit(&chars[i..=i]), lit(&chars[i + 1..])));
+    }
+    for i in 0..chars.len() - 1 {
+        let mut swapped = chars.clone();
+        swapped.swap(i, i + 1);
+        alternatives.push(lit(&swapped));
+    }
+
+    Some(format!("(?:{})", alternatives.join("|")))
+}
+
+// Patrones de una búsqueda por $regex (sin distinguir mayúsculas): cada término
+// y frase tiene que aparecer como texto literal y ningún excluido. Con `fuzzy`
+// cada término suelto admite además una edición.
+#[derive(Debug, Default)]
+pub struct RegexClauses {
+    pub required: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+pub fn regex_clauses(query: &str, fuzzy: bool) -> RegexClauses {
+    let parsed = parse(query);
+    let term_pattern = |term: &String| match fuzzy {
+        true => fuzzy_pattern(term).or_else(|| regex_pattern(term, false)),
+        false => regex_pattern(term, false),
+    };
+
+    RegexClauses {
+        required: parsed
+            .terms
+            .iter()
+            .filter_map(term_pattern)
+            .chain(parsed.phrases.iter().filter_map(|p| regex_pattern(p, false)))
+            .take(MAX_TERMS)
+            .collect(),
+        excluded: parsed.excluded.iter().filter_map(|term| regex_pattern(term, false)).take(MAX_TERMS).collect(),
+    }
+}
+
+// Un motor de búsqueda externo solo recibe palabras (`engine_query`): un
+// "-excluido" lo tomaría como término obligatorio y las comillas se pierden.
+// El repo filtra después sus hits con las frases y los excluidos (`ranked_clauses`).
+pub fn engine_query(query: &str) -> String {
+    let parsed = parse(query);
+    parsed.terms.iter().chain(&parsed.phrases).map(String::as_str).collect::<Vec<_>>().join(" ")
+}
+
+pub fn ranked_clauses(query: &str) -> RegexClauses {
+    let parsed = parse(query);
+    let patterns = |v: &[String]| v.iter().filter_map(|p| regex_pattern(p, false)).take(MAX_TERMS).collect();
+    RegexClauses { required: patterns(&parsed.phrases), excluded: patterns(&parsed.excluded) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_input_matches_itself_literally() {
+        let input = r"a.b*c+(d)[e]{2}|f^g$h\i?j-k/l";
+        let pattern = escape_regex(input);
+        let re = regex::Regex::new(&format!("^{pattern}$")).unwrap();
+        assert!(re.is_match(input));
+        assert!(!re.is_match("axb*c+(d)[e]{2}|f^g$h\\i?j-k/l"));
+    }
+
+    #[test]
+    fn rejects_nested_quantifiers() {
+        for p in ["(a+)+$", "(a*)*", "(a+)*b", "(a|aa)*", "((ab)*c)+", "(?:a+){2,}", "(a{1,3})+"] {
+            assert!(!is_safe_regex(p), "{p}");
+        }
+    }
+
+    #[test]
+    fn rejects_broken_syntax_and_backreferences() {
+        for p in ["[", "[abc", "(", "(a", "a)", "*a", "+", "a**", "a{", "a{,3}", "a{x}", "a{3,1}", "\\", r"(a)\1"] {
+            assert!(!is_safe_regex(p), "{p}");
+        }
+    }
+
+    #[test]
+    fn rejects_special_groups_and_large_repeats() {
+        for p in ["(?R)", "a(?1)", "(?=a)b", "(?!a)b", "(?<=a)b", "(?<n>a)", "(?i:a)", "(?s)a", "(?(1)a|b)", "(*ACCEPT)a", "(?i)+a"] {
+            assert!(!is_safe_regex(p), "{p}");
+        }
+        for p in ["a{1,99999}", "a{101}", "a{101,}", "(?:ab){2,1000}"] {
+            assert!(!is_safe_regex(p), "{p}");
+        }
+        assert!(is_safe_regex("a{100}b{0,100}"));
+    }
+
+    #[test]
+    fn limits_unbounded_quantifiers() {
+        assert!(is_safe_regex(".*foo.*"));
+        assert!(is_safe_regex("a+b{2,}c{1,5}"));
+        assert!(!is_safe_regex(".*.*.*.*.*x"));
+        assert!(!is_safe_regex("a+b+c+"));
+        assert!(!is_safe_regex("a{1,}b*c{2,}"));
+        // lazy / posesivo no suman un cuantificador más
+        assert!(is_safe_regex("a+?b*+"));
+    }
+
+    #[test]
+    fn accepts_ordinary_advanced_patterns() {
+        for p in ["^garc[ií]a$", "(?i)cien a(ñ|n)os", "(?:ab)?c", "colou?r", "[^]x]+", r"\d{4}", "a|b|c"] {
+            assert!(is_safe_regex(p), "{p}");
+        }
+    }
+
+    #[test]
+    fn unsafe_advanced_input_is_searched_literally() {
+        assert_eq!(regex_pattern("(a+)+$", true).unwrap(), escape_regex("(a+)+$"));
+        assert_eq!(regex_pattern("^ab+c$", true).unwrap(), "^ab+c$");
+        assert_eq!(regex_pattern("^ab+c$", false).unwrap(), escape_regex("^ab+c$"));
+        assert_eq!(regex_pattern("   ", true), None);
+    }
+
+    // Todas las variantes a una edición de `term` con letras de `alphabet`
+    fn one_edit_variants(term: &str, alphabet: &str) -> Vec<String> {
+        let chars: Vec<char> = term.chars().collect();
+        let mut out = Vec::new();
+        for i in 0..=chars.len() {
+            for c in alphabet.chars() {
+                let mut v = chars.clone();
+                v.insert(i, c);
+                out.push(v.into_iter().collect());
+                if i < chars.len() {
+                    let mut v = chars.clone();
+                    v[i] = c;
+                    out.push(v.into_iter().collect());
+                }
+            }
+            if i < chars.len() {
+                let mut v = chars.clone();
+                v.remove(i);
+                out.push(v.into_iter().collect());
+            }
+            if i + 1 < chars.len() {
+                let mut v = chars.clone();
+                v.swap(i, i + 1);
+                out.push(v.into_iter().collect());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn edit_distance_counts_edits_up_to_the_bound() {
+        assert_eq!(edit_distance("novela", "novela", 2), Some(0));
+        assert_eq!(edit_distance("novela", "novla", 2), Some(1));
+        assert_eq!(edit_distance("novela", "novelas", 2), Some(1));
+        assert_eq!(edit_distance("novela", "nobela", 2), Some(1));
+        // vecinas intercambiadas: una edición, no dos
+        assert_eq!(edit_distance("novela", "onvela", 2), Some(1));
+        assert_eq!(edit_distance("novela", "nvoela", 1), Some(1));
+        assert_eq!(edit_distance("novela", "nobelas", 2), Some(2));
+        assert_eq!(edit_distance("novela", "nobelas", 1), None);
+        assert_eq!(edit_distance("novela", "nov", 2), None);
+        assert_eq!(edit_distance("", "ab", 2), Some(2));
+        assert_eq!(edit_distance("año", "ano", 1), Some(1));
+    }
+
+    #[test]
+    fn max_edits_grows_with_the_term() {
+        assert_eq!(max_edits("sol"), 0);
+        assert_eq!(max_edits("gabo"), 1);
+        assert_eq!(max_edits("macondo"), 1);
+        assert_eq!(max_edits("cervantes"), 2);
+    }
+
+    #[test]
+    fn fuzzy_pattern_matches_every_one_edit_variant_and_nothing_further() {
+        for term in ["gabo", "novela", "garcía", "macondo", "cortázar", "literatura"] {
+            let pattern = fuzzy_pattern(term).unwrap();
+            // sin anclar, como lo usa $regex: basta con que aparezca en el texto
+            let re = regex::Regex::new(&pattern).unwrap();
+            for variant in one_edit_variants(term, "aexí") {
+                assert!(re.is_match(&variant), "{term}: {variant}");
+            }
+            let two_edits: String = term.chars().rev().collect();
+            assert!(!re.is_match(&two_edits), "{term}: {two_edits}");
+            assert!(!re.is_match(&format!("xx{}", &term[2..])), "{term}");
+        }
+    }
+
+    #[test]
+    fn fuzzy_pattern_escapes_the_term() {
+        let re = regex::Regex::new(&fuzzy_pattern("c++ok").unwrap()).unwrap();
+        assert!(re.is_match("c+ok"));
+        assert!(!re.is_match("cccccok"));
+    }
+
+    #[test]
+    fn fuzzy_pattern_skips_short_terms_and_respects_the_length_cap() {
+        assert_eq!(fuzzy_pattern("sol"), None);
+        // términos largos (7+) también: un typo en una palabra de 7 a 10 letras
+        for (term, typo) in [("olivera", "Horacio Oliveira"), ("exignete", "una lectura exigente"), ("literatrua", "Literatura argentina")] {
+            let re = regex::RegexBuilder::new(&fuzzy_pattern(term).unwrap()).case_insensitive(true).build().unwrap();
+            assert!(re.is_match(typo), "{term}: {typo}");
+        }
+        let longest = "a".repeat(MAX_FUZZY_TERM_LEN);
+        assert!(fuzzy_pattern(&longest).unwrap().chars().count() < 1_500);
+        assert_eq!(fuzzy_pattern(&format!("{longest}a")), None);
+    }
+
+    #[test]
+    fn caps_the_pattern_length() {
+        let long = "a".repeat(MAX_PATTERN_LEN * 5);
+        assert_eq!(regex_pattern(&long, false).unwrap().chars().count(), MAX_PATTERN_LEN);
+        // el tope cuenta el patrón escapado, sin dejar una barra suelta al final
+        let dots = regex_pattern(&".".repeat(MAX_PATTERN_LEN), false).unwrap();
+        assert_eq!(dots, r"\.".repeat(MAX_PATTERN_LEN / 2));
+        let odd = regex_pattern(&format!("a{}", ".".repeat(MAX_PATTERN_LEN)), false).unwrap();
+        assert_eq!(odd.chars().count(), MAX_PATTERN_LEN - 1);
+        assert!(!odd.ends_with('\\'));
+        // el tope se aplica antes de validar: lo que sobra no puede colar nada
+        let tail = format!("{}(a+)+", "a".repeat(MAX_PATTERN_LEN));
+        assert_eq!(regex_pattern(&tail, true).unwrap(), "a".repeat(MAX_PATTERN_LEN));
+    }
+
+    #[test]
+    fn the_engine_gets_words_and_the_repo_phrases_and_exclusions() {
+        assert_eq!(engine_query(r#"novela "familia buendía" -macondo"#), "novela familia buendía");
+        let clauses = ranked_clauses(r#"novela "familia buendía" -macondo"#);
+        assert_eq!(clauses.required, vec![escape_regex("familia buendía")]);
+        assert_eq!(clauses.excluded, vec!["macondo".to_string()]);
+    }
+}
@@ -0,0 +1,64 @@
+// La app entera sobre MemoryRepo (MONGO_URI=memory://), sin Mongo ni Redis
+#![allow(dead_code)]
+
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use serde_json::Value;
+
+use bookreview::config::AppConfig;
+use bookreview::{app, db};
+
+pub async fn client() -> Client {
+    client_with_search(None).await
+}
+
+// Con `Some("memory://")`, la búsqueda pasa por el motor en memoria (MemorySearch)
+pub async fn client_with_search(search_url: Option<&str>) -> Client {
+    Client::tracked(app(db::init_state(config(search_url)).await)).await.expect("valid rocket instance")
+}
+
+pub fn config(search_url: Option<&str>) -> AppConfig {
+    let mut cfg = AppConfig::from_env();
+    cfg.mongo_uri = "memory://".into();
+    cfg.cache_url = Some("memory://".into());
+    cfg.cache_warmup = false;
+    cfg.search_url = search_url.map(str::to_string);
+    cfg
+}
+
+pub async fn get_json(client: &Client, uri: &str) -> (Status, Value) {
+    let res = client.get(uri).dispatch().await;
+    (res.status(), res.into_json().await.unwrap_or(Value::Null))
+}
+
+pub async fn post_json(client: &Client, uri: &str, body: Value) -> (Status, Value) {
+    let res = client.post(uri).header(ContentType::JSON).body(body.to_string()).dispatch().await;
+    (res.status(), res.into_json().await.unwrap_or(Value::Null))
+}
+
+pub async fn put_json(client: &Client, uri: &str, body: Value) -> (Status, Value) {
+    let res = client.put(uri).header(ContentType::JSON).body(body.to_string()).dispatch().await;
+    (res.status(), res.into_json().await.unwrap_or(Value::Null))
+}
+
+pub async fn delete(client: &Client, uri: &str) -> Status {
+    client.delete(uri).dispatch().await.status()
+}
+
+// POST de un formulario de la UI (responde con un redirect)
+pub async fn post_form(client: &Client, uri: &str, body: &str) -> Status {
+    client.post(uri).header(ContentType::Form).body(body).dispatch().await.status()
+}
+
+pub async fn get_html(client: &Client, uri: &str) -> String {
+    let res = client.get(uri).dispatch().await;
+    assert_eq!(res.status(), Status::Ok, "GET {uri}");
+    res.into_string().await.unwrap_or_default()
+}
+
+// Crea por la API y devuelve el id
+pub async fn create(client: &Client, resource: &str, body: Value) -> String {
+    let (status, dto) = post_json(client, &format!("/api/v1/{resource}"), body).await;
+    assert_eq!(status, Status::Created, "POST /api/v1/{resource}: {dto}");
+    dto["id"].as_str().expect("id in the created DTO").to_string()
+}
@@ -0,0 +1,144 @@
+// CRUD y cascadas de la API JSON y la UI contra MemoryRepo
+mod common;
+
+use rocket::http::{ContentType, Status};
+use serde_json::json;
+
+use common::*;
+
+#[rocket::async_test]
+async fn author_book_review_crud() {
+    let client = client().await;
+
+    let author = create(&client, "authors", json!({ "name": "Julio Cortázar", "country": "Argentina", "date_of_birth": "1914-08-26" })).await;
+    let (status, dto) = get_json(&client, &format!("/api/v1/authors/{author}")).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(dto["name"], "Julio Cortázar");
+    assert_eq!(dto["date_of_birth"], "1914-08-26");
+
+    let (status, dto) = put_json(&client, &format!("/api/v1/authors/{author}"), json!({ "name": "Julio Cortázar", "country": "Francia" })).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(dto["country"], "Francia");
+
+    let book = create(&client, "books", json!({ "title": "Rayuela", "author_id": author, "publication_date": "1963-06-28" })).await;
+    let (_, books) = get_json(&client, &format!("/api/v1/books?author_id={author}")).await;
+    assert_eq!(books.as_array().map(Vec::len), Some(1));
+    assert_eq!(books[0]["title"], "Rayuela");
+
+    let review = create(&client, "reviews", json!({ "book_id": book, "text": "Se puede leer en dos órdenes", "score": 5 })).await;
+    let (status, dto) = put_json(&client, &format!("/api/v1/reviews/{review}"), json!({ "book_id": book, "text": "Mejor en el segundo orden", "score": 4, "up_votes": 3 })).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(dto["score"], 4);
+    assert_eq!(get_html(&client, &format!("/books/avg/{book}")).await, "4.0000");
+
+    assert_eq!(delete(&client, &format!("/api/v1/reviews/{review}")).await, Status::NoContent);
+    assert_eq!(get_json(&client, &format!("/api/v1/reviews/{review}")).await.0, Status::NotFound);
+    assert_eq!(delete(&client, &format!("/api/v1/reviews/{review}")).await, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn invalid_input_is_rejected() {
+    let client = client().await;
+
+    let (status, _) = post_json(&client, "/api/v1/authors", json!({ "name": " " })).await;
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    let author = create(&client, "authors", json!({ "name": "Jorge Luis Borges" })).await;
+    let book = create(&client, "books", json!({ "title": "Ficciones", "author_id": author })).await;
+    let (status, _) = post_json(&client, "/api/v1/reviews", json!({ "book_id": book, "text": "Laberintos", "score": 9 })).await;
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    // el mismo rango de años que el formulario
+    let res = client
+        .post("/api/v1/sales")
+        .header(ContentType::JSON)
+        .body(json!({ "book_id": book, "year": 1800, "units": 10 }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::UnprocessableEntity);
+    assert_eq!(res.content_type().map(|c| c.to_string()).as_deref(), Some("application/problem+json"));
+    let problem: serde_json::Value = res.into_json().await.unwrap();
+    assert_eq!(problem["detail"], "year must be between 1900 and 2100");
+
+    // (book_id, year) es único
+    create(&client, "sales", json!({ "book_id": book, "year": 1944, "units": 10 })).await;
+    let sale = get_json(&client, &format!("/api/v1/sales?book_id={book}")).await.1[0]["id"].as_str().unwrap().to_string();
+    let (status, _) = put_json(&client, &format!("/api/v1/sales/{sale}"), json!({ "book_id": book, "year": 2101, "units": 10 })).await;
+    assert_eq!(status, Status::UnprocessableEntity);
+    let (status, _) = post_json(&client, "/api/v1/sales", json!({ "book_id": book, "year": 1944, "units": 20 })).await;
+    assert_eq!(status, Status::Conflict);
+}
+
+#[rocket::async_test]
+async fn deleting_an_author_cascades_to_books_reviews_and_sales() {
+    let client = client().await;
+
+    let author = create(&client, "authors", json!({ "name": "Gabriel García Márquez" })).await;
+    let other = create(&client, "authors", json!({ "name": "Juan Rulfo" })).await;
+    let book = create(&client, "books", json!({ "title": "Cien años de soledad", "author_id": author })).await;
+    let kept = create(&client, "books", json!({ "title": "Pedro Páramo", "author_id": other })).await;
+    for b in [&book, &kept] {
+        create(&client, "reviews", json!({ "book_id": b, "text": "Imprescindible", "score": 5 })).await;
+        create(&client, "sales", json!({ "book_id": b, "year": 2020, "units": 100 })).await;
+    }
+
+    assert_eq!(delete(&client, &format!("/api/v1/authors/{author}")).await, Status::NoContent);
+
+    assert_eq!(get_json(&client, &format!("/api/v1/authors/{author}")).await.0, Status::NotFound);
+    assert_eq!(get_json(&client, &format!("/api/v1/books/{book}")).await.0, Status::NotFound);
+    assert_eq!(get_json(&client, &format!("/api/v1/reviews?book_id={book}")).await.1, json!([]));
+    assert_eq!(get_json(&client, &format!("/api/v1/sales?book_id={book}")).await.1, json!([]));
+
+    // lo del otro autor sigue ahí
+    assert_eq!(get_json(&client, &format!("/api/v1/books/{kept}")).await.0, Status::Ok);
+    assert_eq!(get_json(&client, &format!("/api/v1/reviews?book_id={kept}")).await.1.as_array().map(Vec::len), Some(1));
+    assert_eq!(delete(&client, &format!("/api/v1/authors/{author}")).await, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn deleting_a_book_from_the_ui_cascades_to_reviews_and_sales() {
+    let client = client().await;
+
+    let author = create(&client, "authors", json!({ "name": "Juan Rulfo" })).await;
+    let book = create(&client, "books", json!({ "title": "Pedro Páramo", "author_id": author })).await;
+    create(&client, "reviews", json!({ "book_id": book, "text": "Comala", "score": 5 })).await;
+    create(&client, "sales", json!({ "book_id": book, "year": 1955, "units": 1000 })).await;
+
+    assert_eq!(post_form(&client, &format!("/books/delete/{book}"), "").await, Status::SeeOther);
+
+    assert_eq!(get_json(&client, &format!("/api/v1/books/{book}")).await.0, Status::NotFound);
+    assert_eq!(get_json(&client, &format!("/api/v1/reviews?book_id={book}")).await.1, json!([]));
+    assert_eq!(get_json(&client, &format!("/api/v1/sales?book_id={book}")).await.1, json!([]));
+    assert_eq!(get_json(&client, &format!("/api/v1/authors/{author}")).await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn sale_upserts_and_merges_keep_total_sales() {
+    let client = client().await;
+
+    let author = create(&client, "authors", json!({ "name": "Ernesto Sabato" })).await;
+    let tunnel = create(&client, "books", json!({ "title": "El túnel", "author_id": author })).await;
+    let heroes = create(&client, "books", json!({ "title": "Sobre héroes y tumbas", "author_id": author })).await;
+    let total = |book: String| {
+        let client = &client;
+        async move { get_json(client, &format!("/api/v1/books/{book}")).await.1["total_sales"].clone() }
+    };
+
+    // crear dos veces el mismo (libro, año) reemplaza las units
+    assert_eq!(post_form(&client, "/sales/create", &format!("book_id={tunnel}&year=2020&units=300")).await, Status::SeeOther);
+    assert_eq!(post_form(&client, "/sales/create", &format!("book_id={tunnel}&year=2020&units=500")).await, Status::SeeOther);
+    assert_eq!(post_form(&client, "/sales/create", &format!("book_id={tunnel}&year=2021&units=40")).await, Status::SeeOther);
+    assert_eq!(post_form(&client, "/sales/create", &format!("book_id={heroes}&year=2021&units=7")).await, Status::SeeOther);
+    assert_eq!(total(tunnel.clone()).await, 540);
+    assert_eq!(total(heroes.clone()).await, 7);
+
+    // mover la venta de 2021 al otro libro, que ya tiene 2021: se fusionan
+    let (_, sales) = get_json(&client, &format!("/api/v1/sales?book_id={tunnel}&year=2021")).await;
+    let sale = sales[0]["id"].as_str().unwrap().to_string();
+    assert_eq!(post_form(&client, &format!("/sales/update/{sale}"), &format!("book_id={heroes}&year=2021&units=45")).await, Status::SeeOther);
+
+    assert_eq!(get_json(&client, &format!("/api/v1/sales/{sale}")).await.0, Status::NotFound);
+    assert_eq!(total(tunnel).await, 500);
+    assert_eq!(total(heroes.clone()).await, 45);
+    assert_eq!(get_json(&client, &format!("/api/v1/sales?book_id={heroes}")).await.1.as_array().map(Vec::len), Some(1));
+}
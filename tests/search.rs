@@ -0,0 +1,194 @@
+// Búsqueda global (/search) y autocompletado contra MemoryRepo
+mod common;
+
+use bookreview::db;
+use bookreview::models::SearchHitKind;
+use bookreview::search::facets::FacetSelection;
+use bookreview::search::query::SearchMode;
+use rocket::local::asynchronous::Client;
+use serde_json::json;
+
+use common::*;
+
+struct Catalog {
+    cortazar: String,
+    rayuela: String,
+}
+
+async fn catalog(client: &Client) -> Catalog {
+    let cortazar = create(client, "authors", json!({ "name": "Julio Cortázar", "country": "Argentina", "description": "Cuentista y novelista" })).await;
+    let garcia = create(client, "authors", json!({ "name": "Gabriel García Márquez", "country": "Colombia", "description": "Novelista y periodista" })).await;
+
+    let rayuela = create(client, "books", json!({
+        "title": "Rayuela",
+        "author_id": cortazar,
+        "summary": "Una novela experimental sobre Horacio Oliveira en París y Buenos Aires",
+        "publication_date": "1963-06-28",
+    })).await;
+    let cien = create(client, "books", json!({
+        "title": "Cien años de soledad",
+        "author_id": garcia,
+        "summary": "La novela de la familia Buendía en Macondo",
+        "publication_date": "1967-05-30",
+    })).await;
+
+    create(client, "reviews", json!({ "book_id": rayuela, "text": "Una lectura exigente pero inolvidable", "score": 5, "up_votes": 2 })).await;
+    create(client, "reviews", json!({ "book_id": cien, "text": "Macondo no se olvida", "score": 4 })).await;
+
+    Catalog { cortazar, rayuela }
+}
+
+// Los resultados de un grupo de /search ("Libros", "Autores", "Reseñas")
+fn group<'a>(html: &'a str, heading: &str) -> Option<&'a str> {
+    let start = html.find(&format!("{heading}</h3>"))?;
+    let end = start + html[start..].find("<!-- Pagination -->")?;
+    Some(&html[start..end])
+}
+
+fn total(group: &str) -> usize {
+    group.matches("border-radius: 8px; padding: 16px").count()
+}
+
+#[rocket::async_test]
+async fn text_search_finds_books_authors_and_reviews() {
+    let client = client().await;
+    catalog(&client).await;
+
+    let html = get_html(&client, "/search?q=novela").await;
+    let books = group(&html, "Libros").expect("book results");
+    assert_eq!(total(books), 2);
+    assert!(books.contains("<mark>novela</mark>"));
+
+    let html = get_html(&client, "/search?q=macondo").await;
+    let books = group(&html, "Libros").expect("book results");
+    assert_eq!(total(books), 1);
+    assert!(books.contains("Cien años de soledad"));
+    let reviews = group(&html, "Reseñas").expect("review results");
+    assert!(reviews.contains("Macondo no se olvida"));
+    assert!(group(&html, "Autores").is_none());
+
+    // el país pesa en los autores
+    let html = get_html(&client, "/search?q=argentina").await;
+    let authors = group(&html, "Autores").expect("author results");
+    assert_eq!(total(authors), 1);
+    assert!(authors.contains("Julio Cortázar"));
+
+    // excluidos y frases
+    let html = get_html(&client, "/search?q=novela+-macondo").await;
+    assert_eq!(total(group(&html, "Libros").unwrap()), 1);
+    let html = get_html(&client, "/search?q=%22familia+buend%C3%ADa%22").await;
+    assert!(group(&html, "Libros").unwrap().contains("Cien años de soledad"));
+}
+
+#[rocket::async_test]
+async fn partial_and_fuzzy_matches() {
+    let client = client().await;
+    catalog(&client).await;
+
+    // palabra a medio escribir: $text no la ve, auto cae a regex
+    let html = get_html(&client, "/search?q=exper").await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    let html = get_html(&client, "/search?q=exper&mode=text").await;
+    assert!(group(&html, "Libros").is_none());
+    let html = get_html(&client, "/search?q=olvid&mode=prefix").await;
+    assert_eq!(total(group(&html, "Reseñas").unwrap()), 2);
+
+    // una letra de menos
+    let html = get_html(&client, "/search?q=rayula").await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    assert!(html.contains("Sin coincidencias exactas"));
+    // también en palabras largas ("Oliveira")
+    let html = get_html(&client, "/search?q=olivera").await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    assert!(html.contains("Sin coincidencias exactas"));
+
+    // una página absurda no desborda el cálculo del salto
+    let html = get_html(&client, &format!("/search?q=novela&page={}&reviews_page={}", i64::MAX, i64::MAX)).await;
+    let books = group(&html, "Libros").unwrap();
+    assert_eq!(total(books), 0);
+    assert!(books.contains("Página 1000 de 1"));
+
+    let html = get_html(&client, "/search?q=zzzz").await;
+    assert!(html.contains("No se encontraron libros, autores ni reseñas"));
+}
+
+#[rocket::async_test]
+async fn only_a_corrected_term_marks_results_as_fuzzy() {
+    let state = db::init_state(config(None)).await;
+    let search = |q: String| {
+        let state = &state;
+        async move { state.search(SearchHitKind::Book, &q, SearchMode::Auto, &FacetSelection::default(), 1, 10).await.unwrap() }
+    };
+
+    // demasiado largo para un patrón de typos: la búsqueda no cambia
+    assert!(!search("z".repeat(25)).await.fuzzy);
+    assert!(search("z".repeat(24)).await.fuzzy);
+}
+
+#[rocket::async_test]
+async fn facets_filter_and_count_books() {
+    let client = client().await;
+    let c = catalog(&client).await;
+
+    let html = get_html(&client, "/search?q=novela").await;
+    assert!(html.contains("Argentina (1)"));
+    assert!(html.contains("Colombia (1)"));
+    assert!(html.contains("1960s (2)"));
+
+    let html = get_html(&client, "/search?q=novela&country=Argentina").await;
+    let books = group(&html, "Libros").unwrap();
+    assert_eq!(total(books), 1);
+    assert!(books.contains("Rayuela"));
+    // el conteo de su propia faceta ignora el filtro
+    assert!(html.contains("Colombia (1)"));
+
+    let html = get_html(&client, &format!("/search?q=novela&author={}&score=4", c.cortazar)).await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    let html = get_html(&client, "/search?q=novela&year_from=1965&year_to=1969").await;
+    assert!(group(&html, "Libros").unwrap().contains("Cien años de soledad"));
+    let html = get_html(&client, "/search?q=novela&sales=top").await;
+    assert!(group(&html, "Libros").is_none());
+}
+
+#[rocket::async_test]
+async fn writes_show_up_in_search_and_suggestions() {
+    let client = client().await;
+    let c = catalog(&client).await;
+
+    let (_, suggestions) = get_json(&client, "/api/search/suggest?q=ray").await;
+    assert_eq!(suggestions["suggestions"][0]["text"], "Rayuela");
+
+    // la búsqueda está en cache: los cambios la invalidan
+    assert!(group(&get_html(&client, "/search?q=oliveira").await, "Libros").is_some());
+    assert_eq!(delete(&client, &format!("/api/v1/books/{}", c.rayuela)).await, rocket::http::Status::NoContent);
+    assert!(group(&get_html(&client, "/search?q=oliveira").await, "Libros").is_none());
+    assert!(group(&get_html(&client, "/search?q=inolvidable").await, "Reseñas").is_none());
+
+    create(&client, "books", json!({ "title": "Bestiario", "author_id": c.cortazar, "summary": "Cuentos" })).await;
+    assert!(group(&get_html(&client, "/search?q=cuentos").await, "Libros").unwrap().contains("Bestiario"));
+}
+
+#[rocket::async_test]
+async fn the_search_engine_honours_phrases_and_exclusions() {
+    let client = client_with_search(Some("memory://")).await;
+    catalog(&client).await;
+
+    // responde el motor: corrige el typo él mismo, sin el aviso del regex fuzzy
+    let html = get_html(&client, "/search?q=rayula").await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    assert!(!html.contains("Sin coincidencias exactas"));
+
+    let html = get_html(&client, "/search?q=novela+-macondo").await;
+    let books = group(&html, "Libros").unwrap();
+    assert_eq!(total(books), 1);
+    assert!(books.contains("Rayuela"));
+
+    let html = get_html(&client, "/search?q=%22novela+experimental%22").await;
+    assert!(group(&html, "Libros").unwrap().contains("Rayuela"));
+    // las dos palabras están, pero no como frase
+    let html = get_html(&client, "/search?q=%22experimental+novela%22&mode=text").await;
+    assert!(group(&html, "Libros").is_none());
+
+    let html = get_html(&client, "/search?q=%22lectura+exigente%22+-macondo").await;
+    assert_eq!(total(group(&html, "Reseñas").unwrap()), 1);
+}